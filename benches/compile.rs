@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::time::Instant;
+use trunk_template::parser::{eval_batch, Expr, T};
+
+const RECORDS: usize = 1_000_000;
+
+// Evaluates a formula over many records with the tree walker, the compiled program and
+// the batch evaluation, run with `cargo bench`.
+fn main() {
+    let expr = Expr::parse("3x^2 + 2x*y - y/(x + 1) + (x*y) % 7 - (x - y)^3").unwrap();
+    let records: Vec<(T, T)> = (0..RECORDS as T).map(|i| (i % 1000, i / 1000)).collect();
+
+    let start = Instant::now();
+    let mut variables = HashMap::new();
+    let tree_results: Vec<_> = records
+        .iter()
+        .map(|(x, y)| {
+            variables.insert("x".to_string(), *x);
+            variables.insert("y".to_string(), *y);
+            black_box(&expr).eval_with(&variables)
+        })
+        .collect();
+    let tree_time = start.elapsed();
+
+    let start = Instant::now();
+    let program = expr.compile();
+    let (x, y) = (program.slot("x").unwrap(), program.slot("y").unwrap());
+    let mut values = [0; 2];
+    let compiled_results: Vec<_> = records
+        .iter()
+        .map(|record| {
+            values[x] = record.0;
+            values[y] = record.1;
+            black_box(&program).eval(&values)
+        })
+        .collect();
+    let compiled_time = start.elapsed();
+
+    let start = Instant::now();
+    let xs: Vec<T> = records.iter().map(|record| record.0).collect();
+    let ys: Vec<T> = records.iter().map(|record| record.1).collect();
+    let batch_results = eval_batch(black_box(&expr), &[("x", &xs), ("y", &ys)]);
+    let batch_time = start.elapsed();
+
+    assert_eq!(tree_results, compiled_results);
+    assert_eq!(tree_results, batch_results);
+    println!("{RECORDS} records");
+    println!("Expr::eval_with: {tree_time:?}");
+    println!("Program::eval:   {compiled_time:?}");
+    println!("eval_batch:      {batch_time:?}");
+}
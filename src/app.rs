@@ -2,12 +2,23 @@ use yew::prelude::*;
 use yew_hooks::prelude::*;
 
 use crate::button::Button;
-use crate::parser::{parse, OPERATORS_PRECEDENCE, T};
+use trunk_template::parser::{solve, Complex, Function, Number, Session, T};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Calc,
+    Complex,
+    Solve,
+}
 
 #[function_component(App)]
 pub fn app() -> Html {
     let value_state = use_state(|| "".to_string());
     let allow_input = use_state(|| true);
+    let mode = use_state(|| Mode::Calc);
+    // Keep the assigned variables between computations, each mode has its numbers
+    let session = use_mut_ref(Session::<T>::new);
+    let complex_session = use_mut_ref(Session::<Complex>::new);
 
     // Define callbacks
     let add_to_state = {
@@ -53,19 +64,56 @@ pub fn app() -> Html {
         })
     };
 
+    let toggle_mode = {
+        let mode = mode.clone();
+        Callback::from(move |_: String| {
+            mode.set(match *mode {
+                Mode::Calc => Mode::Complex,
+                Mode::Complex => Mode::Solve,
+                Mode::Solve => Mode::Calc,
+            })
+        })
+    };
+
     let compute_result = {
         let value_state = value_state.clone();
         let allow_input = allow_input.clone();
+        let mode = mode.clone();
+        let session = session.clone();
+        let complex_session = complex_session.clone();
         Callback::from(move |_| {
             if !*allow_input {
                 return;
             }
 
             let str_value = (*value_state).clone();
-            let result = parse(&str_value);
+
+            // "=" after the name of a new variable starts an assignment, after the name and
+            // the parameters of a new function it starts its definition
+            let is_head = match *mode {
+                Mode::Calc => is_definition_head(&str_value, &session.borrow()),
+                Mode::Complex => is_definition_head(&str_value, &complex_session.borrow()),
+                Mode::Solve => false,
+            };
+            if is_head {
+                value_state.set(str_value + "=");
+                return;
+            }
+
+            // In solve mode, the first press of "=" separates both sides of the equation
+            if *mode == Mode::Solve && !str_value.contains('=') {
+                value_state.set(str_value + "=");
+                return;
+            }
+
+            let result = match *mode {
+                Mode::Solve => solve(&str_value).map(|solution| solution.to_string()),
+                Mode::Calc => run(&mut session.borrow_mut(), &str_value),
+                Mode::Complex => run(&mut complex_session.borrow_mut(), &str_value),
+            };
 
             let str_result = match result {
-                Ok(expr) => format!("{:?}", expr),
+                Ok(result) => result,
                 Err(err) => {
                     allow_input.set(false);
                     err
@@ -81,16 +129,22 @@ pub fn app() -> Html {
         let add_to_state = add_to_state.clone();
         let clear_entry = clear_entry.clone();
         let compute_result = compute_result.clone();
+        let session = session.clone();
         use_event_with_window("keyup", move |event: KeyboardEvent| {
             event.prevent_default();
             event.stop_immediate_propagation();
             let key = event.key();
             match key.as_str() {
-                "Enter" => compute_result.emit("".to_string()),
+                "Enter" | "=" => compute_result.emit("".to_string()),
                 "Backspace" => clear_entry.emit("".to_string()),
                 key if key.parse::<T>().is_ok() => add_to_state.emit(key.to_string()),
-                key if OPERATORS_PRECEDENCE.contains_key(key) => add_to_state.emit(key.to_string()),
-                "(" | ")" => add_to_state.emit(key.to_string()),
+                key if session.borrow().config().is_operator(key) => {
+                    add_to_state.emit(key.to_string())
+                }
+                "(" | ")" | "[" | "]" | "," | ";" | ">" | "." => add_to_state.emit(key.to_string()),
+                key if key.len() == 1 && key.starts_with(char::is_alphabetic) => {
+                    add_to_state.emit(key.to_string())
+                }
                 _ => {}
             }
         })
@@ -106,6 +160,7 @@ pub fn app() -> Html {
 
     // Build the calculator grid
     let buttons_grid = vec![
+        vec!["x", "i", ",", "mode"],
         vec!["(", ")", "%", "CE"], 
         vec!["7", "8", "9", "/"], 
         vec!["4", "5", "6", "*"], 
@@ -117,6 +172,14 @@ pub fn app() -> Html {
         let btns_html: Vec<_> = row.iter().map(|btn| {
             if *btn == "=" {
                 html! { <Button value="" text={*btn} on_click={compute_result.clone()} /> }
+            } else if *btn == "mode" {
+                // Name of the next mode
+                let text = match *mode {
+                    Mode::Calc => "Cplx",
+                    Mode::Complex => "Solve",
+                    Mode::Solve => "Calc",
+                };
+                html! { <Button value="" text={text} on_click={toggle_mode.clone()} class={ "mode" } /> }
             } else if *btn == "CE" {
                 html! { <Button value="" text={*btn} on_click={clear_entry.clone()} class={ "reset" } />}
             } else {
@@ -127,17 +190,69 @@ pub fn app() -> Html {
     })
     .collect::<Vec<_>>();
 
+    let functions: Vec<_> = match *mode {
+        Mode::Complex => complex_session
+            .borrow()
+            .functions()
+            .map(|function| function.to_string())
+            .collect(),
+        _ => session
+            .borrow()
+            .functions()
+            .map(|function| function.to_string())
+            .collect(),
+    };
+    let functions: Vec<_> = functions
+        .into_iter()
+        .map(|function| html! { <li>{ function }</li> })
+        .collect();
+
     // Render HTML
     html! {
-    <table id="calculator">
-        <tr>
-            <td colspan="3">
-                <input type="text" id="result" value={ (*value_state).clone() } class={ if *allow_input { classes!("") } else { classes!("error")} }/>
-            </td>
-            <Button value="" text="C" on_click={clear} class={ "reset" } />
-        </tr>
-
-        { buttons_grid }
-    </table>
+    <div id="app">
+        <table id="calculator">
+            <tr>
+                <td colspan="3">
+                    <input type="text" id="result" value={ (*value_state).clone() } class={ if *allow_input { classes!("") } else { classes!("error")} }/>
+                </td>
+                <Button value="" text="C" on_click={clear} class={ "reset" } />
+            </tr>
+
+            { buttons_grid }
+        </table>
+        if !functions.is_empty() {
+            <ul id="functions">{ for functions }</ul>
+        }
+    </div>
+    }
+}
+
+fn is_name(text: &str) -> bool {
+    text.starts_with(char::is_alphabetic) && text.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+// Result of a statement, complex numbers being displayed as a + bi
+fn run<N: Number>(session: &mut Session<N>, input: &str) -> Result<String, String> {
+    session.run(input).map(|result| match result {
+        Some(result) => result.to_string(),
+        None => "".to_string(),
+    })
+}
+
+// `name` for a variable or `name(param, ...)` for a function which isn't defined yet, the
+// built-in functions and constants such as `i` being evaluated
+fn is_definition_head<N: Number>(text: &str, session: &Session<N>) -> bool {
+    if is_name(text) {
+        return !Function::is_builtin(text)
+            && session.get(text).is_none()
+            && N::constant(text).is_none();
     }
+
+    let Some((name, params)) = text.strip_suffix(')').and_then(|text| text.split_once('(')) else {
+        return false;
+    };
+    is_name(name)
+        && !Function::is_builtin(name)
+        && session.function(name).is_none()
+        && (params.is_empty() || params.split(',').all(|param| is_name(param.trim())))
 }
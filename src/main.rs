@@ -1,6 +1,5 @@
 mod app;
 mod button;
-mod parser;
 
 use app::App;
 
@@ -0,0 +1,244 @@
+use super::expr::{BinaryOp, Expr};
+use super::number::Number;
+use std::collections::{BTreeMap, HashMap};
+
+// Values of a node of the expression for every row
+enum Column<'a, N> {
+    // Same value on every row
+    Constant(Result<N, String>),
+    Input(&'a str, &'a [N]),
+    // Errors are rare, they are kept apart so that values stay packed
+    Values {
+        values: Vec<N>,
+        errors: BTreeMap<usize, String>,
+    },
+}
+
+impl<N: Number> Column<'_, N> {
+    fn get(&self, row: usize) -> Result<N, String> {
+        match self {
+            Column::Constant(value) => value.clone(),
+            Column::Input(name, values) => values
+                .get(row)
+                .copied()
+                .ok_or_else(|| format!("Missing value of {name} in row {row}")),
+            Column::Values { values, errors } => match errors.get(&row) {
+                Some(error) => Err(error.clone()),
+                None => Ok(values[row]),
+            },
+        }
+    }
+
+    // All the values, if none of them is missing or an error
+    fn values(&self, rows: usize) -> Option<&[N]> {
+        match self {
+            Column::Input(_, values) if values.len() >= rows => Some(&values[..rows]),
+            Column::Values { values, errors } if errors.is_empty() => Some(values),
+            _ => None,
+        }
+    }
+
+    fn collect(rows: usize, f: impl Fn(usize) -> Result<N, String>) -> Self {
+        let mut values = Vec::with_capacity(rows);
+        let mut errors = BTreeMap::new();
+        for row in 0..rows {
+            match f(row) {
+                Ok(value) => values.push(value),
+                Err(error) => {
+                    values.push(N::from_litteral(0));
+                    errors.insert(row, error);
+                }
+            }
+        }
+        Column::Values { values, errors }
+    }
+}
+
+struct Batch<'a, N> {
+    columns: HashMap<&'a str, &'a [N]>,
+    rows: usize,
+}
+
+enum Visit<'e, N> {
+    Node(&'e Expr),
+    Apply(BinaryOp<N>),
+}
+
+impl<'a, N: Number> Batch<'a, N> {
+    // The tree is walked with an explicit stack so that deep expressions don't overflow
+    // the call stack
+    fn eval(&self, expr: &Expr) -> Column<'a, N> {
+        let mut visits = vec![Visit::Node(expr)];
+        let mut columns: Vec<Column<'a, N>> = vec![];
+
+        while let Some(visit) = visits.pop() {
+            let expr = match visit {
+                Visit::Apply(op) => {
+                    let right = columns.pop().unwrap();
+                    let left = columns.pop().unwrap();
+                    columns.push(self.apply(left, right, op));
+                    continue;
+                }
+                Visit::Node(expr) => expr,
+            };
+
+            let (left, right, op): (_, _, BinaryOp<N>) = match expr {
+                Expr::Litteral(value) => {
+                    columns.push(Column::Constant(Ok(N::from_litteral(*value))));
+                    continue;
+                }
+                Expr::Variable(name) => {
+                    columns.push(match self.columns.get_key_value(name.as_str()) {
+                        Some((name, values)) => Column::Input(name, values),
+                        None => Column::Constant(
+                            N::constant(name).ok_or_else(|| format!("Unknown variable {name}")),
+                        ),
+                    });
+                    continue;
+                }
+                Expr::Call(name, args) if name == "decimal" => {
+                    columns.push(Column::Constant(Expr::eval_decimal(args)));
+                    continue;
+                }
+                Expr::Call(..) | Expr::Lambda(..) | Expr::List(..) | Expr::Index(..) => {
+                    columns.push(self.eval_rows(expr));
+                    continue;
+                }
+                Expr::Add(left, right) => (left, right, N::add),
+                Expr::Sub(left, right) => (left, right, N::sub),
+                Expr::Prod(left, right) => (left, right, N::mul),
+                Expr::Div(left, right) => (left, right, N::div),
+                Expr::Mod(left, right) => (left, right, N::rem),
+                Expr::Pow(left, right) => (left, right, N::pow),
+            };
+            visits.extend([Visit::Apply(op), Visit::Node(right), Visit::Node(left)]);
+        }
+
+        columns.pop().unwrap()
+    }
+
+    fn apply(&self, left: Column<'a, N>, right: Column<'a, N>, op: BinaryOp<N>) -> Column<'a, N> {
+        if let (Column::Constant(_), Column::Constant(_)) = (&left, &right) {
+            return Column::Constant(left.get(0).and_then(|l| op(l, right.get(0)?)));
+        }
+
+        // The left operand is evaluated first, so its error wins like in `eval_with`
+        match (left.values(self.rows), right.values(self.rows)) {
+            (Some(left), Some(right)) => {
+                Column::collect(self.rows, |row| op(left[row], right[row]))
+            }
+            _ => Column::collect(self.rows, |row| op(left.get(row)?, right.get(row)?)),
+        }
+    }
+
+    // Calls need some of their arguments unevaluated, they are evaluated row by row like
+    // the values which aren't numbers
+    fn eval_rows(&self, expr: &Expr) -> Column<'a, N> {
+        let inputs: Vec<(String, Column<N>)> = expr
+            .free_variables()
+            .into_iter()
+            .filter_map(|name| {
+                let (key, values) = self.columns.get_key_value(name.as_str())?;
+                Some((name, Column::Input(key, values)))
+            })
+            .collect();
+
+        Column::collect(self.rows, |row| {
+            let variables = inputs
+                .iter()
+                .map(|(name, column)| Ok((name.clone(), column.get(row)?)))
+                .collect::<Result<HashMap<_, _>, String>>()?;
+            expr.eval_with(&variables)
+        })
+    }
+}
+
+// Evaluates `expr` once per row, the i-th row binding each variable to the i-th value of
+// its column. Each node of the expression is evaluated for all the rows at once.
+pub fn eval_batch<N: Number>(expr: &Expr, columns: &[(&str, &[N])]) -> Vec<Result<N, String>> {
+    let rows = columns
+        .iter()
+        .map(|(_, values)| values.len())
+        .max()
+        .unwrap_or(1);
+    let batch = Batch {
+        columns: columns.iter().copied().collect(),
+        rows,
+    };
+
+    let column = batch.eval(expr);
+    (0..rows).map(|row| column.get(row)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Limits, ParserConfig, T};
+
+    const DEPTH: usize = 300_000;
+
+    #[test]
+    fn same_as_eval() {
+        let expr = Expr::parse("x^2 - 3x*y + 10 / (y - 2) + 7 % x").unwrap();
+        let xs: Vec<T> = vec![1, 2, 3, 4, 5, 6];
+        let ys: Vec<T> = vec![2, 3, 4, 0, 2, -5];
+
+        let results = eval_batch(&expr, &[("x", &xs), ("y", &ys)]);
+        assert_eq!(results.len(), xs.len());
+        for ((x, y), result) in xs.iter().zip(&ys).zip(results) {
+            let variables = HashMap::from([("x".to_string(), *x), ("y".to_string(), *y)]);
+            assert_eq!(result, expr.eval_with(&variables));
+        }
+    }
+
+    #[test]
+    fn calls() {
+        let expr = Expr::parse("integrate(a*t, t, 0, x) + 1").unwrap();
+        let xs = [0.0, 1.0, 2.0];
+        let a = [2.0, 2.0, 4.0];
+
+        let results = eval_batch(&expr, &[("x", &xs), ("a", &a)]);
+        assert_eq!(results, vec![Ok(1.0), Ok(2.0), Ok(9.0)]);
+    }
+
+    #[test]
+    fn constants_and_errors() {
+        let expr = Expr::parse("2 + 3").unwrap();
+        assert_eq!(
+            eval_batch::<T>(&expr, &[("x", &[1, 2])]),
+            vec![Ok(5), Ok(5)]
+        );
+        assert_eq!(eval_batch::<T>(&expr, &[]), vec![Ok(5)]);
+
+        let expr = Expr::parse("x + y").unwrap();
+        let results = eval_batch::<T>(&expr, &[("x", &[1, 2]), ("y", &[1])]);
+        assert_eq!(results[0], Ok(2));
+        assert!(results[1].is_err());
+
+        let expr = Expr::parse("1 / 0 + z").unwrap();
+        let results = eval_batch::<T>(&expr, &[("x", &[1, 2])]);
+        assert_eq!(results, vec![expr.eval(), expr.eval()]);
+
+        let expr = Expr::parse("x * 2.5").unwrap();
+        let results = eval_batch::<T>(&expr, &[("x", &[1, 2])]);
+        assert_eq!(
+            results,
+            vec![expr.eval_with(&HashMap::from([("x".to_string(), 1)])); 2]
+        );
+        assert_eq!(
+            eval_batch(&expr, &[("x", &[1.0, 2.0])]),
+            vec![Ok(2.5), Ok(5.0)]
+        );
+    }
+
+    #[test]
+    fn deep_eval() {
+        let input = vec!["x"; DEPTH].join("+");
+        let config = ParserConfig::default().limits(Limits::unlimited());
+        let expr = config.parse(&input).unwrap();
+        assert_eq!(
+            eval_batch::<T>(&expr, &[("x", &[1, 2])]),
+            vec![Ok(DEPTH as T), Ok(2 * DEPTH as T)]
+        );
+    }
+}
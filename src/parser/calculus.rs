@@ -0,0 +1,211 @@
+use super::context::Context;
+use super::expr::Expr;
+use super::number::Number;
+use std::collections::HashMap;
+
+const INTEGRATION_TOLERANCE: f64 = 1e-10;
+const INTEGRATION_MAX_DEPTH: usize = 50;
+const INTEGRATION_MAX_EVALUATIONS: usize = 100_000;
+
+// Evaluates `body` with `var` bound to a floating point value, on top of the variables
+// already in scope.
+struct BoundExpr<'a, 'f> {
+    body: &'a Expr,
+    var: &'a str,
+    variables: HashMap<String, f64>,
+    evaluations: usize,
+    context: &'a mut Context<'f, f64>,
+}
+
+impl<'a, 'f> BoundExpr<'a, 'f> {
+    fn new<N: Number>(
+        body: &'a Expr,
+        var: &'a str,
+        variables: &HashMap<String, N>,
+        context: &'a mut Context<'f, f64>,
+    ) -> Self {
+        let variables = variables
+            .iter()
+            .map(|(name, value)| (name.clone(), value.to_f64()))
+            .collect();
+
+        Self {
+            body,
+            var,
+            variables,
+            evaluations: 0,
+            context,
+        }
+    }
+
+    fn at(&mut self, x: f64) -> Result<f64, String> {
+        self.evaluations += 1;
+        if self.evaluations > INTEGRATION_MAX_EVALUATIONS {
+            return Err(format!(
+                "Unable to evaluate {:?} with enough precision",
+                self.body
+            ));
+        }
+
+        self.variables.insert(self.var.to_string(), x);
+        self.body.eval_in(&self.variables, self.context)
+    }
+}
+
+fn simpson(a: f64, b: f64, fa: f64, fm: f64, fb: f64) -> f64 {
+    (b - a) / 6.0 * (fa + 4.0 * fm + fb)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn adaptive_simpson(
+    f: &mut BoundExpr,
+    a: f64,
+    b: f64,
+    fa: f64,
+    fm: f64,
+    fb: f64,
+    whole: f64,
+    tolerance: f64,
+    depth: usize,
+) -> Result<f64, String> {
+    let m = (a + b) / 2.0;
+    let flm = f.at((a + m) / 2.0)?;
+    let frm = f.at((m + b) / 2.0)?;
+    let left = simpson(a, m, fa, flm, fm);
+    let right = simpson(m, b, fm, frm, fb);
+    let delta = left + right - whole;
+
+    if depth == 0 || delta.abs() <= 15.0 * tolerance {
+        return Ok(left + right + delta / 15.0);
+    }
+
+    Ok(
+        adaptive_simpson(f, a, m, fa, flm, fm, left, tolerance / 2.0, depth - 1)?
+            + adaptive_simpson(f, m, b, fm, frm, fb, right, tolerance / 2.0, depth - 1)?,
+    )
+}
+
+// Adaptive Simpson quadrature of `body` over `var` from `a` to `b`.
+pub fn integrate<N: Number>(
+    body: &Expr,
+    var: &str,
+    a: f64,
+    b: f64,
+    variables: &HashMap<String, N>,
+    context: &mut Context<N>,
+) -> Result<f64, String> {
+    if a == b {
+        return Ok(0.0);
+    }
+
+    let mut floats = context.floats();
+    let result = integrate_floats(BoundExpr::new(body, var, variables, &mut floats), a, b);
+    context.resume(floats);
+    result
+}
+
+fn integrate_floats(mut f: BoundExpr, a: f64, b: f64) -> Result<f64, String> {
+    let fa = f.at(a)?;
+    let fm = f.at((a + b) / 2.0)?;
+    let fb = f.at(b)?;
+    let whole = simpson(a, b, fa, fm, fb);
+
+    adaptive_simpson(
+        &mut f,
+        a,
+        b,
+        fa,
+        fm,
+        fb,
+        whole,
+        INTEGRATION_TOLERANCE,
+        INTEGRATION_MAX_DEPTH,
+    )
+}
+
+// Five-point central difference of `body` with respect to `var` at `at`.
+pub fn diff<N: Number>(
+    body: &Expr,
+    var: &str,
+    at: f64,
+    variables: &HashMap<String, N>,
+    context: &mut Context<N>,
+) -> Result<f64, String> {
+    let mut floats = context.floats();
+    let result = diff_floats(BoundExpr::new(body, var, variables, &mut floats), at);
+    context.resume(floats);
+    result
+}
+
+fn diff_floats(mut f: BoundExpr, at: f64) -> Result<f64, String> {
+    let h = 1e-3 * at.abs().max(1.0);
+
+    let (f_2h, f_h) = (f.at(at + 2.0 * h)?, f.at(at + h)?);
+    let (f_minus_h, f_minus_2h) = (f.at(at - h)?, f.at(at - 2.0 * h)?);
+
+    Ok((-f_2h + 8.0 * f_h - 8.0 * f_minus_h + f_minus_2h) / (12.0 * h))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval_f64(input: &str) -> f64 {
+        Expr::parse(input)
+            .unwrap()
+            .eval_with::<f64>(&HashMap::new())
+            .unwrap()
+    }
+
+    #[test]
+    fn integrate_polynomial() {
+        let result = eval_f64("integrate(x^2, x, 0, 3)");
+        assert!((result - 9.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn integrate_reversed_bounds() {
+        let result = eval_f64("integrate(x, x, 2, 0)");
+        assert!((result + 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn integrate_uses_outer_variables() {
+        let mut variables = HashMap::new();
+        variables.insert("a".to_string(), 2.0);
+        let expr = Expr::parse("integrate(a*x, x, 0, 1)").unwrap();
+        let result = expr.eval_with::<f64>(&variables).unwrap();
+        assert!((result - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn diff_polynomial() {
+        let result = eval_f64("diff(x^3, x, 2)");
+        assert!((result - 12.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn nested_calculus() {
+        let result = eval_f64("integrate(diff(y^2, y, x), x, 0, 1)");
+        assert!((result - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn integer_result() {
+        let expr = Expr::parse("integrate(x^2, x, 0, 3) + diff(x^2, x, 3)").unwrap();
+        assert_eq!(expr.eval().unwrap(), 15);
+    }
+
+    #[test]
+    fn errors() {
+        assert!(Expr::parse("integrate(x, 2, 0, 1)")
+            .unwrap()
+            .eval()
+            .is_err());
+        assert!(Expr::parse("integrate(x, x, 0)").unwrap().eval().is_err());
+        assert!(Expr::parse("integrate(1/x, x, 0, 1)")
+            .unwrap()
+            .eval()
+            .is_err());
+    }
+}
@@ -0,0 +1,319 @@
+use super::context::Context;
+use super::expr::Expr;
+use super::limits::Limits;
+use super::number::Number;
+use super::T;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+enum Instruction {
+    Push(T),
+    // Decimal litteral with its digits and its number of decimals
+    Decimal(T, u32),
+    Load(usize),
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+    // Calls need some of their arguments unevaluated, they go through `Expr::eval_with`
+    // like the lambdas and lists, which aren't numbers
+    Call(Expr),
+}
+
+enum Emit<'a> {
+    Node(&'a Expr),
+    Instruction(Instruction),
+}
+
+// Flat stack machine version of an expression, its variables are resolved to slots
+// given in the order of `variables()`.
+#[derive(Debug, Clone)]
+pub struct Program {
+    instructions: Vec<Instruction>,
+    // Evaluation steps taken before each instruction, counted like the tree walker which
+    // counts a node before its operands
+    steps: Vec<usize>,
+    variables: Vec<String>,
+    stack_size: usize,
+}
+
+impl Expr {
+    pub fn compile(&self) -> Program {
+        let mut program = Program {
+            instructions: vec![],
+            steps: vec![],
+            variables: vec![],
+            stack_size: 0,
+        };
+        program.stack_size = program.emit(self);
+        program
+    }
+}
+
+impl Program {
+    // Returns the stack size needed to evaluate `expr`, the tree is walked with an
+    // explicit stack so that deep expressions don't overflow the call stack
+    fn emit(&mut self, expr: &Expr) -> usize {
+        let mut emits = vec![Emit::Node(expr)];
+        let mut sizes: Vec<usize> = vec![];
+        let mut opened = 0;
+
+        while let Some(emit) = emits.pop() {
+            let expr = match emit {
+                Emit::Instruction(instruction) => {
+                    let right_size = sizes.pop().unwrap();
+                    let left_size = sizes.pop().unwrap();
+                    sizes.push(left_size.max(right_size + 1));
+                    self.push(instruction, 0);
+                    continue;
+                }
+                Emit::Node(expr) => expr,
+            };
+
+            // Decimal litterals are built without evaluating a call
+            let decimal = match expr {
+                Expr::Call(name, args) if name == "decimal" => Expr::decimal_parts(args).ok(),
+                _ => None,
+            };
+            if let Some((digits, scale)) = decimal {
+                self.push(Instruction::Decimal(digits, scale), opened + 1);
+                opened = 0;
+                sizes.push(1);
+                continue;
+            }
+
+            let (left, right, instruction) = match expr {
+                Expr::Litteral(value) => {
+                    self.push(Instruction::Push(*value), opened + 1);
+                    opened = 0;
+                    sizes.push(1);
+                    continue;
+                }
+                Expr::Variable(name) => {
+                    let slot = self.slot(name).unwrap_or_else(|| {
+                        self.variables.push(name.clone());
+                        self.variables.len() - 1
+                    });
+                    self.push(Instruction::Load(slot), opened + 1);
+                    opened = 0;
+                    sizes.push(1);
+                    continue;
+                }
+                Expr::Call(..) | Expr::Lambda(..) | Expr::List(..) | Expr::Index(..) => {
+                    // Every variable of the program is given to the call, so they need a slot
+                    for name in expr.free_variables() {
+                        if self.slot(&name).is_none() {
+                            self.variables.push(name);
+                        }
+                    }
+                    // The call counts its own step when it is evaluated
+                    self.push(Instruction::Call(expr.clone()), opened);
+                    opened = 0;
+                    sizes.push(1);
+                    continue;
+                }
+                Expr::Add(left, right) => (left, right, Instruction::Add),
+                Expr::Sub(left, right) => (left, right, Instruction::Sub),
+                Expr::Prod(left, right) => (left, right, Instruction::Mul),
+                Expr::Div(left, right) => (left, right, Instruction::Div),
+                Expr::Mod(left, right) => (left, right, Instruction::Rem),
+                Expr::Pow(left, right) => (left, right, Instruction::Pow),
+            };
+            opened += 1;
+            emits.extend([
+                Emit::Instruction(instruction),
+                Emit::Node(right),
+                Emit::Node(left),
+            ]);
+        }
+
+        sizes.pop().unwrap()
+    }
+
+    fn push(&mut self, instruction: Instruction, steps: usize) {
+        self.instructions.push(instruction);
+        self.steps.push(steps);
+    }
+
+    pub fn variables(&self) -> &[String] {
+        &self.variables
+    }
+
+    pub fn slot(&self, name: &str) -> Option<usize> {
+        self.variables.iter().position(|variable| variable == name)
+    }
+
+    // Evaluates the program with values[i] bound to variables()[i]. The results are
+    // the same as the ones of `Expr::eval_with`: the variables without a value, at the end
+    // of `variables()`, are the constants of the backend such as `i` for `Complex`.
+    pub fn eval<N: Number>(&self, values: &[N]) -> Result<N, String> {
+        self.eval_limited(values, &Limits::default())
+    }
+
+    // Same as `eval`, with the results and errors of `Expr::eval_limited`
+    pub fn eval_limited<N: Number>(&self, values: &[N], limits: &Limits) -> Result<N, String> {
+        if values.len() > self.variables.len() {
+            return Err(format!(
+                "Expected {} values ({}), got {}",
+                self.variables.len(),
+                self.variables.join(", "),
+                values.len()
+            ));
+        }
+
+        let mut context = Context::new(limits);
+        let mut stack: Vec<N> = Vec::with_capacity(self.stack_size);
+        for (instruction, steps) in self.instructions.iter().zip(&self.steps) {
+            for _ in 0..*steps {
+                context.step()?;
+            }
+            let value = match instruction {
+                Instruction::Push(value) => N::from_litteral(*value),
+                Instruction::Decimal(digits, scale) => N::from_decimal(*digits, *scale)?,
+                Instruction::Load(slot) => match values.get(*slot) {
+                    Some(value) => *value,
+                    None => {
+                        let name = &self.variables[*slot];
+                        N::constant(name).ok_or_else(|| format!("Unknown variable {name}"))?
+                    }
+                },
+                Instruction::Call(expr) => {
+                    let variables: HashMap<String, N> = self
+                        .variables
+                        .iter()
+                        .cloned()
+                        .zip(values.iter().copied())
+                        .collect();
+                    expr.eval_in(&variables, &mut context)?
+                }
+                binary => {
+                    let right = stack.pop().unwrap();
+                    let left = stack.pop().unwrap();
+                    match binary {
+                        Instruction::Add => left.add(right)?,
+                        Instruction::Sub => left.sub(right)?,
+                        Instruction::Mul => left.mul(right)?,
+                        Instruction::Div => left.div(right)?,
+                        Instruction::Rem => left.rem(right)?,
+                        Instruction::Pow => left.pow(right)?,
+                        _ => unreachable!(),
+                    }
+                }
+            };
+            stack.push(value);
+        }
+
+        Ok(stack.pop().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Complex, Money, ParserConfig, Quantity, Time};
+
+    fn compare<N: Number>(input: &str, values: &[(&str, N)]) {
+        let expr = Expr::parse(input).unwrap();
+        let program = expr.compile();
+
+        let variables: HashMap<String, N> = values
+            .iter()
+            .map(|(name, value)| (name.to_string(), *value))
+            .collect();
+        // The other variables are constants, which are left out after the last value
+        let last = program
+            .variables()
+            .iter()
+            .rposition(|name| variables.contains_key(name));
+        let slots: Vec<N> = program.variables()[..last.map_or(0, |last| last + 1)]
+            .iter()
+            .map(|name| variables.get(name).copied().or_else(|| N::constant(name)))
+            .collect::<Option<_>>()
+            .unwrap();
+
+        assert_eq!(program.eval(&slots), expr.eval_with(&variables));
+        for max_eval_steps in 0..40 {
+            let limits = Limits {
+                max_eval_steps,
+                ..Limits::default()
+            };
+            assert_eq!(
+                program.eval_limited(&slots, &limits),
+                expr.eval_limited(&variables, &limits)
+            );
+        }
+    }
+
+    #[test]
+    fn same_results() {
+        compare::<T>("(2) + (17*2-30) * (5)+2 - (8/2)*4", &[]);
+        compare::<T>("2^3^2 - 7 % 3", &[]);
+        compare::<T>("3x^2 + 2x*y - y/(x + 1)", &[("x", 4), ("y", -9)]);
+        compare::<f64>("3x^2 + 2x*y - y/(x + 1)", &[("x", 0.3), ("y", -9.1)]);
+        compare::<f64>("x % 2 + x^y", &[("x", 2.5), ("y", 0.5)]);
+        compare::<f64>("integrate(a*t^2, t, 0, x) + x", &[("x", 1.5), ("a", 2.0)]);
+        compare::<f64>("2.5 * x + 0.125", &[("x", 3.0)]);
+        compare::<T>("2.0 * x + 1.50 * 2", &[("x", 3)]);
+    }
+
+    #[test]
+    fn constants() {
+        let hours = |value| Time::Duration(value * 3600.0);
+        compare::<Complex>("3 + 4i", &[]);
+        compare::<Complex>("x * i + i^2", &[("x", Complex::new(2.0, 1.0))]);
+        compare::<Quantity>("10 m / 2 s + x", &[("x", Quantity::from_litteral(1))]);
+        compare::<Quantity>("x * 2 km to m", &[("x", Quantity::from_litteral(3))]);
+        compare::<Money>("2.50 EUR * x + $1", &[("x", Money::from_litteral(2))]);
+        compare::<Time>("3h 20min + x * 2 h", &[("x", Time::Number(2.0))]);
+        compare::<Time>("x + 1 day", &[("x", hours(1.0))]);
+        // Unknown without a value
+        compare::<Complex>("x * i", &[]);
+        compare::<T>("x * i", &[("x", 2)]);
+    }
+
+    #[test]
+    fn same_errors() {
+        compare::<T>("1 / (x - 2) + 1 / 0", &[("x", 2)]);
+        compare::<T>("0^x", &[("x", 0)]);
+        compare::<T>("x + 2.5", &[("x", 1)]);
+        compare::<f64>("(-8)^x", &[("x", 0.5)]);
+        compare::<T>("1 / 0 + x * (2 - sum(i, 1, 5, i / (x - 3)))", &[("x", 3)]);
+    }
+
+    #[test]
+    fn steps() {
+        let expr = Expr::parse("sum(i, 1, 1000, i * x)").unwrap();
+        let program = expr.compile();
+        let variables = HashMap::from([("x".to_string(), 2)]);
+        let limits = Limits {
+            max_eval_steps: 100,
+            ..Limits::default()
+        };
+
+        let error = program.eval_limited(&[2], &limits).unwrap_err();
+        assert_eq!(error, "Evaluation takes too many steps (the limit is 100)");
+        assert_eq!(Err(error), expr.eval_limited(&variables, &limits));
+        assert_eq!(program.eval(&[2]), Ok(1001000));
+    }
+
+    #[test]
+    fn deep() {
+        let input = vec!["x"; 300_000].join("+");
+        let config = ParserConfig::default().limits(Limits::unlimited());
+        let program = config.parse(&input).unwrap().compile();
+        assert_eq!(
+            program.eval_limited(&[1], &Limits::unlimited()),
+            Ok(300_000)
+        );
+    }
+
+    #[test]
+    fn slots() {
+        let program = Expr::parse("y * x + y").unwrap().compile();
+        assert_eq!(program.variables(), ["y", "x"]);
+        assert_eq!(program.eval(&[2, 3]).unwrap(), 8);
+        assert!(program.eval(&[2]).is_err());
+    }
+}
@@ -0,0 +1,303 @@
+use super::number::Number;
+use super::T;
+use std::fmt::Display;
+
+// Complex number with floating point parts. The variable `i` is the imaginary unit when
+// it isn't defined, so `3+4i` is a complex literal.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    pub const I: Complex = Complex { re: 0.0, im: 1.0 };
+
+    pub fn new(re: f64, im: f64) -> Complex {
+        Complex { re, im }
+    }
+
+    pub fn is_real(&self) -> bool {
+        self.im == 0.0
+    }
+
+    fn is_zero(&self) -> bool {
+        self.re == 0.0 && self.im == 0.0
+    }
+
+    fn modulus(self) -> f64 {
+        self.re.hypot(self.im)
+    }
+
+    fn exp(self) -> Complex {
+        let scale = self.re.exp();
+        Complex::new(scale * self.im.cos(), scale * self.im.sin())
+    }
+
+    fn check(self) -> Result<Complex, String> {
+        if !self.re.is_finite() || !self.im.is_finite() {
+            return Err(format!("{self} is not a finite number"));
+        }
+
+        Ok(self)
+    }
+}
+
+impl Display for Complex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Adding 0 turns -0 into 0
+        let (re, im) = (self.re + 0.0, self.im + 0.0);
+        let imaginary = |im: f64| match im {
+            1.0 => "i".to_string(),
+            -1.0 => "-i".to_string(),
+            im => format!("{im}i"),
+        };
+        match (re, im) {
+            (re, 0.0) => write!(f, "{re}"),
+            (0.0, im) => write!(f, "{}", imaginary(im)),
+            (re, im) if im < 0.0 => write!(f, "{re} - {}", imaginary(-im)),
+            (re, im) => write!(f, "{re} + {}", imaginary(im)),
+        }
+    }
+}
+
+impl Number for Complex {
+    fn from_litteral(value: T) -> Self {
+        Complex::new(value as f64, 0.0)
+    }
+
+    fn from_f64(value: f64) -> Result<Self, String> {
+        Ok(Complex::new(value, 0.0))
+    }
+
+    // Real part, for the conversions to the other backends
+    fn to_f64(self) -> f64 {
+        self.re
+    }
+
+    // Bounds, indices and sizes would silently lose the imaginary part
+    fn to_real(self) -> Result<f64, String> {
+        if self.im != 0.0 {
+            return Err(format!("{self} is not a real number"));
+        }
+        Ok(self.re)
+    }
+
+    fn add(self, other: Self) -> Result<Self, String> {
+        Ok(Complex::new(self.re + other.re, self.im + other.im))
+    }
+
+    fn sub(self, other: Self) -> Result<Self, String> {
+        Ok(Complex::new(self.re - other.re, self.im - other.im))
+    }
+
+    fn mul(self, other: Self) -> Result<Self, String> {
+        Ok(Complex::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        ))
+    }
+
+    fn div(self, other: Self) -> Result<Self, String> {
+        if other.is_zero() {
+            return Err(format!("Cannot divide {} by zero", self));
+        }
+
+        let norm = other.re * other.re + other.im * other.im;
+        Ok(Complex::new(
+            (self.re * other.re + self.im * other.im) / norm,
+            (self.im * other.re - self.re * other.im) / norm,
+        ))
+    }
+
+    fn rem(self, other: Self) -> Result<Self, String> {
+        if !self.is_real() || !other.is_real() {
+            return Err(format!("{self} % {other} is only defined for real numbers"));
+        }
+        if other.is_zero() {
+            return Err(format!("Cannot divide {} by zero", self));
+        }
+
+        Ok(Complex::new(self.re % other.re, 0.0))
+    }
+
+    fn pow(self, other: Self) -> Result<Self, String> {
+        if self.is_zero() && other.is_zero() {
+            return Err("0^0 is undefined".to_string());
+        }
+
+        // Integer powers by repeated multiplications, which keeps i^2 = -1 exact
+        if other.is_real() && other.re.fract() == 0.0 && other.re.abs() <= u32::MAX as f64 {
+            let mut exponent = other.re.abs() as u32;
+            let (mut base, mut result) = (self, Complex::from_litteral(1));
+            while exponent > 0 {
+                if exponent % 2 == 1 {
+                    result = result.mul(base)?;
+                }
+                base = base.mul(base)?;
+                exponent /= 2;
+            }
+            if other.re < 0.0 {
+                return Complex::from_litteral(1).div(result)?.check();
+            }
+            return result.check();
+        }
+
+        if self.is_zero() {
+            return Ok(self);
+        }
+        other.mul(self.ln()?)?.exp().check()
+    }
+
+    fn constant(name: &str) -> Option<Self> {
+        match name {
+            "i" => Some(Complex::I),
+            _ => None,
+        }
+    }
+
+    // Principal square root, computed without the polar form so that sqrt(-4) = 2i
+    fn sqrt(self) -> Result<Self, String> {
+        let modulus = self.modulus();
+        let re = ((modulus + self.re) / 2.0).sqrt();
+        let im = ((modulus - self.re) / 2.0).sqrt();
+        Ok(Complex::new(re, if self.im < 0.0 { -im } else { im }))
+    }
+
+    fn ln(self) -> Result<Self, String> {
+        if self.is_zero() {
+            return Err("ln(0) is undefined".to_string());
+        }
+
+        Ok(Complex::new(self.modulus().ln(), self.im.atan2(self.re)))
+    }
+
+    fn abs(self) -> Result<Self, String> {
+        Ok(Complex::new(self.modulus(), 0.0))
+    }
+
+    fn arg(self) -> Result<Self, String> {
+        Ok(Complex::new(self.im.atan2(self.re), 0.0))
+    }
+
+    fn round(self, digits: i32) -> Result<Self, String> {
+        Ok(Complex::new(
+            Number::round(self.re, digits)?,
+            Number::round(self.im, digits)?,
+        ))
+    }
+
+    fn re(self) -> Self {
+        Complex::new(self.re, 0.0)
+    }
+
+    fn im(self) -> Self {
+        Complex::new(self.im, 0.0)
+    }
+
+    fn conj(self) -> Self {
+        Complex::new(self.re, -self.im)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Session;
+
+    fn c(re: f64, im: f64) -> Complex {
+        Complex::new(re, im)
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(c(1.0, 2.0).add(c(3.0, -1.0)), Ok(c(4.0, 1.0)));
+        assert_eq!(c(1.0, 2.0).mul(c(3.0, 4.0)), Ok(c(-5.0, 10.0)));
+        assert_eq!(c(-5.0, 10.0).div(c(3.0, 4.0)), Ok(c(1.0, 2.0)));
+        assert_eq!(Complex::I.pow(c(2.0, 0.0)), Ok(c(-1.0, 0.0)));
+        assert_eq!(c(0.0, 2.0).pow(c(-1.0, 0.0)), Ok(c(0.0, -0.5)));
+        assert_eq!(
+            c(1.0, 1.0).div(c(0.0, 0.0)).unwrap_err(),
+            "Cannot divide 1 + i by zero"
+        );
+        assert!(c(1.0, 1.0).rem(c(2.0, 0.0)).is_err());
+
+        // e^(i pi) = -1, up to rounding
+        let z = c(std::f64::consts::E, 0.0)
+            .pow(c(0.0, std::f64::consts::PI))
+            .unwrap();
+        assert!((z.re + 1.0).abs() < 1e-12 && z.im.abs() < 1e-12);
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(c(-1.0, 0.0).sqrt(), Ok(Complex::I));
+        assert_eq!(c(-4.0, 0.0).sqrt(), Ok(c(0.0, 2.0)));
+        assert_eq!(c(3.0, -4.0).sqrt(), Ok(c(2.0, -1.0)));
+        assert_eq!(c(-1.0, 0.0).ln(), Ok(c(0.0, std::f64::consts::PI)));
+        assert_eq!(c(3.0, 4.0).abs(), Ok(c(5.0, 0.0)));
+        assert_eq!(c(0.0, 1.0).arg(), Ok(c(std::f64::consts::FRAC_PI_2, 0.0)));
+        assert_eq!(c(3.0, 4.0).conj(), c(3.0, -4.0));
+        assert_eq!(
+            (c(3.0, 4.0).re(), c(3.0, 4.0).im()),
+            (c(3.0, 0.0), c(4.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(c(3.0, 4.0).to_string(), "3 + 4i");
+        assert_eq!(c(3.0, -4.5).to_string(), "3 - 4.5i");
+        assert_eq!(c(0.0, 1.0).to_string(), "i");
+        assert_eq!(c(-2.0, -1.0).to_string(), "-2 - i");
+        assert_eq!(c(2.5, -0.0).to_string(), "2.5");
+        assert_eq!(c(-0.0, -3.0).to_string(), "-3i");
+    }
+
+    #[test]
+    fn complex_mode() {
+        let mut session = Session::<Complex>::new();
+        let mut eval = |input| session.run(input).map(|result| result.unwrap().to_string());
+        assert_eq!(eval("3+4i"), Ok("3 + 4i".to_string()));
+        assert_eq!(eval("(1+2i)(3-i)"), Ok("5 + 5i".to_string()));
+        assert_eq!(eval("1/2i"), Ok("-0.5i".to_string()));
+        assert_eq!(eval("sqrt(-1)"), Ok("i".to_string()));
+        assert_eq!(eval("ln(-1)"), Ok("3.141592653589793i".to_string()));
+        assert_eq!(eval("abs(3+4i) + re(2-i) + im(2-i)"), Ok("6".to_string()));
+        assert_eq!(eval("conj(1+2i)"), Ok("1 - 2i".to_string()));
+        assert_eq!(eval("arg(-2)"), Ok(std::f64::consts::PI.to_string()));
+        // A variable named i shadows the imaginary unit
+        assert_eq!(eval("sum(i, 1, 3, i)"), Ok("6".to_string()));
+
+        // Bounds, indices and sizes must be real
+        assert_eq!(
+            eval("integrate(x, x, 0, 1+i)").unwrap_err(),
+            "1 + i is not a real number"
+        );
+        assert_eq!(
+            eval("diff(x^2, x, i)").unwrap_err(),
+            "i is not a real number"
+        );
+        assert_eq!(
+            eval("sum(k, 1, 3i, k)").unwrap_err(),
+            "3i is not a real number"
+        );
+        assert_eq!(eval("[1, 2][i]").unwrap_err(), "i is not a real number");
+        assert_eq!(eval("round(2, i)").unwrap_err(), "i is not a real number");
+        assert_eq!(eval("identity(2i)").unwrap_err(), "2i is not a real number");
+        assert_eq!(eval("integrate(x, x, 0, 2+0i)"), Ok("2".to_string()));
+
+        // The other backends have no imaginary numbers
+        let mut session = Session::<T>::new();
+        assert_eq!(
+            session.run("sqrt(-1)"),
+            Err("sqrt(-1) is not a real number".to_string())
+        );
+        assert_eq!(
+            session.run("ln(-1)").unwrap_err(),
+            "ln(-1) is not a real number"
+        );
+        assert_eq!(session.run("3+4i").unwrap_err(), "Unknown variable i");
+        assert_eq!(session.run("sqrt(16) + abs(-2)"), Ok(Some(6.into())));
+    }
+}
@@ -0,0 +1,394 @@
+use super::expr::Expr;
+use super::functions::Function;
+use super::limits::Limits;
+use super::pratt::Parser;
+use super::preprocessor::preprocess_tokens;
+use super::session::Statement;
+use super::tokenizer::{is_identifier, tokenize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, LazyLock};
+
+pub static DEFAULT_CONFIG: LazyLock<ParserConfig> = LazyLock::new(ParserConfig::default);
+
+// Node built by an infix operator
+#[derive(Clone)]
+pub enum Operation {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+    // Call to the function with the given name, with both operands as arguments
+    Function(String),
+    // Node built by a closure from both operands, such as `(left + right) / 2`
+    Custom(Arc<dyn Fn(Expr, Expr) -> Expr + Send + Sync>),
+}
+
+impl Operation {
+    pub fn custom(build: impl Fn(Expr, Expr) -> Expr + Send + Sync + 'static) -> Self {
+        Operation::Custom(Arc::new(build))
+    }
+
+    pub fn build(&self, left: Expr, right: Expr) -> Expr {
+        let node = match self {
+            Operation::Add => Expr::Add,
+            Operation::Sub => Expr::Sub,
+            Operation::Mul => Expr::Prod,
+            Operation::Div => Expr::Div,
+            Operation::Rem => Expr::Mod,
+            Operation::Pow => Expr::Pow,
+            Operation::Function(name) => return Expr::Call(name.clone(), vec![left, right]),
+            Operation::Custom(build) => return build(left, right),
+        };
+        node(Box::new(left), Box::new(right))
+    }
+}
+
+impl fmt::Debug for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operation::Add => write!(f, "Add"),
+            Operation::Sub => write!(f, "Sub"),
+            Operation::Mul => write!(f, "Mul"),
+            Operation::Div => write!(f, "Div"),
+            Operation::Rem => write!(f, "Rem"),
+            Operation::Pow => write!(f, "Pow"),
+            Operation::Function(name) => write!(f, "Function({name:?})"),
+            Operation::Custom(_) => write!(f, "Custom"),
+        }
+    }
+}
+
+// Closures are only equal to themselves
+impl PartialEq for Operation {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Operation::Function(name), Operation::Function(other)) => name == other,
+            (Operation::Custom(build), Operation::Custom(other)) => Arc::ptr_eq(build, other),
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Operator {
+    pub precedence: usize,
+    pub right_associative: bool,
+    pub operation: Operation,
+}
+
+// Infix operators understood by a parser and the limits it enforces, each product can
+// build its own dialect:
+//
+//     let config = ParserConfig::default()
+//         .remove("%")
+//         .rename("^", "**")
+//         .right_associative("**", false);
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParserConfig {
+    operators: HashMap<String, Operator>,
+    // Infix operator applied to 0 by the prefix form, `-x` being `0 - x`
+    negation: Option<String>,
+    pub(super) limits: Limits,
+}
+
+impl Default for ParserConfig {
+    fn default() -> Self {
+        ParserConfig::empty()
+            .operator("+", 0, Operation::Add)
+            .operator("-", 0, Operation::Sub)
+            .operator("*", 1, Operation::Mul)
+            .operator("/", 1, Operation::Div)
+            .operator("%", 1, Operation::Rem)
+            .operator("^", 2, Operation::Pow)
+            .right_associative("^", true)
+            .negation("-")
+    }
+}
+
+impl ParserConfig {
+    pub fn empty() -> Self {
+        ParserConfig {
+            operators: HashMap::new(),
+            negation: None,
+            limits: Limits::default(),
+        }
+    }
+
+    // Adds or replaces a left associative operator
+    pub fn operator(mut self, symbol: &str, precedence: usize, operation: Operation) -> Self {
+        self.operators.insert(
+            symbol.to_string(),
+            Operator {
+                precedence,
+                right_associative: false,
+                operation,
+            },
+        );
+        self
+    }
+
+    pub fn remove(mut self, symbol: &str) -> Self {
+        self.operators.remove(symbol);
+        if self.negation.as_deref() == Some(symbol) {
+            self.negation = None;
+        }
+        self
+    }
+
+    pub fn rename(mut self, symbol: &str, new_symbol: &str) -> Self {
+        if let Some(operator) = self.operators.remove(symbol) {
+            self.operators.insert(new_symbol.to_string(), operator);
+        }
+        if self.negation.as_deref() == Some(symbol) {
+            self.negation = Some(new_symbol.to_string());
+        }
+        self
+    }
+
+    // Makes the infix operator `symbol` usable as a prefix, `symbol x` being `0 symbol x`
+    pub fn negation(mut self, symbol: &str) -> Self {
+        self.negation = Some(symbol.to_string());
+        self
+    }
+
+    pub fn get_negation(&self) -> Option<&str> {
+        self.negation.as_deref()
+    }
+
+    pub fn precedence(mut self, symbol: &str, precedence: usize) -> Self {
+        if let Some(operator) = self.operators.get_mut(symbol) {
+            operator.precedence = precedence;
+        }
+        self
+    }
+
+    pub fn right_associative(mut self, symbol: &str, right_associative: bool) -> Self {
+        if let Some(operator) = self.operators.get_mut(symbol) {
+            operator.right_associative = right_associative;
+        }
+        self
+    }
+
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn get(&self, symbol: &str) -> Option<&Operator> {
+        self.operators.get(symbol)
+    }
+
+    pub fn is_operator(&self, symbol: &str) -> bool {
+        self.operators.contains_key(symbol)
+    }
+
+    pub fn symbols(&self) -> impl Iterator<Item = &str> {
+        self.operators.keys().map(|symbol| symbol.as_str())
+    }
+
+    pub fn parse(&self, input: &str) -> Result<Expr, String> {
+        self.parse_tokens(self.raw_tokens(input)?)
+    }
+
+    // Statements separated by ';', each one an expression, an assignment `name = expr` or
+    // a function definition `name(param, ...) = expr`
+    pub fn parse_statements(&self, input: &str) -> Result<Vec<Statement>, String> {
+        let raw_tokens = self.raw_tokens(input)?;
+        let mut statements = vec![];
+        for statement in raw_tokens.split(|token| token == ";") {
+            statements.push(match statement {
+                [] => continue,
+                [name, equal, expr @ ..] if equal == "=" && is_identifier(name) => {
+                    Statement::Assignment(name.clone(), self.parse_tokens(expr.to_vec())?)
+                }
+                [name, open, rest @ ..]
+                    if open == "(" && is_identifier(name) && rest.contains(&"=".to_string()) =>
+                {
+                    Statement::Definition(self.parse_definition(name, rest)?)
+                }
+                _ => Statement::Expr(self.parse_tokens(statement.to_vec())?),
+            });
+        }
+
+        if statements.is_empty() {
+            return Err("Expected an expression".to_string());
+        }
+        Ok(statements)
+    }
+
+    // `rest` follows the opening parenthesis of the parameters
+    fn parse_definition(&self, name: &str, rest: &[String]) -> Result<Function, String> {
+        let equal = rest.iter().position(|token| token == "=").unwrap();
+        let (Some((close, params)), body) = (rest[..equal].split_last(), &rest[equal + 1..]) else {
+            return Err(format!("Invalid definition of {name}"));
+        };
+        if close != ")" {
+            return Err(format!("Invalid definition of {name}"));
+        }
+
+        let params: Vec<String> = match params {
+            [] => vec![],
+            params => params
+                .split(|token| token == ",")
+                .map(|param| match param {
+                    [param] if is_identifier(param) => Ok(param.clone()),
+                    _ => Err(format!("Invalid parameters in the definition of {name}")),
+                })
+                .collect::<Result<_, _>>()?,
+        };
+
+        let expr = self.parse_tokens(body.to_vec())?;
+        Function::new(name, params, expr, &join_tokens(body))
+    }
+
+    fn raw_tokens(&self, input: &str) -> Result<Vec<String>, String> {
+        self.validate()?;
+        self.limits.check_input_length(input)?;
+        tokenize(input, self)
+    }
+
+    fn tokens(&self, raw_tokens: Vec<String>) -> Result<Vec<String>, String> {
+        let tokens = preprocess_tokens(raw_tokens, self)?;
+        self.limits.check_tokens(tokens.len())?;
+        Ok(tokens)
+    }
+
+    fn parse_tokens(&self, raw_tokens: Vec<String>) -> Result<Expr, String> {
+        Parser::new(self.tokens(raw_tokens)?, self).parse()
+    }
+
+    // Symbols made of letters, digits, parentheses or separators would be split by the
+    // tokenizer
+    fn validate(&self) -> Result<(), String> {
+        for symbol in self.symbols() {
+            if symbol.is_empty()
+                || symbol
+                    .chars()
+                    .any(|c| c.is_alphanumeric() || c.is_whitespace() || "_()[],;".contains(c))
+                // Arrow of the lambdas
+                || symbol == "->"
+            {
+                return Err(format!("Invalid operator symbol '{symbol}'"));
+            }
+        }
+        if let Some(negation) = self.get_negation() {
+            if !self.is_operator(negation) {
+                return Err(format!("The negation '{negation}' isn't an operator"));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Text of an expression from its tokens, with spaces around the operators
+fn join_tokens(tokens: &[String]) -> String {
+    let mut text = String::new();
+    for (i, token) in tokens.iter().enumerate() {
+        let glued = i == 0
+            || token == ")"
+            || token == ","
+            || tokens[i - 1] == "("
+            || (token == "(" && is_identifier(&tokens[i - 1]));
+        if !glued {
+            text.push(' ');
+        }
+        text.push_str(token);
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::T;
+
+    fn eval(config: &ParserConfig, input: &str) -> Result<T, String> {
+        config.parse(input)?.eval()
+    }
+
+    #[test]
+    fn default_dialect() {
+        let config = ParserConfig::default();
+        assert_eq!(eval(&config, "2^3^2 + 7 % 3").unwrap(), 513);
+    }
+
+    #[test]
+    fn custom_dialect() {
+        let config = ParserConfig::default()
+            .remove("%")
+            .rename("^", "**")
+            .right_associative("**", false);
+
+        assert_eq!(eval(&config, "2**3**2").unwrap(), 64);
+        assert_eq!(eval(&config, "2 * 3 ** 2").unwrap(), 18);
+        assert!(config.parse("2^3").is_err());
+        assert!(config.parse("7 % 3").is_err());
+    }
+
+    #[test]
+    fn precedence() {
+        let config = ParserConfig::default().precedence("+", 5);
+        assert_eq!(eval(&config, "2 * 3 + 4").unwrap(), 14);
+    }
+
+    #[test]
+    fn multi_character_operators() {
+        let config = ParserConfig::default()
+            .operator("//", 1, Operation::Div)
+            .operator("<>", 0, Operation::Function("f".to_string()));
+
+        assert_eq!(eval(&config, "7 // 2 * 2").unwrap(), 6);
+        assert_eq!(
+            format!("{:?}", config.parse("1 <> 2 * 3").unwrap()),
+            "f(1, Prod(2, 3))"
+        );
+    }
+
+    #[test]
+    fn custom_operations() {
+        let average = Operation::custom(|left, right| {
+            let sum = Expr::Add(Box::new(left), Box::new(right));
+            Expr::Div(Box::new(sum), Box::new(Expr::Litteral(2)))
+        });
+        let config = ParserConfig::default().operator("<>", 0, average);
+
+        assert_eq!(eval(&config, "3 <> 4 * 2").unwrap(), 5);
+        assert_eq!(
+            config
+                .parse("1 <> 2.5")
+                .unwrap()
+                .eval_with::<f64>(&HashMap::new()),
+            Ok(1.75)
+        );
+        assert_eq!(config, config.clone());
+    }
+
+    #[test]
+    fn negation() {
+        let config = ParserConfig::default().rename("-", "~");
+        assert_eq!(eval(&config, "~3 ~ (~2) * 4").unwrap(), 5);
+        assert_eq!(config.get_negation(), Some("~"));
+
+        let config = ParserConfig::default()
+            .operator("!", 0, Operation::Sub)
+            .negation("!");
+        assert_eq!(eval(&config, "!3 + 1").unwrap(), -2);
+        assert!(config.parse("-3").is_err());
+
+        let config = ParserConfig::default().negation("!");
+        assert_eq!(
+            config.parse("1").unwrap_err(),
+            "The negation '!' isn't an operator"
+        );
+        assert!(ParserConfig::default().remove("-").parse("-1").is_err());
+    }
+
+    #[test]
+    fn invalid_symbols() {
+        let config = ParserConfig::default().operator("mod", 1, Operation::Rem);
+        assert!(config.parse("7 mod 3").is_err());
+    }
+}
@@ -0,0 +1,103 @@
+use super::functions::{Function, Functions};
+use super::limits::Limits;
+use super::number::Number;
+use super::value::Value;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// State shared by the nested evaluations of an expression: the evaluation steps left and
+// the user defined functions with their call depth
+pub(super) struct Context<'a, N> {
+    max_steps: usize,
+    remaining_steps: usize,
+    max_call_depth: usize,
+    call_depth: usize,
+    functions: Option<&'a Functions>,
+    // Variables of the session, the only ones seen by the bodies of the functions rather
+    // than the ones of their callers
+    globals: Rc<HashMap<String, Value<N>>>,
+}
+
+impl<'a, N: Number> Context<'a, N> {
+    pub(super) fn new(limits: &Limits) -> Context<'a, N> {
+        Context {
+            max_steps: limits.max_eval_steps,
+            remaining_steps: limits.max_eval_steps,
+            max_call_depth: limits.max_call_depth,
+            call_depth: 0,
+            functions: None,
+            globals: Rc::new(HashMap::new()),
+        }
+    }
+
+    pub(super) fn with_functions(
+        limits: &Limits,
+        functions: &'a Functions,
+        globals: HashMap<String, Value<N>>,
+    ) -> Context<'a, N> {
+        Context {
+            functions: Some(functions),
+            globals: Rc::new(globals),
+            ..Context::new(limits)
+        }
+    }
+
+    pub(super) fn globals(&self) -> Rc<HashMap<String, Value<N>>> {
+        self.globals.clone()
+    }
+
+    // Same steps, functions and globals for the evaluations at non integer points, which
+    // give them back with `resume`
+    pub(super) fn floats(&self) -> Context<'a, f64> {
+        let globals = self
+            .globals
+            .iter()
+            .filter_map(|(name, value)| match value {
+                Value::Number(value) => Some((name.clone(), Value::Number(value.to_f64()))),
+                _ => None,
+            })
+            .collect();
+        Context {
+            max_steps: self.max_steps,
+            remaining_steps: self.remaining_steps,
+            max_call_depth: self.max_call_depth,
+            call_depth: self.call_depth,
+            functions: self.functions,
+            globals: Rc::new(globals),
+        }
+    }
+
+    pub(super) fn resume(&mut self, floats: Context<f64>) {
+        self.remaining_steps = floats.remaining_steps;
+    }
+
+    pub(super) fn step(&mut self) -> Result<(), String> {
+        if self.remaining_steps == 0 {
+            return Err(format!(
+                "Evaluation takes too many steps (the limit is {})",
+                self.max_steps
+            ));
+        }
+        self.remaining_steps -= 1;
+        Ok(())
+    }
+
+    pub(super) fn function(&self, name: &str) -> Option<&'a Function> {
+        self.functions?.get(name)
+    }
+
+    pub(super) fn enter_call(&mut self, name: &str) -> Result<(), String> {
+        if self.call_depth >= self.max_call_depth {
+            return Err(format!(
+                "Too many nested calls in {name} (the limit is {})",
+                self.max_call_depth
+            ));
+        }
+        self.call_depth += 1;
+        Ok(())
+    }
+
+    pub(super) fn leave_call(&mut self) {
+        self.call_depth -= 1;
+    }
+}
@@ -0,0 +1,415 @@
+use super::number::Number;
+use super::T;
+use std::cell::Cell;
+use std::fmt::Display;
+
+const OVERFLOW: &str = "Overflow in decimal arithmetic";
+
+thread_local! {
+    // Settings used by the evaluations running in `DecimalContext::scope`
+    static CONTEXT: Cell<DecimalContext> = Cell::new(DecimalContext::default());
+}
+
+// How a result is rounded to the precision, and by `round(x, digits)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Rounding {
+    // To the nearest, ties to the even neighbour
+    #[default]
+    HalfEven,
+    // To the nearest, ties away from zero
+    HalfUp,
+    Truncate,
+    Ceiling,
+    Floor,
+}
+
+impl Rounding {
+    // num / den rounded to an integer, den isn't 0
+    pub(super) fn divide(self, num: T, den: T) -> Result<T, String> {
+        let (num, den) = match den < 0 {
+            true => (
+                num.checked_neg().ok_or(OVERFLOW)?,
+                den.checked_neg().ok_or(OVERFLOW)?,
+            ),
+            false => (num, den),
+        };
+        let (floor, remainder) = (num.div_euclid(den), num.rem_euclid(den));
+        if remainder == 0 {
+            return Ok(floor);
+        }
+        // den > 1 here, so floor is at most T::MAX / 2
+        let ceiling = floor + 1;
+        let toward_zero = if num < 0 { ceiling } else { floor };
+        Ok(match self {
+            Rounding::Truncate => toward_zero,
+            Rounding::Floor => floor,
+            Rounding::Ceiling => ceiling,
+            // The remainder is compared with its distance to den, 2 * remainder could
+            // overflow
+            Rounding::HalfEven | Rounding::HalfUp => match remainder.cmp(&(den - remainder)) {
+                std::cmp::Ordering::Less => floor,
+                std::cmp::Ordering::Greater => ceiling,
+                std::cmp::Ordering::Equal if self == Rounding::HalfUp => {
+                    if num < 0 {
+                        floor
+                    } else {
+                        ceiling
+                    }
+                }
+                std::cmp::Ordering::Equal => floor + floor.rem_euclid(2),
+            },
+        })
+    }
+}
+
+// Number of decimals kept by the operations of `Decimal` and how they are rounded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecimalContext {
+    pub precision: u32,
+    pub rounding: Rounding,
+}
+
+impl Default for DecimalContext {
+    fn default() -> Self {
+        DecimalContext {
+            precision: 10,
+            rounding: Rounding::HalfEven,
+        }
+    }
+}
+
+impl DecimalContext {
+    pub fn new(precision: u32, rounding: Rounding) -> Self {
+        DecimalContext {
+            precision,
+            rounding,
+        }
+    }
+
+    pub fn current() -> DecimalContext {
+        CONTEXT.with(Cell::get)
+    }
+
+    // Runs `f` with these settings
+    pub fn scope<R>(&self, f: impl FnOnce() -> R) -> R {
+        let previous = CONTEXT.with(|context| context.replace(*self));
+        let result = f();
+        CONTEXT.with(|context| context.set(previous));
+        result
+    }
+}
+
+// Base 10 fixed point number: `digits` / 10^`scale`, without trailing zeros. Sums and
+// differences are exact, the other operations are rounded to the precision of the
+// current `DecimalContext`, so 0.1 + 0.2 is exactly 0.3.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Decimal {
+    digits: T,
+    scale: u32,
+}
+
+impl Decimal {
+    pub fn new(digits: T, scale: u32) -> Decimal {
+        let mut decimal = Decimal { digits, scale };
+        while decimal.scale > 0 && decimal.digits % 10 == 0 {
+            decimal.digits /= 10;
+            decimal.scale -= 1;
+        }
+        decimal
+    }
+
+    // Decimal such as "-12.375"
+    pub fn parse(text: &str) -> Result<Decimal, String> {
+        let (integer, decimals) = text.split_once('.').unwrap_or((text, ""));
+        let digits = format!("{integer}{decimals}")
+            .parse::<T>()
+            .map_err(|_| format!("Invalid decimal {text}"))?;
+        Ok(Decimal::new(digits, decimals.len() as u32))
+    }
+
+    pub fn digits(&self) -> T {
+        self.digits
+    }
+
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    // Rounded to `decimals` decimals, which can be negative to round to tens, hundreds...
+    pub fn round_to(self, decimals: i32, rounding: Rounding) -> Result<Decimal, String> {
+        let removed = self.scale as i32 - decimals;
+        if removed <= 0 {
+            return Ok(self);
+        }
+        let Some(power) = u32::try_from(removed)
+            .ok()
+            .and_then(|n| T::checked_pow(10, n))
+        else {
+            return Ok(Decimal::new(0, 0));
+        };
+        let digits = rounding.divide(self.digits, power)?;
+        match u32::try_from(decimals) {
+            Ok(scale) => Ok(Decimal::new(digits, scale)),
+            Err(_) => {
+                let power = T::checked_pow(10, decimals.unsigned_abs()).ok_or(OVERFLOW)?;
+                Ok(Decimal::new(digits.checked_mul(power).ok_or(OVERFLOW)?, 0))
+            }
+        }
+    }
+
+    // Both digits at the same scale
+    fn aligned(self, other: Decimal) -> Result<(T, T, u32), String> {
+        let scale = self.scale.max(other.scale);
+        let widen = |decimal: Decimal| {
+            T::checked_pow(10, scale - decimal.scale)
+                .and_then(|power| decimal.digits.checked_mul(power))
+                .ok_or(OVERFLOW)
+        };
+        Ok((widen(self)?, widen(other)?, scale))
+    }
+
+    // num / den with the given number of decimals, rounded with the current context
+    fn quotient(num: T, den: T, scale: u32) -> Result<Decimal, String> {
+        let rounding = DecimalContext::current().rounding;
+        Ok(Decimal::new(rounding.divide(num, den)?, scale))
+    }
+}
+
+// `digits` divided by 10^`decimals`
+pub(super) fn with_point(digits: T, decimals: usize) -> String {
+    let sign = if digits < 0 { "-" } else { "" };
+    let digits = format!("{:0>width$}", digits.unsigned_abs(), width = decimals + 1);
+    let (integer, fraction) = digits.split_at(digits.len() - decimals);
+    match fraction {
+        "" => format!("{sign}{integer}"),
+        fraction => format!("{sign}{integer}.{fraction}"),
+    }
+}
+
+impl Display for Decimal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", with_point(self.digits, self.scale as usize))
+    }
+}
+
+impl Number for Decimal {
+    fn from_litteral(value: T) -> Self {
+        Decimal::new(value, 0)
+    }
+
+    // Litterals are exact, only the operations are rounded
+    fn from_decimal(digits: T, scale: u32) -> Result<Self, String> {
+        Ok(Decimal::new(digits, scale))
+    }
+
+    fn from_f64(value: f64) -> Result<Self, String> {
+        if !value.is_finite() {
+            return Err(format!("{value} is not a finite number"));
+        }
+
+        let context = DecimalContext::current();
+        Decimal::parse(&value.to_string())?.round_to(context.precision as i32, context.rounding)
+    }
+
+    fn to_f64(self) -> f64 {
+        self.digits as f64 / 10f64.powi(self.scale as i32)
+    }
+
+    fn add(self, other: Self) -> Result<Self, String> {
+        let (left, right, scale) = self.aligned(other)?;
+        Ok(Decimal::new(
+            left.checked_add(right).ok_or(OVERFLOW)?,
+            scale,
+        ))
+    }
+
+    fn sub(self, other: Self) -> Result<Self, String> {
+        let (left, right, scale) = self.aligned(other)?;
+        Ok(Decimal::new(
+            left.checked_sub(right).ok_or(OVERFLOW)?,
+            scale,
+        ))
+    }
+
+    fn mul(self, other: Self) -> Result<Self, String> {
+        let context = DecimalContext::current();
+        let digits = self.digits.checked_mul(other.digits).ok_or(OVERFLOW)?;
+        Decimal::new(digits, self.scale + other.scale)
+            .round_to(context.precision as i32, context.rounding)
+    }
+
+    fn div(self, other: Self) -> Result<Self, String> {
+        if other.digits == 0 {
+            return Err(format!("Cannot divide {} by zero", self));
+        }
+
+        // self.digits * 10^(precision + other.scale - self.scale) / other.digits, the
+        // power being moved to the denominator when it is negative
+        let precision = DecimalContext::current().precision;
+        let exponent = (precision + other.scale) as i64 - self.scale as i64;
+        let power = T::checked_pow(10, exponent.unsigned_abs() as u32).ok_or(OVERFLOW)?;
+        let (num, den) = match exponent >= 0 {
+            true => (self.digits.checked_mul(power), Some(other.digits)),
+            false => (Some(self.digits), other.digits.checked_mul(power)),
+        };
+        Decimal::quotient(num.ok_or(OVERFLOW)?, den.ok_or(OVERFLOW)?, precision)
+    }
+
+    fn rem(self, other: Self) -> Result<Self, String> {
+        if other.digits == 0 {
+            return Err(format!("Cannot divide {} by zero", self));
+        }
+
+        // Only T::MIN % -1 overflows, whose remainder is 0
+        let (left, right, scale) = self.aligned(other)?;
+        Ok(Decimal::new(left.checked_rem(right).unwrap_or(0), scale))
+    }
+
+    fn pow(self, other: Self) -> Result<Self, String> {
+        if self.digits == 0 && other.digits == 0 {
+            return Err("0^0 is undefined".to_string());
+        }
+        if other.scale > 0 {
+            return Decimal::from_f64(Number::pow(self.to_f64(), other.to_f64())?);
+        }
+
+        let exponent = u32::try_from(other.digits.unsigned_abs()).map_err(|_| OVERFLOW)?;
+        let (mut base, mut result, mut left) = (self, Decimal::new(1, 0), exponent);
+        while left > 0 {
+            if left % 2 == 1 {
+                result = result.mul(base)?;
+            }
+            left /= 2;
+            if left > 0 {
+                base = base.mul(base)?;
+            }
+        }
+        if other.digits < 0 {
+            if result.digits == 0 {
+                return Err(format!("Cannot raise {self} to a negative power"));
+            }
+            return Decimal::new(1, 0).div(result);
+        }
+        Ok(result)
+    }
+
+    fn round(self, digits: i32) -> Result<Self, String> {
+        self.round_to(digits, DecimalContext::current().rounding)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Expr, Session};
+    use std::collections::HashMap;
+
+    fn d(text: &str) -> Decimal {
+        Decimal::parse(text).unwrap()
+    }
+
+    fn eval(input: &str) -> Result<String, String> {
+        let variables: HashMap<String, Decimal> = HashMap::new();
+        Ok(Expr::parse(input)?.eval_with(&variables)?.to_string())
+    }
+
+    #[test]
+    fn rounding_modes() {
+        let cases = [
+            (Rounding::HalfEven, [2, 2, -2, 3]),
+            (Rounding::HalfUp, [3, 2, -3, 3]),
+            (Rounding::Truncate, [2, 2, -2, 2]),
+            (Rounding::Ceiling, [3, 3, -2, 3]),
+            (Rounding::Floor, [2, 2, -3, 2]),
+        ];
+        for (rounding, expected) in cases {
+            let results = [(5, 2), (9, 4), (-5, 2), (11, 4)]
+                .map(|(num, den)| rounding.divide(num, den).unwrap());
+            assert_eq!(results, expected, "{rounding:?}");
+        }
+    }
+
+    #[test]
+    fn overflow() {
+        let rounding = Rounding::HalfEven;
+        assert_eq!(rounding.divide(T::MAX, T::MAX - 1), Ok(1));
+        assert_eq!(rounding.divide(T::MAX - 1, T::MAX), Ok(1));
+        assert_eq!(rounding.divide(T::MIN, -1), Err(OVERFLOW.to_string()));
+        assert_eq!(rounding.divide(1, T::MIN), Err(OVERFLOW.to_string()));
+        assert_eq!(
+            d("0.90000000000000000000000000000000000001").round_to(0, rounding),
+            Ok(Decimal::new(1, 0))
+        );
+        assert_eq!(
+            d("0.10000000000000000000000000000000000001").round_to(0, rounding),
+            Ok(Decimal::new(0, 0))
+        );
+        assert_eq!(
+            Decimal::new(T::MIN, 0).rem(Decimal::new(-1, 0)),
+            Ok(Decimal::new(0, 0))
+        );
+        assert_eq!(
+            Decimal::new(T::MAX, 0).add(Decimal::new(1, 0)),
+            Err(OVERFLOW.to_string())
+        );
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(eval("0.1 + 0.2").unwrap(), "0.3");
+        assert_eq!(eval("0.1 + 0.2 - 0.3").unwrap(), "0");
+        assert_eq!(eval("1.5 * 1.5").unwrap(), "2.25");
+        assert_eq!(eval("1 / 3").unwrap(), "0.3333333333");
+        assert_eq!(eval("2 / 3").unwrap(), "0.6666666667");
+        assert_eq!(eval("7.5 % 2").unwrap(), "1.5");
+        assert_eq!(eval("1.1^2").unwrap(), "1.21");
+        assert_eq!(eval("2^-2").unwrap(), "0.25");
+        assert_eq!(eval("1 / 0.001").unwrap(), "1000");
+        assert_eq!(d("-12.500"), Decimal::new(-125, 1));
+    }
+
+    #[test]
+    fn contexts() {
+        let truncate = DecimalContext::new(10, Rounding::Truncate);
+        assert_eq!(truncate.scope(|| eval("2 / 3")).unwrap(), "0.6666666666");
+
+        let cents = DecimalContext::new(2, Rounding::HalfUp);
+        assert_eq!(cents.scope(|| eval("1 / 8")).unwrap(), "0.13");
+        assert_eq!(cents.scope(|| eval("0.25 * 0.5")).unwrap(), "0.13");
+        // Litterals and sums are exact, only products and quotients are rounded
+        assert_eq!(cents.scope(|| eval("0.125")).unwrap(), "0.125");
+        assert_eq!(cents.scope(|| eval("0.125 * 8")).unwrap(), "1");
+        assert_eq!(cents.scope(|| eval("1.005 + 1")).unwrap(), "2.005");
+        assert_eq!(DecimalContext::current(), DecimalContext::default());
+    }
+
+    #[test]
+    fn round() {
+        assert_eq!(eval("round(2.5, 0)").unwrap(), "2");
+        assert_eq!(eval("round(3.5, 0)").unwrap(), "4");
+        assert_eq!(eval("round(1.2345, 2)").unwrap(), "1.23");
+        assert_eq!(eval("round(1234.5, -2)").unwrap(), "1200");
+
+        let half_up = DecimalContext::new(10, Rounding::HalfUp);
+        assert_eq!(half_up.scope(|| eval("round(2.5, 0)")).unwrap(), "3");
+        assert_eq!(half_up.scope(|| eval("round(-2.5, 0)")).unwrap(), "-3");
+        let floor = DecimalContext::new(10, Rounding::Floor);
+        assert_eq!(floor.scope(|| eval("round(-2.51, 1)")).unwrap(), "-2.6");
+        let ceiling = DecimalContext::new(10, Rounding::Ceiling);
+        assert_eq!(ceiling.scope(|| eval("round(2.01, 1)")).unwrap(), "2.1");
+
+        let mut session = Session::<Decimal>::new();
+        assert_eq!(
+            session.run("round(2, 0.5)").unwrap_err(),
+            "round expects an integer number of digits, got 0.5"
+        );
+        // The other backends round half away from zero
+        assert_eq!(Expr::parse("round(15, -1)").unwrap().eval(), Ok(20));
+        assert_eq!(
+            Expr::parse("round(2.25, 1)")
+                .unwrap()
+                .eval_with(&HashMap::<String, f64>::new()),
+            Ok(2.3)
+        );
+    }
+}
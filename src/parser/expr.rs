@@ -1,25 +1,14 @@
-use super::preprocessor::preprocess_tokens;
-use super::tokenizer::tokenize;
+use super::calculus;
+use super::config::DEFAULT_CONFIG;
+use super::context::Context;
+use super::functions::Functions;
+use super::interval::Interval;
+use super::limits::Limits;
+use super::number::Number;
+use super::value::{self, Scope};
 use super::T;
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{BTreeSet, HashMap};
 use std::fmt::Debug;
-use std::sync::LazyLock;
-
-pub static OPERATORS_PRECEDENCE: LazyLock<HashMap<String, usize>> = LazyLock::new(|| {
-    let mut map = HashMap::new();
-    map.insert("+".to_string(), 0);
-    map.insert("-".to_string(), 0);
-    map.insert("*".to_string(), 1);
-    map.insert("/".to_string(), 1);
-    map.insert("%".to_string(), 1);
-    map.insert("^".to_string(), 2);
-    map
-});
-pub static RIGHT_ASSOCIATIVE_OPERATORS: LazyLock<HashSet<String>> = LazyLock::new(|| {
-    let mut set = HashSet::new();
-    set.insert("^".to_string());
-    set
-});
 
 pub enum Expr {
     Litteral(T),
@@ -29,152 +18,575 @@ pub enum Expr {
     Sub(Box<Expr>, Box<Expr>),
     Pow(Box<Expr>, Box<Expr>),
     Mod(Box<Expr>, Box<Expr>),
+    Variable(String),
+    Call(String, Vec<Expr>),
+    // Anonymous function, such as `x -> x^2` or `(x, y) -> x * y`
+    Lambda(Vec<String>, Box<Expr>),
+    List(Vec<Expr>),
+    // Item of a list, such as `v[0]`
+    Index(Box<Expr>, Box<Expr>),
+}
+
+// Formatting, evaluation, cloning and destruction use explicit stacks rather than recursion, so
+// that the depth of a tree is bounded by the memory rather than by the stack.
+enum Piece<'a> {
+    Expr(&'a Expr),
+    Text(&'a str),
 }
 
 impl Debug for Expr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            // Expr::Litteral(val) => write!(f, "Litteral({})", val),
-            Expr::Litteral(val) => write!(f, "{}", val),
-            Expr::Add(left, right) => write!(f, "Add({:?}, {:?})", left, right),
-            Expr::Prod(left, right) => write!(f, "Prod({:?}, {:?})", left, right),
-            Expr::Div(left, right) => write!(f, "Div({:?}, {:?})", left, right),
-            Expr::Sub(left, right) => write!(f, "Sub({:?}, {:?})", left, right),
-            Expr::Pow(left, right) => write!(f, "Pow({:?}, {:?})", left, right),
-            Expr::Mod(left, right) => write!(f, "Mod({:?}, {:?})", left, right),
+        let mut pieces = vec![Piece::Expr(self)];
+        while let Some(piece) = pieces.pop() {
+            let expr = match piece {
+                Piece::Text(text) => {
+                    write!(f, "{}", text)?;
+                    continue;
+                }
+                Piece::Expr(expr) => expr,
+            };
+
+            let (name, left, right) = match expr {
+                // Expr::Litteral(val) => write!(f, "Litteral({})", val),
+                Expr::Litteral(val) => {
+                    write!(f, "{}", val)?;
+                    continue;
+                }
+                Expr::Variable(name) => {
+                    write!(f, "{}", name)?;
+                    continue;
+                }
+                Expr::Call(name, args) => {
+                    // Pushed in reverse order
+                    pieces.push(Piece::Text(")"));
+                    for (i, arg) in args.iter().enumerate().rev() {
+                        pieces.push(Piece::Expr(arg));
+                        if i > 0 {
+                            pieces.push(Piece::Text(", "));
+                        }
+                    }
+                    pieces.push(Piece::Text("("));
+                    pieces.push(Piece::Text(name));
+                    continue;
+                }
+                Expr::Lambda(params, body) => {
+                    write!(f, "{}", lambda_params(params))?;
+                    pieces.push(Piece::Expr(body));
+                    continue;
+                }
+                Expr::List(items) => {
+                    pieces.push(Piece::Text("]"));
+                    for (i, item) in items.iter().enumerate().rev() {
+                        pieces.push(Piece::Expr(item));
+                        if i > 0 {
+                            pieces.push(Piece::Text(", "));
+                        }
+                    }
+                    pieces.push(Piece::Text("["));
+                    continue;
+                }
+                Expr::Index(list, index) => {
+                    pieces.extend([
+                        Piece::Text("]"),
+                        Piece::Expr(index),
+                        Piece::Text("["),
+                        Piece::Expr(list),
+                    ]);
+                    continue;
+                }
+                Expr::Add(left, right) => ("Add(", left, right),
+                Expr::Prod(left, right) => ("Prod(", left, right),
+                Expr::Div(left, right) => ("Div(", left, right),
+                Expr::Sub(left, right) => ("Sub(", left, right),
+                Expr::Pow(left, right) => ("Pow(", left, right),
+                Expr::Mod(left, right) => ("Mod(", left, right),
+            };
+            pieces.extend([
+                Piece::Text(")"),
+                Piece::Expr(right),
+                Piece::Text(", "),
+                Piece::Expr(left),
+                Piece::Text(name),
+            ]);
+        }
+        Ok(())
+    }
+}
+
+// Parameters of a lambda with its arrow, such as `x -> ` or `(x, y) -> `
+pub(super) fn lambda_params(params: &[String]) -> String {
+    match params {
+        [param] => format!("{param} -> "),
+        _ => format!("({}) -> ", params.join(", ")),
+    }
+}
+
+impl Drop for Expr {
+    fn drop(&mut self) {
+        // Children are detached before being dropped, so each drop only goes one level deep
+        let mut detached = vec![];
+        self.detach_children(&mut detached);
+        while let Some(mut expr) = detached.pop() {
+            expr.detach_children(&mut detached);
         }
     }
 }
 
+impl Clone for Expr {
+    fn clone(&self) -> Self {
+        // Post-order walk, the clones of the children are on `clones` when their parent is
+        // cloned
+        let mut pending = vec![(self, false)];
+        let mut clones: Vec<Expr> = vec![];
+        while let Some((expr, visited)) = pending.pop() {
+            let children = expr.children();
+            if !visited && !children.is_empty() {
+                pending.push((expr, true));
+                pending.extend(children.into_iter().rev().map(|child| (child, false)));
+                continue;
+            }
+
+            let mut args = clones.split_off(clones.len() - children.len()).into_iter();
+            let mut child = || Box::new(args.next().unwrap());
+            let clone = match expr {
+                Expr::Litteral(value) => Expr::Litteral(*value),
+                Expr::Variable(name) => Expr::Variable(name.clone()),
+                Expr::Add(..) => Expr::Add(child(), child()),
+                Expr::Prod(..) => Expr::Prod(child(), child()),
+                Expr::Div(..) => Expr::Div(child(), child()),
+                Expr::Sub(..) => Expr::Sub(child(), child()),
+                Expr::Pow(..) => Expr::Pow(child(), child()),
+                Expr::Mod(..) => Expr::Mod(child(), child()),
+                Expr::Call(name, _) => Expr::Call(name.clone(), args.collect()),
+                Expr::Lambda(params, _) => Expr::Lambda(params.clone(), child()),
+                Expr::List(_) => Expr::List(args.collect()),
+                Expr::Index(..) => Expr::Index(child(), child()),
+            };
+            clones.push(clone);
+        }
+        clones.pop().unwrap()
+    }
+}
+
+// Pending work of `free_variables`, the names of a scope are bound around its expression
+enum Scoped<'a> {
+    Expr(&'a Expr),
+    Bind(Vec<&'a str>),
+    Unbind(Vec<&'a str>),
+}
+
+pub(super) type BinaryOp<N> = fn(N, N) -> Result<N, String>;
+
+pub(super) enum Step<'a, N> {
+    Eval(&'a Expr),
+    Apply(BinaryOp<N>),
+}
+
 impl Expr {
     pub fn eval(&self) -> Result<T, String> {
-        match self {
-            Expr::Litteral(val) => Ok(*val),
-            Expr::Add(left, right) => Ok(left.eval()? + right.eval()?),
-            Expr::Prod(left, right) => Ok(left.eval()? * right.eval()?),
-            Expr::Sub(left, right) => Ok(left.eval()? - right.eval()?),
-            Expr::Div(left, right) => {
-                let left = left.eval()?;
-                let right = right.eval()?;
+        self.eval_with(&HashMap::new())
+    }
 
-                if right == T::from(0) {
-                    return Err(format!("Cannot divide {} by zero", left));
-                }
+    pub fn eval_with<N: Number>(&self, variables: &HashMap<String, N>) -> Result<N, String> {
+        self.eval_limited(variables, &Limits::default())
+    }
 
-                Ok(left / right)
-            }
-            Expr::Pow(left, right) => {
-                let left = left.eval()?;
-                let right = right.eval()?;
+    // Bounds of the values of the expression for the variables in their intervals
+    pub fn eval_interval(&self, variables: &HashMap<String, Interval>) -> Result<Interval, String> {
+        self.eval_with(variables)
+    }
+
+    pub fn eval_limited<N: Number>(
+        &self,
+        variables: &HashMap<String, N>,
+        limits: &Limits,
+    ) -> Result<N, String> {
+        self.eval_in(variables, &mut Context::new(limits))
+    }
+
+    pub fn eval_with_functions<N: Number>(
+        &self,
+        variables: &HashMap<String, N>,
+        functions: &Functions,
+        limits: &Limits,
+    ) -> Result<N, String> {
+        let globals = value::from_numbers(variables);
+        self.eval_in(
+            variables,
+            &mut Context::with_functions(limits, functions, globals),
+        )
+    }
+
+    pub(super) fn eval_in<N: Number>(
+        &self,
+        variables: &HashMap<String, N>,
+        context: &mut Context<N>,
+    ) -> Result<N, String> {
+        let mut steps = vec![Step::Eval(self)];
+        let mut values: Vec<N> = vec![];
 
-                if left == T::from(0) && right == T::from(0) {
-                    return Err("0^0 is undefined".to_string());
+        while let Some(step) = steps.pop() {
+            let expr = match step {
+                Step::Apply(operation) => {
+                    let right = values.pop().unwrap();
+                    let left = values.pop().unwrap();
+                    values.push(operation(left, right)?);
+                    continue;
                 }
+                Step::Eval(expr) => expr,
+            };
 
-                if right.is_negative() {
-                    return Err("Cannot raise to a negative power".to_string());
+            context.step()?;
+            let (left, right, operation): (_, _, BinaryOp<N>) = match expr {
+                Expr::Litteral(val) => {
+                    values.push(N::from_litteral(*val));
+                    continue;
                 }
+                Expr::Variable(name) => {
+                    let value = variables
+                        .get(name)
+                        .copied()
+                        .or_else(|| N::constant(name))
+                        .ok_or_else(|| format!("Unknown variable {name}"))?;
+                    values.push(value);
+                    continue;
+                }
+                Expr::Call(name, args) => {
+                    values.push(Expr::eval_call(name, args, variables, context)?);
+                    continue;
+                }
+                Expr::Lambda(..) | Expr::List(..) | Expr::Index(..) => {
+                    let variables = value::from_numbers(variables);
+                    let value = expr.eval_value_in(&Scope::Global(&variables), context)?;
+                    values.push(value.number()?);
+                    continue;
+                }
+                Expr::Add(left, right) => (left, right, N::add),
+                Expr::Prod(left, right) => (left, right, N::mul),
+                Expr::Sub(left, right) => (left, right, N::sub),
+                Expr::Div(left, right) => (left, right, N::div),
+                Expr::Pow(left, right) => (left, right, N::pow),
+                Expr::Mod(left, right) => (left, right, N::rem),
+            };
+            // The left operand is evaluated first, so its error wins
+            steps.extend([Step::Apply(operation), Step::Eval(right), Step::Eval(left)]);
+        }
 
-                Ok(left.pow(right as u32))
-            }
-            Expr::Mod(left, right) => {
-                let left = left.eval()?;
-                let right = right.eval()?;
+        Ok(values.pop().unwrap())
+    }
 
-                if right == T::from(0) {
-                    return Err(format!("Cannot divide {} by zero", left));
-                }
+    pub(super) fn eval_call<N: Number>(
+        name: &str,
+        args: &[Expr],
+        variables: &HashMap<String, N>,
+        context: &mut Context<N>,
+    ) -> Result<N, String> {
+        match name {
+            // The body is passed unevaluated and evaluated with the bound variable
+            "integrate" => {
+                let [body, var, a, b] = args else {
+                    return Err(format!(
+                        "integrate expects 4 arguments (expr, var, a, b), got {}",
+                        args.len()
+                    ));
+                };
+                N::approximation(name)?;
+                let var = Expr::bound_variable(name, var)?;
+                let a = a.eval_in(variables, context)?.to_real()?;
+                let b = b.eval_in(variables, context)?.to_real()?;
+
+                N::from_f64(calculus::integrate(body, var, a, b, variables, context)?)
+            }
+            "diff" => {
+                let [body, var, at] = args else {
+                    return Err(format!(
+                        "diff expects 3 arguments (expr, var, at), got {}",
+                        args.len()
+                    ));
+                };
+                N::approximation(name)?;
+                let var = Expr::bound_variable(name, var)?;
+                let at = at.eval_in(variables, context)?.to_real()?;
 
-                Ok(left % right)
+                N::from_f64(calculus::diff(body, var, at, variables, context)?)
             }
+            "decimal" => Expr::eval_decimal(args),
+            "unit" => Expr::eval_unit(args),
+            "iso_date" => Expr::eval_iso_date(args),
+            // The higher-order built-ins go through the evaluation of values
+            name if value::BUILTIN_FUNCTIONS.contains(&name) => {
+                let variables = value::from_numbers(variables);
+                Expr::call_value(name, args, &Scope::Global(&variables), context)?.number()
+            }
+            _ => match context.function(name) {
+                Some(function) => function.call(args, variables, context),
+                None => Err(format!("Unknown function {name}")),
+            },
         }
     }
 
-    fn build_next_expr(
-        operator: &str,
-        expressions_queue: &mut VecDeque<Expr>,
-    ) -> Result<(), String> {
-        // For now we only have operators with two arguments
-        if expressions_queue.len() < 2 {
-            return Err("Missmatched operator".to_string());
-        }
+    // Decimal litterals, 2.5 being decimal(25, 1)
+    pub(super) fn eval_decimal<N: Number>(args: &[Expr]) -> Result<N, String> {
+        let (digits, scale) = Expr::decimal_parts(args)?;
+        N::from_decimal(digits, scale)
+    }
 
-        let right = Box::new(expressions_queue.pop_back().unwrap());
-        let left = Box::new(expressions_queue.pop_back().unwrap());
-
-        let expr = match operator {
-            "+" => Expr::Add(left, right),
-            "-" => Expr::Sub(left, right),
-            "*" => Expr::Prod(left, right),
-            "/" => Expr::Div(left, right),
-            "%" => Expr::Mod(left, right),
-            "^" => Expr::Pow(left, right),
-            _ => unreachable!(),
+    // Units of the durations such as 3h 20min, unit(h) being the unit h even if a variable
+    // has its name
+    pub(super) fn eval_unit<N: Number>(args: &[Expr]) -> Result<N, String> {
+        let [Expr::Variable(name)] = args else {
+            return Err("unit expects the name of a unit".to_string());
         };
+        N::constant(name)
+            .ok_or_else(|| format!("{name} is a unit, evaluate it with durations or quantities"))
+    }
 
-        expressions_queue.push_back(expr);
-        Ok(())
+    // Date litterals, 2026-10-18 being iso_date(2026, 10, 18)
+    pub(super) fn eval_iso_date<N: Number>(args: &[Expr]) -> Result<N, String> {
+        let [Expr::Litteral(year), Expr::Litteral(month), Expr::Litteral(day)] = args else {
+            return Err("iso_date expects a year, a month and a day".to_string());
+        };
+        N::from_iso_date(*year, *month, *day)
+    }
+
+    pub(super) fn decimal_parts(args: &[Expr]) -> Result<(T, u32), String> {
+        let [Expr::Litteral(digits), Expr::Litteral(scale)] = args else {
+            return Err("decimal expects its digits and its number of decimals".to_string());
+        };
+        match u32::try_from(*scale) {
+            Ok(scale) if T::checked_pow(10, scale).is_some() => Ok((*digits, scale)),
+            _ => Err(format!("Invalid number of decimals {scale}")),
+        }
+    }
+
+    pub(super) fn bound_variable<'a>(function: &str, expr: &'a Expr) -> Result<&'a str, String> {
+        match expr {
+            Expr::Variable(name) => Ok(name),
+            _ => Err(format!(
+                "Expected a variable name in {function}, got {:?}",
+                expr
+            )),
+        }
     }
 
     pub fn parse(input: &str) -> Result<Expr, String> {
-        let mut operators_queue: VecDeque<String> = VecDeque::new();
-        let mut expressions_queue: VecDeque<Expr> = VecDeque::new();
-
-        let raw_tokens = tokenize(input)?;
-        let tokens = preprocess_tokens(raw_tokens)?;
-        for token in tokens {
-            if let Ok(value) = token.parse::<T>() {
-                expressions_queue.push_back(Expr::Litteral(value));
-            } else if OPERATORS_PRECEDENCE.contains_key(&token) {
-                while let Some(op2) = operators_queue.pop_back() {
-                    if op2 != "("
-                        && (OPERATORS_PRECEDENCE[&op2] > OPERATORS_PRECEDENCE[&token]
-                            || (OPERATORS_PRECEDENCE[&op2] == OPERATORS_PRECEDENCE[&token]
-                                && !RIGHT_ASSOCIATIVE_OPERATORS.contains(&token)))
-                    {
-                        Expr::build_next_expr(&op2, &mut expressions_queue)?;
-                    } else {
-                        operators_queue.push_back(op2);
-                        break;
-                    }
+        DEFAULT_CONFIG.parse(input)
+    }
+
+    // Variables the value of the expression depends on. The tree is walked with an
+    // explicit stack, the bound variables are counted while their scope is walked.
+    pub fn free_variables(&self) -> BTreeSet<String> {
+        let mut bound: HashMap<&str, usize> = HashMap::new();
+        let mut free_variables = BTreeSet::new();
+        let mut pending = vec![Scoped::Expr(self)];
+        while let Some(scoped) = pending.pop() {
+            let expr = match scoped {
+                Scoped::Bind(names) => {
+                    names
+                        .iter()
+                        .for_each(|name| *bound.entry(name).or_default() += 1);
+                    continue;
+                }
+                Scoped::Unbind(names) => {
+                    names
+                        .iter()
+                        .for_each(|name| *bound.get_mut(name).unwrap() -= 1);
+                    continue;
                 }
+                Scoped::Expr(expr) => expr,
+            };
 
-                operators_queue.push_back(token);
-            } else if token == "(" {
-                operators_queue.push_back(token);
-            } else if token == ")" {
-                loop {
-                    if operators_queue.is_empty() {
-                        return Err("Mismatched parentheses".to_string());
+            match expr {
+                Expr::Variable(name) => {
+                    if bound.get(name.as_str()).copied().unwrap_or(0) == 0 {
+                        free_variables.insert(name.clone());
                     }
-
-                    let op = operators_queue.pop_back().unwrap();
-                    if op == "(" {
-                        break;
+                }
+                Expr::Lambda(params, body) => {
+                    let params: Vec<&str> = params.iter().map(String::as_str).collect();
+                    pending.extend([
+                        Scoped::Unbind(params.clone()),
+                        Scoped::Expr(body),
+                        Scoped::Bind(params),
+                    ]);
+                }
+                // The argument of `unit` is the name of a unit
+                Expr::Call(name, _) if name == "unit" => {}
+                Expr::Call(name, args) => {
+                    // Positions of the variable bound by the call and of the expression it
+                    // is bound in
+                    let binding = match name.as_str() {
+                        "integrate" | "diff" => Some((1, 0)),
+                        "sum" | "prod" if args.len() == 4 => Some((0, 3)),
+                        _ => None,
+                    };
+                    for (i, arg) in args.iter().enumerate().rev() {
+                        match (binding, binding.and_then(|(bound, _)| args.get(bound))) {
+                            (Some((bound, _)), _) if i == bound => {}
+                            (Some((_, body)), Some(Expr::Variable(name))) if i == body => {
+                                pending.extend([
+                                    Scoped::Unbind(vec![name.as_str()]),
+                                    Scoped::Expr(arg),
+                                    Scoped::Bind(vec![name.as_str()]),
+                                ]);
+                            }
+                            _ => pending.push(Scoped::Expr(arg)),
+                        }
                     }
-
-                    Expr::build_next_expr(&op, &mut expressions_queue)?;
                 }
+                expr => pending.extend(expr.children().into_iter().rev().map(Scoped::Expr)),
             }
         }
+        free_variables
+    }
+
+    // Operands, arguments, items or body of the node, in the order they are written
+    pub(super) fn children(&self) -> Vec<&Expr> {
+        match self {
+            Expr::Litteral(_) | Expr::Variable(_) => vec![],
+            Expr::Add(left, right)
+            | Expr::Prod(left, right)
+            | Expr::Div(left, right)
+            | Expr::Sub(left, right)
+            | Expr::Pow(left, right)
+            | Expr::Mod(left, right)
+            | Expr::Index(left, right) => vec![left, right],
+            Expr::Call(_, args) | Expr::List(args) => args.iter().collect(),
+            Expr::Lambda(_, body) => vec![body],
+        }
+    }
 
-        while let Some(op) = operators_queue.pop_back() {
-            if op == "(" {
-                return Err("Mismatched parentheses".to_string());
+    pub(super) fn detach_children(&mut self, detached: &mut Vec<Expr>) {
+        match self {
+            Expr::Litteral(_) | Expr::Variable(_) => {}
+            Expr::Add(left, right)
+            | Expr::Prod(left, right)
+            | Expr::Div(left, right)
+            | Expr::Sub(left, right)
+            | Expr::Pow(left, right)
+            | Expr::Mod(left, right) => {
+                detached.push(std::mem::replace(left, Expr::Litteral(0)));
+                detached.push(std::mem::replace(right, Expr::Litteral(0)));
             }
+            Expr::Call(_, args) => detached.append(args),
+            Expr::Lambda(_, body) => detached.push(std::mem::replace(body, Expr::Litteral(0))),
+            Expr::List(items) => detached.append(items),
+            Expr::Index(list, index) => {
+                detached.push(std::mem::replace(list, Expr::Litteral(0)));
+                detached.push(std::mem::replace(index, Expr::Litteral(0)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::ParserConfig;
+
+    const DEPTH: usize = 300_000;
 
-            Expr::build_next_expr(&op, &mut expressions_queue)?;
+    // Built by hand since the default limits keep the parser from building such trees
+    fn deep_sum(depth: usize) -> Expr {
+        let mut expr = Expr::Litteral(1);
+        for i in 0..depth {
+            let one = Box::new(Expr::Litteral(1));
+            expr = if i % 2 == 0 {
+                Expr::Add(Box::new(expr), one)
+            } else {
+                Expr::Add(one, Box::new(expr))
+            };
         }
+        expr
+    }
+
+    #[test]
+    fn deep_eval() {
+        let expr = deep_sum(DEPTH);
+        assert_eq!(
+            expr.eval_limited(&HashMap::new(), &Limits::unlimited()),
+            Ok(DEPTH as T + 1)
+        );
+        assert_eq!(
+            Expr::parse("1 + 2 * (3 - 4) ^ 2 / 5 % 4").unwrap().eval(),
+            Ok(1)
+        );
+    }
+
+    #[test]
+    fn deep_debug() {
+        let formatted = format!("{:?}", deep_sum(DEPTH));
+        assert!(formatted.starts_with("Add(1, Add(Add(1, "));
+        assert_eq!(formatted.matches("Add(").count(), DEPTH);
+
+        let expr = Expr::parse("f(1, g(x, y) + 2, h())").unwrap();
+        assert_eq!(format!("{:?}", expr), "f(1, Add(g(x, y), 2), h())");
+    }
 
-        if expressions_queue.len() > 1 {
-            return Err(format!(
-                "Unable to parse {input}. Too many expressions remaining after parsing."
-            ));
+    #[test]
+    fn deep_parse_and_drop() {
+        let input = vec!["1"; DEPTH].join("+");
+        let config = ParserConfig::default().limits(Limits::unlimited());
+        let expr = config.parse(&input).unwrap();
+        assert_eq!(
+            expr.eval_limited::<T>(&HashMap::new(), &Limits::unlimited()),
+            Ok(DEPTH as T)
+        );
+        drop(expr);
+
+        let mut call = Expr::Litteral(0);
+        for _ in 0..DEPTH {
+            call = Expr::Call("f".to_string(), vec![call]);
         }
+        drop(call);
+    }
+
+    #[test]
+    fn deep_clone() {
+        let expr = deep_sum(DEPTH);
+        let clone = expr.clone();
+        assert_eq!(
+            clone.eval_limited(&HashMap::new(), &Limits::unlimited()),
+            Ok(DEPTH as T + 1)
+        );
+
+        let expr = Expr::parse("f(x -> [x, 2][1], y) - -3").unwrap();
+        assert_eq!(format!("{:?}", expr.clone()), format!("{:?}", expr));
+    }
+
+    #[test]
+    fn free_variables() {
+        let expr = Expr::parse("a * integrate(x * b, x, 0, c) + diff(y^2, y, d)").unwrap();
+        assert_eq!(
+            expr.free_variables().into_iter().collect::<Vec<_>>(),
+            vec!["a", "b", "c", "d"]
+        );
+        let expr = Expr::parse("map(x -> x * k, l) + sum(i, 1, n, i * j)").unwrap();
+        assert_eq!(
+            expr.free_variables().into_iter().collect::<Vec<_>>(),
+            vec!["j", "k", "l", "n"]
+        );
+        assert!(Expr::parse("integrate()")
+            .unwrap()
+            .free_variables()
+            .is_empty());
+        let expr = Expr::parse("x -> (y -> x + y + z)(1) + sum(y, 1, 2, x * y) + y").unwrap();
+        assert_eq!(
+            expr.free_variables().into_iter().collect::<Vec<_>>(),
+            vec!["y", "z"]
+        );
 
-        match expressions_queue.pop_back() {
-            Some(expr) => Ok(expr),
-            None => Err(format!("Unable to parse {input}")),
+        let mut expr = Expr::Variable("x".to_string());
+        for i in 0..DEPTH {
+            let y = Box::new(Expr::Variable(format!("y{}", i % 3)));
+            expr = Expr::Lambda(
+                vec!["x".to_string()],
+                Box::new(Expr::Add(Box::new(expr), y)),
+            );
         }
+        assert_eq!(
+            expr.free_variables().into_iter().collect::<Vec<_>>(),
+            vec!["y0", "y1", "y2"]
+        );
     }
 }
@@ -0,0 +1,201 @@
+use super::expr::Expr;
+use super::number::Number;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+// Named formulas referring to each other by name, like the cells of a spreadsheet:
+//
+//     let mut formulas = FormulaSet::new();
+//     formulas.insert("total", "subtotal + tax")?;
+//     formulas.insert("tax", "subtotal / 5")?;
+//     formulas.eval(&inputs)?; // with `subtotal` in the inputs
+//
+// The variables which aren't formulas are inputs. A formula shadows an input of the
+// same name.
+#[derive(Debug, Clone, Default)]
+pub struct FormulaSet {
+    formulas: BTreeMap<String, Expr>,
+}
+
+impl FormulaSet {
+    pub fn new() -> Self {
+        FormulaSet::default()
+    }
+
+    pub fn insert(&mut self, name: &str, input: &str) -> Result<(), String> {
+        let expr = Expr::parse(input).map_err(|e| format!("{name}: {e}"))?;
+        self.insert_expr(name, expr);
+        Ok(())
+    }
+
+    pub fn insert_expr(&mut self, name: &str, expr: Expr) {
+        self.formulas.insert(name.to_string(), expr);
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Expr> {
+        self.formulas.remove(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Expr> {
+        self.formulas.get(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.formulas.keys().map(|name| name.as_str())
+    }
+
+    // Formulas the given formula refers to directly
+    pub fn dependencies(&self, name: &str) -> BTreeSet<String> {
+        let Some(expr) = self.formulas.get(name) else {
+            return BTreeSet::new();
+        };
+        expr.free_variables()
+            .into_iter()
+            .filter(|variable| self.formulas.contains_key(variable))
+            .collect()
+    }
+
+    // Variables that have to be given to `eval`
+    pub fn inputs(&self) -> BTreeSet<String> {
+        self.formulas
+            .values()
+            .flat_map(|expr| expr.free_variables())
+            .filter(|variable| !self.formulas.contains_key(variable))
+            .collect()
+    }
+
+    // Every formula after the ones it depends on
+    pub fn order(&self) -> Result<Vec<String>, String> {
+        let dependencies: HashMap<&str, Vec<String>> = self
+            .names()
+            .map(|name| (name, self.dependencies(name).into_iter().collect()))
+            .collect();
+
+        // Depth first search, the formulas on `path` are the ones being visited
+        let mut done: BTreeSet<&str> = BTreeSet::new();
+        let mut order = vec![];
+        for root in self.names() {
+            if done.contains(root) {
+                continue;
+            }
+
+            let mut path: Vec<(&str, usize)> = vec![(root, 0)];
+            while let Some(&(name, next)) = path.last() {
+                let Some(dependency) = dependencies[name].get(next) else {
+                    path.pop();
+                    done.insert(name);
+                    order.push(name.to_string());
+                    continue;
+                };
+                path.last_mut().unwrap().1 += 1;
+
+                if let Some(start) = path.iter().position(|(name, _)| name == dependency) {
+                    let cycle: Vec<&str> = path[start..]
+                        .iter()
+                        .map(|(name, _)| *name)
+                        .chain([dependency.as_str()])
+                        .collect();
+                    return Err(format!("Circular reference: {}", cycle.join(" -> ")));
+                }
+                if !done.contains(dependency.as_str()) {
+                    path.push((dependency, 0));
+                }
+            }
+        }
+
+        Ok(order)
+    }
+
+    // Values of every formula
+    pub fn eval<N: Number>(
+        &self,
+        inputs: &HashMap<String, N>,
+    ) -> Result<BTreeMap<String, N>, String> {
+        let mut variables = inputs.clone();
+        let mut values = BTreeMap::new();
+        for name in self.order()? {
+            let value = self.formulas[&name]
+                .eval_with(&variables)
+                .map_err(|e| format!("{name}: {e}"))?;
+            variables.insert(name.clone(), value);
+            values.insert(name, value);
+        }
+        Ok(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::T;
+
+    fn invoice() -> FormulaSet {
+        let mut formulas = FormulaSet::new();
+        formulas.insert("total", "subtotal + tax").unwrap();
+        formulas.insert("tax", "subtotal / 5").unwrap();
+        formulas.insert("subtotal", "price * quantity").unwrap();
+        formulas
+    }
+
+    #[test]
+    fn order() {
+        let formulas = invoice();
+        assert_eq!(formulas.order().unwrap(), vec!["subtotal", "tax", "total"]);
+        assert_eq!(
+            formulas.inputs().into_iter().collect::<Vec<_>>(),
+            vec!["price", "quantity"]
+        );
+        assert_eq!(
+            formulas
+                .dependencies("total")
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec!["subtotal", "tax"]
+        );
+    }
+
+    #[test]
+    fn eval() {
+        let mut inputs: HashMap<String, T> = HashMap::new();
+        inputs.insert("price".to_string(), 25);
+        inputs.insert("quantity".to_string(), 4);
+
+        let values = invoice().eval(&inputs).unwrap();
+        assert_eq!(values["subtotal"], 100);
+        assert_eq!(values["tax"], 20);
+        assert_eq!(values["total"], 120);
+
+        inputs.remove("quantity");
+        assert_eq!(
+            invoice().eval(&inputs).unwrap_err(),
+            "subtotal: Unknown variable quantity"
+        );
+    }
+
+    #[test]
+    fn cycles() {
+        let mut formulas = invoice();
+        formulas.insert("price", "total - 1").unwrap();
+        assert_eq!(
+            formulas.order().unwrap_err(),
+            "Circular reference: price -> total -> subtotal -> price"
+        );
+
+        let mut formulas = FormulaSet::new();
+        formulas.insert("x", "x + 1").unwrap();
+        assert_eq!(formulas.order().unwrap_err(), "Circular reference: x -> x");
+        assert!(formulas.eval::<T>(&HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn long_chain() {
+        let mut formulas = FormulaSet::new();
+        formulas.insert("a0", "1").unwrap();
+        for i in 1..10_000 {
+            formulas
+                .insert(&format!("a{i}"), &format!("a{} + 1", i - 1))
+                .unwrap();
+        }
+        let values = formulas.eval::<T>(&HashMap::new()).unwrap();
+        assert_eq!(values["a9999"], 10_000);
+    }
+}
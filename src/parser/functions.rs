@@ -0,0 +1,177 @@
+use super::context::Context;
+use super::expr::Expr;
+use super::number::Number;
+use super::value;
+use super::visit::Folder;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
+
+const BUILTIN_FUNCTIONS: [&str; 2] = ["integrate", "diff"];
+
+pub type Functions = BTreeMap<String, Function>;
+
+// User defined function, such as `f(x, y) = x^2 + y^2`
+#[derive(Debug, Clone)]
+pub struct Function {
+    name: String,
+    params: Vec<String>,
+    // With the parameters renamed, see `Rename`
+    body: Expr,
+    definition: String,
+}
+
+// Renames the parameters of a function to names no variable can have, so that they
+// shadow the variables of the same name and can't be seen by the functions it calls
+struct Rename<'a> {
+    function: &'a str,
+    params: Vec<&'a str>,
+    // Parameters of the enclosing lambdas, which shadow the ones of the function
+    shadowing: Vec<Vec<String>>,
+}
+
+impl Rename<'_> {
+    fn rename(&self, name: String) -> String {
+        if self.params.contains(&name.as_str())
+            && !self.shadowing.iter().flatten().any(|param| *param == name)
+        {
+            format!("{}#{}", self.function, name)
+        } else {
+            name
+        }
+    }
+}
+
+impl Folder for Rename<'_> {
+    fn fold_variable(&mut self, name: String) -> Expr {
+        Expr::Variable(self.rename(name))
+    }
+
+    // A parameter can be a lambda, which is called by its name. The argument of `unit` is
+    // the name of a unit, which keeps its name.
+    fn fold_call(&mut self, name: String, args: Vec<Expr>) -> Expr {
+        if name == "unit" {
+            let prefix = format!("{}#", self.function);
+            let args = args
+                .into_iter()
+                .map(|arg| match &arg {
+                    Expr::Variable(unit) => match unit.strip_prefix(&prefix) {
+                        Some(unit) => Expr::Variable(unit.to_string()),
+                        None => arg,
+                    },
+                    _ => arg,
+                })
+                .collect();
+            return Expr::Call(name, args);
+        }
+        Expr::Call(self.rename(name), args)
+    }
+
+    // The parameters of a lambda shadow the ones of the function in its body
+    fn enter(&mut self, expr: &Expr) {
+        if let Expr::Lambda(params, _) = expr {
+            self.shadowing.push(params.clone());
+        }
+    }
+
+    fn fold_lambda(&mut self, params: Vec<String>, body: Expr) -> Expr {
+        self.shadowing.pop();
+        Expr::Lambda(params, Box::new(body))
+    }
+}
+
+impl Function {
+    pub fn is_builtin(name: &str) -> bool {
+        BUILTIN_FUNCTIONS.contains(&name) || value::BUILTIN_FUNCTIONS.contains(&name)
+    }
+
+    // `definition` is the text of the body, as written by the user
+    pub fn new(
+        name: &str,
+        params: Vec<String>,
+        body: Expr,
+        definition: &str,
+    ) -> Result<Function, String> {
+        if Function::is_builtin(name) {
+            return Err(format!("Cannot redefine the built-in function {name}"));
+        }
+        for (i, param) in params.iter().enumerate() {
+            if params[..i].contains(param) {
+                return Err(format!("Duplicate parameter {param} in {name}"));
+            }
+        }
+
+        let body = Rename {
+            function: name,
+            params: params.iter().map(String::as_str).collect(),
+            shadowing: vec![],
+        }
+        .fold_expr(body);
+
+        Ok(Function {
+            definition: format!("{name}({}) = {definition}", params.join(", ")),
+            name: name.to_string(),
+            params,
+            body,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn params(&self) -> &[String] {
+        &self.params
+    }
+
+    // Body with the parameters renamed, to be evaluated with `param_name` bound to the
+    // arguments
+    pub(super) fn body(&self) -> &Expr {
+        &self.body
+    }
+
+    pub(super) fn param_name(&self, param: &str) -> String {
+        format!("{}#{}", self.name, param)
+    }
+
+    pub(super) fn call<N: Number>(
+        &self,
+        args: &[Expr],
+        variables: &HashMap<String, N>,
+        context: &mut Context<N>,
+    ) -> Result<N, String> {
+        check_arity(&self.name, &self.params, args.len())?;
+
+        // The body sees the globals, not the variables bound by its callers
+        let mut scope: HashMap<String, N> = context
+            .globals()
+            .iter()
+            .filter_map(|(name, value)| Some((name.clone(), value.clone().number().ok()?)))
+            .collect();
+        for (param, arg) in self.params.iter().zip(args) {
+            let value = arg.eval_in(variables, context)?;
+            scope.insert(self.param_name(param), value);
+        }
+
+        context.enter_call(&self.name)?;
+        let result = self.body.eval_in(&scope, context);
+        context.leave_call();
+        result
+    }
+}
+
+pub(super) fn check_arity(name: &str, params: &[String], args: usize) -> Result<(), String> {
+    if args != params.len() {
+        return Err(format!(
+            "{name} expects {} arguments ({}), got {args}",
+            params.len(),
+            params.join(", "),
+        ));
+    }
+    Ok(())
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.definition)
+    }
+}
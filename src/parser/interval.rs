@@ -0,0 +1,476 @@
+use super::number::Number;
+use super::T;
+use std::cmp::Ordering;
+use std::fmt::Display;
+
+// Closed interval of reals. The bounds of the results are rounded outward, so that the
+// result of an evaluation contains every value the expression takes for the values of
+// its variables in their intervals. A bound is infinite after a division by an interval
+// containing zero.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Interval {
+    lo: f64,
+    hi: f64,
+}
+
+// Bounds of an exact value, given its rounded value and the sign of the rounding error
+// (exact - rounded). A NaN error, from an infinite or a too small value, widens both.
+fn enclose(rounded: f64, error: f64) -> (f64, f64) {
+    match error.partial_cmp(&0.0) {
+        Some(Ordering::Equal) => (rounded, rounded),
+        Some(Ordering::Greater) => (rounded, rounded.next_up()),
+        Some(Ordering::Less) => (rounded.next_down(), rounded),
+        None => (rounded.next_down(), rounded.next_up()),
+    }
+}
+
+// The error terms computed with fma lose their sign below the normal numbers
+fn normal(rounded: f64, error: f64) -> f64 {
+    if rounded != 0.0 && rounded.abs() < f64::MIN_POSITIVE * 2f64.powi(53) {
+        return f64::NAN;
+    }
+    error
+}
+
+fn sum(a: f64, b: f64) -> (f64, f64) {
+    let sum = a + b;
+    let b_part = sum - a;
+    enclose(sum, (a - (sum - b_part)) + (b - b_part))
+}
+
+fn product(a: f64, b: f64) -> (f64, f64) {
+    // 0 * inf only comes from a bound multiplied by the bound of a half-line
+    if a == 0.0 || b == 0.0 {
+        return (0.0, 0.0);
+    }
+    let product = a * b;
+    enclose(product, normal(product, a.mul_add(b, -product)))
+}
+
+fn quotient(a: f64, b: f64) -> (f64, f64) {
+    let quotient = a / b;
+    let remainder = -quotient.mul_add(b, -a);
+    enclose(quotient, normal(quotient, remainder * b.signum()))
+}
+
+// Bounds of x^n for x >= 0, by squaring
+fn power(x: f64, mut n: u32) -> (f64, f64) {
+    let (mut base, mut result) = ((x, x), (1.0, 1.0));
+    while n > 0 {
+        if n % 2 == 1 {
+            result = (product(result.0, base.0).0, product(result.1, base.1).1);
+        }
+        base = (product(base.0, base.0).0, product(base.1, base.1).1);
+        n /= 2;
+    }
+    result
+}
+
+// Bounds of a function of the math library, whose results are within an ulp but aren't
+// correctly rounded
+fn approximate(value: f64) -> (f64, f64) {
+    (value.next_down().next_down(), value.next_up().next_up())
+}
+
+impl Interval {
+    pub fn new(lo: f64, hi: f64) -> Result<Interval, String> {
+        if lo.is_nan() || hi.is_nan() || lo > hi || lo == f64::INFINITY || hi == -f64::INFINITY {
+            return Err(format!("[{lo}, {hi}] is not a valid interval"));
+        }
+
+        Ok(Interval { lo, hi })
+    }
+
+    pub fn point(value: f64) -> Interval {
+        Interval {
+            lo: value,
+            hi: value,
+        }
+    }
+
+    pub fn lo(&self) -> f64 {
+        self.lo
+    }
+
+    pub fn hi(&self) -> f64 {
+        self.hi
+    }
+
+    pub fn contains(&self, value: f64) -> bool {
+        self.lo <= value && value <= self.hi
+    }
+
+    fn is_point(&self) -> bool {
+        self.lo == self.hi
+    }
+
+    // Smallest interval containing all the bounds
+    fn hull(bounds: impl IntoIterator<Item = (f64, f64)>) -> Interval {
+        bounds.into_iter().fold(
+            Interval {
+                lo: f64::INFINITY,
+                hi: -f64::INFINITY,
+            },
+            |hull, (lo, hi)| Interval {
+                lo: hull.lo.min(lo),
+                hi: hull.hi.max(hi),
+            },
+        )
+    }
+
+    fn corners(self, other: Interval, f: fn(f64, f64) -> (f64, f64)) -> Interval {
+        Interval::hull([
+            f(self.lo, other.lo),
+            f(self.lo, other.hi),
+            f(self.hi, other.lo),
+            f(self.hi, other.hi),
+        ])
+    }
+
+    // x^n for an integer n >= 0, even powers being positive
+    fn powi(self, n: u32) -> Interval {
+        let (lo, hi) = (power(self.lo.abs(), n), power(self.hi.abs(), n));
+        if n % 2 == 1 {
+            let signed = |x: f64, (low, high): (f64, f64)| match x < 0.0 {
+                true => (-high, -low),
+                false => (low, high),
+            };
+            return Interval::hull([signed(self.lo, lo), signed(self.hi, hi)]);
+        }
+        match self.contains(0.0) {
+            true => Interval::hull([(0.0, 0.0), (0.0, lo.1.max(hi.1))]),
+            false => Interval::hull([lo, hi]),
+        }
+    }
+}
+
+impl Display for Interval {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.is_point() {
+            true => write!(f, "{}", self.lo),
+            false => write!(f, "[{}, {}]", self.lo, self.hi),
+        }
+    }
+}
+
+impl Number for Interval {
+    fn from_litteral(value: T) -> Self {
+        let rounded = value as f64;
+        let (lo, hi) = match (rounded as T).cmp(&value) {
+            Ordering::Equal => (rounded, rounded),
+            Ordering::Less => (rounded, rounded.next_up()),
+            Ordering::Greater => (rounded.next_down(), rounded),
+        };
+        Interval { lo, hi }
+    }
+
+    fn from_f64(value: f64) -> Result<Self, String> {
+        if !value.is_finite() {
+            return Err(format!("{value} is not a finite number"));
+        }
+
+        Ok(Interval::point(value))
+    }
+
+    // Middle of the interval, for the callers needing a single number
+    fn to_f64(self) -> f64 {
+        self.lo / 2.0 + self.hi / 2.0
+    }
+
+    fn add(self, other: Self) -> Result<Self, String> {
+        Ok(Interval {
+            lo: sum(self.lo, other.lo).0,
+            hi: sum(self.hi, other.hi).1,
+        })
+    }
+
+    fn sub(self, other: Self) -> Result<Self, String> {
+        Ok(Interval {
+            lo: sum(self.lo, -other.hi).0,
+            hi: sum(self.hi, -other.lo).1,
+        })
+    }
+
+    fn mul(self, other: Self) -> Result<Self, String> {
+        Ok(self.corners(other, product))
+    }
+
+    // A divisor containing zero gives half-lines, or the whole line when zero is inside
+    // it, which is the hull of the two half-lines of the quotients
+    fn div(self, other: Self) -> Result<Self, String> {
+        if other == Interval::point(0.0) {
+            return Err(format!("Cannot divide {} by zero", self));
+        }
+        if self == Interval::point(0.0) {
+            return Ok(self);
+        }
+
+        match (other.lo, other.hi) {
+            (0.0, hi) => self.mul(Interval {
+                lo: quotient(1.0, hi).0,
+                hi: f64::INFINITY,
+            }),
+            (lo, 0.0) => self.mul(Interval {
+                lo: -f64::INFINITY,
+                hi: quotient(1.0, lo).1,
+            }),
+            (lo, hi) if lo < 0.0 && hi > 0.0 => Ok(Interval {
+                lo: -f64::INFINITY,
+                hi: f64::INFINITY,
+            }),
+            _ => Ok(self.corners(other, quotient)),
+        }
+    }
+
+    // The remainder has the sign of the dividend, and is smaller than it and the divisor
+    fn rem(self, other: Self) -> Result<Self, String> {
+        if other == Interval::point(0.0) {
+            return Err(format!("Cannot divide {} by zero", self));
+        }
+        if self.is_point() && other.is_point() {
+            return Ok(Interval::point(self.lo % other.lo));
+        }
+
+        let bound = other.lo.abs().max(other.hi.abs());
+        Ok(Interval {
+            lo: self.lo.min(0.0).max(-bound),
+            hi: self.hi.max(0.0).min(bound),
+        })
+    }
+
+    fn pow(self, other: Self) -> Result<Self, String> {
+        if self == Interval::point(0.0) && other == Interval::point(0.0) {
+            return Err("0^0 is undefined".to_string());
+        }
+
+        let exponent = other.lo;
+        if other.is_point() && exponent.fract() == 0.0 && exponent.abs() <= u32::MAX as f64 {
+            let power = self.powi(exponent.abs() as u32);
+            if exponent < 0.0 {
+                return Interval::point(1.0).div(power);
+            }
+            return Ok(power);
+        }
+
+        // x^y is monotonic in x and in y for x >= 0, its bounds are at the corners
+        if self.lo < 0.0 {
+            return Err(format!(
+                "{self}^{other} is not a real number for the negative values of {self}"
+            ));
+        }
+        let corners = self.corners(other, |x, y| approximate(x.powf(y)));
+        Ok(Interval {
+            lo: corners.lo.max(0.0),
+            hi: corners.hi,
+        })
+    }
+
+    // sqrt is correctly rounded, its error is the sign of x - sqrt(x)^2
+    fn sqrt(self) -> Result<Self, String> {
+        if self.lo < 0.0 {
+            return Err(format!("sqrt({self}) is not a real number"));
+        }
+
+        let root = |x: f64| {
+            let root = x.sqrt();
+            enclose(root, normal(root, -root.mul_add(root, -x)))
+        };
+        Ok(Interval {
+            lo: root(self.lo).0.max(0.0),
+            hi: root(self.hi).1,
+        })
+    }
+
+    fn ln(self) -> Result<Self, String> {
+        if self == Interval::point(0.0) {
+            return Err("ln(0) is undefined".to_string());
+        }
+        if self.lo <= 0.0 {
+            return Err(format!("ln({self}) is not a real number"));
+        }
+
+        Ok(Interval {
+            lo: approximate(self.lo.ln()).0,
+            hi: approximate(self.hi.ln()).1,
+        })
+    }
+
+    fn abs(self) -> Result<Self, String> {
+        Ok(match (self.lo >= 0.0, self.hi <= 0.0) {
+            (true, _) => self,
+            (_, true) => Interval {
+                lo: -self.hi,
+                hi: -self.lo,
+            },
+            _ => Interval {
+                lo: 0.0,
+                hi: self.hi.max(-self.lo),
+            },
+        })
+    }
+
+    fn arg(self) -> Result<Self, String> {
+        let pi = (std::f64::consts::PI, std::f64::consts::PI.next_up());
+        Ok(match (self.lo >= 0.0, self.hi < 0.0) {
+            (true, _) => Interval::point(0.0),
+            (_, true) => Interval { lo: pi.0, hi: pi.1 },
+            _ => Interval { lo: 0.0, hi: pi.1 },
+        })
+    }
+
+    // The error of a numerical method isn't known, its result has no guaranteed bounds
+    fn approximation(function: &str) -> Result<(), String> {
+        Err(format!(
+            "{function} only approximates its result, it can't give guaranteed bounds"
+        ))
+    }
+
+    // Rounding is monotonic, each bound is rounded and widened by the error of the scaling
+    fn round(self, digits: i32) -> Result<Self, String> {
+        let round = |x: f64| match x.is_finite() {
+            true => Number::round(x, digits),
+            false => Ok(x),
+        };
+        Ok(Interval {
+            lo: round(self.lo)?.next_down(),
+            hi: round(self.hi)?.next_up(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Expr;
+
+    fn interval(lo: f64, hi: f64) -> Interval {
+        Interval::new(lo, hi).unwrap()
+    }
+
+    fn eval(input: &str, variables: &[(&str, Interval)]) -> Result<Interval, String> {
+        let variables = variables
+            .iter()
+            .map(|(name, value)| (name.to_string(), *value))
+            .collect();
+        Expr::parse(input)?.eval_interval(&variables)
+    }
+
+    #[test]
+    fn arithmetic() {
+        let (a, b) = (interval(1.0, 2.0), interval(-3.0, 4.0));
+        assert_eq!(a.add(b), Ok(interval(-2.0, 6.0)));
+        assert_eq!(a.sub(b), Ok(interval(-3.0, 5.0)));
+        assert_eq!(a.mul(b), Ok(interval(-6.0, 8.0)));
+        assert_eq!(b.mul(b), Ok(interval(-12.0, 16.0)));
+        assert_eq!(b.abs(), Ok(interval(0.0, 4.0)));
+        assert_eq!(
+            eval("x - x", &[("x", a)]),
+            Ok(interval(-1.0, 1.0)),
+            "every occurrence of a variable is independent"
+        );
+        assert_eq!(eval("7 % 3", &[]), Ok(Interval::point(1.0)));
+        assert_eq!(eval("x % 3", &[("x", b)]), Ok(interval(-3.0, 3.0)));
+    }
+
+    #[test]
+    fn division() {
+        let a = interval(1.0, 2.0);
+        assert_eq!(a.div(interval(4.0, 8.0)), Ok(interval(0.125, 0.5)));
+        assert_eq!(a.div(interval(0.0, 4.0)), Ok(interval(0.25, f64::INFINITY)));
+        assert_eq!(
+            a.div(interval(-4.0, 0.0)),
+            Ok(interval(-f64::INFINITY, -0.25))
+        );
+        assert_eq!(
+            a.div(interval(-1.0, 1.0)),
+            Ok(interval(-f64::INFINITY, f64::INFINITY))
+        );
+        assert_eq!(
+            interval(-1.0, 1.0).div(interval(0.0, 1.0)),
+            Ok(interval(-f64::INFINITY, f64::INFINITY))
+        );
+        assert_eq!(
+            Interval::point(0.0).div(interval(-1.0, 1.0)),
+            Ok(Interval::point(0.0))
+        );
+        assert_eq!(
+            eval("1 / x", &[("x", Interval::point(0.0))]).unwrap_err(),
+            "Cannot divide 1 by zero"
+        );
+    }
+
+    #[test]
+    fn powers() {
+        let x = interval(-2.0, 3.0);
+        assert_eq!(eval("x^2", &[("x", x)]), Ok(interval(0.0, 9.0)));
+        assert_eq!(eval("x^3", &[("x", x)]), Ok(interval(-8.0, 27.0)));
+        assert_eq!(
+            eval("x^2", &[("x", interval(-3.0, -2.0))]),
+            Ok(interval(4.0, 9.0))
+        );
+        // x * x doesn't know that both factors are the same
+        assert_eq!(eval("x * x", &[("x", x)]), Ok(interval(-6.0, 9.0)));
+        assert_eq!(
+            eval("x^-1", &[("x", interval(2.0, 4.0))]),
+            Ok(interval(0.25, 0.5))
+        );
+        assert_eq!(
+            eval("sqrt(x)", &[("x", interval(4.0, 9.0))]),
+            Ok(interval(2.0, 3.0))
+        );
+
+        let root = eval("x^0.5", &[("x", interval(4.0, 9.0))]).unwrap();
+        assert!(root.lo() < 2.0 && root.contains(2.0) && root.contains(3.0) && root.hi() > 3.0);
+        assert!(eval("x^0.5", &[("x", x)]).is_err());
+        assert!(eval("sqrt(x)", &[("x", x)]).is_err());
+    }
+
+    #[test]
+    fn approximations() {
+        assert_eq!(
+            eval("integrate(x^2, x, 0, 1)", &[]).unwrap_err(),
+            "integrate only approximates its result, it can't give guaranteed bounds"
+        );
+        assert_eq!(
+            eval("diff(x^3, x, 1)", &[]).unwrap_err(),
+            "diff only approximates its result, it can't give guaranteed bounds"
+        );
+    }
+
+    #[test]
+    fn outward_rounding() {
+        // 0.1 and 0.2 aren't floats, the sum contains the exact 3/10
+        let sum = eval("0.1 + 0.2", &[]).unwrap();
+        assert!(sum.lo() < 0.3 && 0.3 < sum.hi());
+        assert!(sum.hi() - sum.lo() < 1e-15);
+        let third = eval("1 / 3", &[]).unwrap();
+        assert_eq!(third.lo().next_up(), third.hi());
+        assert!(third.lo() * 3.0 <= 1.0);
+
+        // Exact operations aren't widened
+        assert_eq!(eval("(1 + 2) * 3 / 4", &[]), Ok(Interval::point(2.25)));
+        let big = eval("2^60 + 1", &[]).unwrap();
+        assert_eq!(
+            (big.lo(), big.hi()),
+            (2f64.powi(60), 2f64.powi(60).next_up())
+        );
+    }
+
+    #[test]
+    fn stack_up() {
+        // Gap left by three parts of 10 ± 0.1 mm in a housing of 30.5 ± 0.2 mm
+        let part = interval(9.9, 10.1);
+        let housing = interval(30.3, 30.7);
+        let gap = eval(
+            "housing - (a + b + c)",
+            &[("housing", housing), ("a", part), ("b", part), ("c", part)],
+        )
+        .unwrap();
+        assert!(gap.lo().abs() < 1e-12 && (gap.hi() - 1.0).abs() < 1e-12);
+        assert_eq!(
+            Interval::new(2.0, 1.0).unwrap_err(),
+            "[2, 1] is not a valid interval"
+        );
+        assert_eq!(interval(1.0, 2.5).to_string(), "[1, 2.5]");
+    }
+}
@@ -0,0 +1,178 @@
+// Bounds on the work done for a single input, to keep untrusted input from exhausting
+// the memory, the stack or the CPU
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    // In characters
+    pub max_input_length: usize,
+    pub max_tokens: usize,
+    // Nesting of parentheses, calls, prefix and right associative operators
+    pub max_depth: usize,
+    pub max_nodes: usize,
+    // Nodes evaluated, including the ones evaluated by integrate and diff
+    pub max_eval_steps: usize,
+    // Nested calls to user defined functions
+    pub max_call_depth: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_input_length: 10_000,
+            max_tokens: 10_000,
+            max_depth: 256,
+            max_nodes: 10_000,
+            max_eval_steps: 10_000_000,
+            max_call_depth: 100,
+        }
+    }
+}
+
+impl Limits {
+    pub fn unlimited() -> Self {
+        Limits {
+            max_input_length: usize::MAX,
+            max_tokens: usize::MAX,
+            max_depth: usize::MAX,
+            max_nodes: usize::MAX,
+            max_eval_steps: usize::MAX,
+            max_call_depth: usize::MAX,
+        }
+    }
+
+    pub(super) fn check_input_length(&self, input: &str) -> Result<(), String> {
+        let length = input.chars().count();
+        if length > self.max_input_length {
+            return Err(format!(
+                "Input is too long ({length} characters, the limit is {})",
+                self.max_input_length
+            ));
+        }
+        Ok(())
+    }
+
+    pub(super) fn check_tokens(&self, tokens: usize) -> Result<(), String> {
+        if tokens > self.max_tokens {
+            return Err(format!(
+                "Input has too many tokens (the limit is {})",
+                self.max_tokens
+            ));
+        }
+        Ok(())
+    }
+
+    pub(super) fn check_depth(&self, depth: usize) -> Result<(), String> {
+        if depth > self.max_depth {
+            return Err(format!(
+                "Expression is nested too deeply (the limit is {} levels)",
+                self.max_depth
+            ));
+        }
+        Ok(())
+    }
+
+    pub(super) fn check_nodes(&self, nodes: usize) -> Result<(), String> {
+        if nodes > self.max_nodes {
+            return Err(format!(
+                "Expression is too large (the limit is {} nodes)",
+                self.max_nodes
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Expr, ParserConfig};
+    use std::collections::HashMap;
+
+    fn config(limits: Limits) -> ParserConfig {
+        ParserConfig::default().limits(limits)
+    }
+
+    #[test]
+    fn input_length() {
+        let limits = Limits {
+            max_input_length: 5,
+            ..Limits::default()
+        };
+        assert!(config(limits).parse("1 + 2").is_ok());
+        assert_eq!(
+            config(limits).parse("1 + 23").unwrap_err(),
+            "Input is too long (6 characters, the limit is 5)"
+        );
+    }
+
+    #[test]
+    fn tokens() {
+        let limits = Limits {
+            max_tokens: 3,
+            ..Limits::default()
+        };
+        assert!(config(limits).parse("1 + 2").is_ok());
+        assert_eq!(
+            config(limits).parse("1+2+3").unwrap_err(),
+            "Input has too many tokens (the limit is 3)"
+        );
+    }
+
+    #[test]
+    fn depth() {
+        let limits = Limits {
+            max_depth: 4,
+            ..Limits::default()
+        };
+        assert!(config(limits).parse("((1))").is_ok());
+        assert!(config(limits).parse("1 + 1 + 1 + 1 + 1 + 1").is_ok());
+        assert_eq!(
+            config(limits).parse("((((1))))").unwrap_err(),
+            "Expression is nested too deeply (the limit is 4 levels)"
+        );
+        assert!(config(limits).parse("2^2^2^2^2").is_err());
+
+        let deep = format!("{}1{}", "(".repeat(100_000), ")".repeat(100_000));
+        assert!(Expr::parse(&deep).is_err());
+        let deep = format!("{}1{}", "(".repeat(200), ")".repeat(200));
+        assert!(Expr::parse(&deep).is_ok());
+    }
+
+    #[test]
+    fn nodes() {
+        let limits = Limits {
+            max_nodes: 5,
+            ..Limits::default()
+        };
+        assert!(config(limits).parse("1 + 2 * 3").is_ok());
+        assert_eq!(
+            config(limits).parse("1 + 2 * 3 - 4").unwrap_err(),
+            "Expression is too large (the limit is 5 nodes)"
+        );
+    }
+
+    #[test]
+    fn eval_steps() {
+        let limits = Limits {
+            max_eval_steps: 5,
+            ..Limits::default()
+        };
+        let variables: HashMap<String, i128> = HashMap::new();
+        let expr = Expr::parse("1 + 2 * 3").unwrap();
+        assert_eq!(expr.eval_limited(&variables, &limits).unwrap(), 7);
+
+        let expr = Expr::parse("1 + 2 * 3 - 4").unwrap();
+        assert_eq!(
+            expr.eval_limited(&variables, &limits).unwrap_err(),
+            "Evaluation takes too many steps (the limit is 5)"
+        );
+
+        // Steps spent by integrate count towards the same limit
+        let limits = Limits {
+            max_eval_steps: 10,
+            ..Limits::default()
+        };
+        let expr = Expr::parse("integrate(x^2, x, 0, 3)").unwrap();
+        assert!(expr.eval_limited(&variables, &limits).is_err());
+        assert_eq!(expr.eval(), Ok(9));
+    }
+}
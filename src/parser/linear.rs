@@ -0,0 +1,317 @@
+use super::expr::Expr;
+use super::number::Number;
+use super::rational::Rational;
+use super::solver::Equation;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Display;
+
+#[derive(Debug, PartialEq)]
+pub enum SystemSolution {
+    Unique(BTreeMap<String, Rational>),
+    Inconsistent,
+    // Equations without unknowns which all hold, such as 1 = 1
+    Identity,
+    // Unknowns that can take any value
+    Underdetermined(Vec<String>),
+}
+
+impl Display for SystemSolution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SystemSolution::Unique(values) => {
+                for (i, (name, value)) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{} = {}", name, value)?;
+                }
+                Ok(())
+            }
+            SystemSolution::Inconsistent => write!(f, "The system has no solution"),
+            SystemSolution::Identity => write!(f, "Every value is a solution"),
+            SystemSolution::Underdetermined(free) => write!(
+                f,
+                "The system has infinitely many solutions, {} can take any value",
+                free.join(", ")
+            ),
+        }
+    }
+}
+
+// sum(terms[x] * x) + constant
+#[derive(Debug, Default)]
+struct LinearForm {
+    terms: BTreeMap<String, Rational>,
+    constant: Rational,
+}
+
+impl LinearForm {
+    fn constant(value: Rational) -> LinearForm {
+        LinearForm {
+            terms: BTreeMap::new(),
+            constant: value,
+        }
+    }
+
+    fn as_constant(&self) -> Option<Rational> {
+        self.terms.is_empty().then_some(self.constant)
+    }
+
+    fn add(mut self, other: LinearForm) -> Result<LinearForm, String> {
+        for (name, coefficient) in other.terms {
+            let term = self.terms.entry(name).or_default();
+            *term = term.add(coefficient)?;
+        }
+        self.constant = self.constant.add(other.constant)?;
+        self.terms.retain(|_, coefficient| !coefficient.is_zero());
+        Ok(self)
+    }
+
+    fn sub(self, other: LinearForm) -> Result<LinearForm, String> {
+        self.add(other.scale(Rational::integer(-1))?)
+    }
+
+    fn scale(mut self, factor: Rational) -> Result<LinearForm, String> {
+        for coefficient in self.terms.values_mut() {
+            *coefficient = coefficient.mul(factor)?;
+        }
+        self.constant = self.constant.mul(factor)?;
+        self.terms.retain(|_, coefficient| !coefficient.is_zero());
+        Ok(self)
+    }
+
+    fn parse(expr: &Expr) -> Result<LinearForm, String> {
+        let not_linear = || Err(format!("{:?} is not linear", expr));
+
+        match expr {
+            Expr::Litteral(value) => Ok(LinearForm::constant(Rational::integer(*value))),
+            Expr::Variable(name) => Ok(LinearForm {
+                terms: BTreeMap::from([(name.clone(), Rational::ONE)]),
+                constant: Rational::ZERO,
+            }),
+            Expr::Add(left, right) => LinearForm::parse(left)?.add(LinearForm::parse(right)?),
+            Expr::Sub(left, right) => LinearForm::parse(left)?.sub(LinearForm::parse(right)?),
+            Expr::Prod(left, right) => {
+                let left = LinearForm::parse(left)?;
+                let right = LinearForm::parse(right)?;
+                match (left.as_constant(), right.as_constant()) {
+                    (Some(factor), _) => right.scale(factor),
+                    (_, Some(factor)) => left.scale(factor),
+                    _ => not_linear(),
+                }
+            }
+            Expr::Div(left, right) => match LinearForm::parse(right)?.as_constant() {
+                Some(divisor) if divisor.is_zero() => {
+                    Err(format!("Cannot divide {:?} by zero", left))
+                }
+                Some(divisor) => LinearForm::parse(left)?.scale(divisor.recip()?),
+                None => not_linear(),
+            },
+            Expr::Pow(base, exponent) => {
+                let base = LinearForm::parse(base)?;
+                let Some(exponent) = LinearForm::parse(exponent)?.as_constant() else {
+                    return not_linear();
+                };
+                match base.as_constant() {
+                    Some(base) => Ok(LinearForm::constant(base.pow(exponent)?)),
+                    None if exponent == Rational::ONE => Ok(base),
+                    None => not_linear(),
+                }
+            }
+            Expr::Mod(..)
+            | Expr::Call(..)
+            | Expr::Lambda(..)
+            | Expr::List(..)
+            | Expr::Index(..) => {
+                if !expr.free_variables().is_empty() {
+                    return not_linear();
+                }
+                Ok(LinearForm::constant(
+                    expr.eval_with::<Rational>(&HashMap::new())?,
+                ))
+            }
+        }
+    }
+}
+
+// Solves a system of linear equations separated by ';' with an exact Gaussian
+// elimination.
+pub fn solve_system(input: &str) -> Result<SystemSolution, String> {
+    let equations = input
+        .split(';')
+        .filter(|equation| !equation.trim().is_empty())
+        .map(Equation::parse)
+        .collect::<Result<Vec<_>, _>>()?;
+    if equations.is_empty() {
+        return Err("Expected at least one equation".to_string());
+    }
+
+    // Each equation becomes sum(a_i * x_i) = -constant
+    let forms = equations
+        .iter()
+        .map(|equation| LinearForm::parse(&equation.left)?.sub(LinearForm::parse(&equation.right)?))
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let unknowns: Vec<String> = forms
+        .iter()
+        .flat_map(|form| form.terms.keys().cloned())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+
+    let mut rows = forms
+        .into_iter()
+        .map(|form| {
+            let mut row: Vec<Rational> = unknowns
+                .iter()
+                .map(|name| form.terms.get(name).copied().unwrap_or_default())
+                .collect();
+            row.push(form.constant.checked_neg()?);
+            Ok(row)
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let pivots = reduce(&mut rows)?;
+
+    // A row 0 = c with c != 0 left after the elimination
+    if rows[pivots.len()..]
+        .iter()
+        .any(|row| !row[unknowns.len()].is_zero())
+    {
+        return Ok(SystemSolution::Inconsistent);
+    }
+
+    if unknowns.is_empty() {
+        return Ok(SystemSolution::Identity);
+    }
+
+    if pivots.len() < unknowns.len() {
+        let free = unknowns
+            .into_iter()
+            .enumerate()
+            .filter(|(column, _)| !pivots.contains(column))
+            .map(|(_, name)| name)
+            .collect();
+        return Ok(SystemSolution::Underdetermined(free));
+    }
+
+    Ok(SystemSolution::Unique(
+        unknowns
+            .into_iter()
+            .zip(rows)
+            .map(|(name, row)| (name, *row.last().unwrap()))
+            .collect(),
+    ))
+}
+
+// Puts the augmented matrix in reduced row echelon form, returns the pivot columns
+fn reduce(rows: &mut [Vec<Rational>]) -> Result<Vec<usize>, String> {
+    let columns = rows[0].len() - 1;
+    let mut pivots = vec![];
+
+    for column in 0..columns {
+        let rank = pivots.len();
+        let Some(pivot) = (rank..rows.len()).find(|&row| !rows[row][column].is_zero()) else {
+            continue;
+        };
+        rows.swap(rank, pivot);
+
+        let factor = rows[rank][column].recip()?;
+        for value in rows[rank].iter_mut() {
+            *value = value.mul(factor)?;
+        }
+
+        let pivot_row = rows[rank].clone();
+        for (i, row) in rows.iter_mut().enumerate() {
+            let factor = row[column];
+            if i == rank || factor.is_zero() {
+                continue;
+            }
+            for (value, pivot_value) in row.iter_mut().zip(&pivot_row) {
+                *value = value.sub(factor.mul(*pivot_value)?)?;
+            }
+        }
+
+        pivots.push(column);
+    }
+
+    Ok(pivots)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unique(values: &[(&str, Rational)]) -> SystemSolution {
+        SystemSolution::Unique(
+            values
+                .iter()
+                .map(|(name, value)| (name.to_string(), *value))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn two_unknowns() {
+        let solution = solve_system("2x + y = 5; x - y = 1").unwrap();
+        assert_eq!(
+            solution,
+            unique(&[("x", Rational::integer(2)), ("y", Rational::integer(1))])
+        );
+        assert_eq!(solution.to_string(), "x = 2, y = 1");
+    }
+
+    #[test]
+    fn exact_fractions() {
+        let solution = solve_system("3a + b/2 = 1; a - b = 0; c = a + b").unwrap();
+        let r = |num, den| Rational::new(num, den).unwrap();
+        assert_eq!(
+            solution,
+            unique(&[("a", r(2, 7)), ("b", r(2, 7)), ("c", r(4, 7))])
+        );
+    }
+
+    #[test]
+    fn constant_subexpressions() {
+        let solution = solve_system("2^3 x = 4 % 3 + 7; (1 + 1) y = x").unwrap();
+        assert_eq!(solution.to_string(), "x = 1, y = 1/2");
+    }
+
+    #[test]
+    fn inconsistent() {
+        let solution = solve_system("x + y = 1; 2x + 2y = 3").unwrap();
+        assert_eq!(solution, SystemSolution::Inconsistent);
+        let solution = solve_system("1 = 2").unwrap();
+        assert_eq!(solution, SystemSolution::Inconsistent);
+    }
+
+    #[test]
+    fn identity() {
+        let solution = solve_system("1 = 1; 2 + 2 = 4").unwrap();
+        assert_eq!(solution, SystemSolution::Identity);
+        assert_eq!(solution.to_string(), "Every value is a solution");
+    }
+
+    #[test]
+    fn underdetermined() {
+        let solution = solve_system("x + y + z = 1; x - y = 0").unwrap();
+        assert_eq!(
+            solution,
+            SystemSolution::Underdetermined(vec!["z".to_string()])
+        );
+        let solution = solve_system("x + y = 1; 2x + 2y = 2").unwrap();
+        assert_eq!(
+            solution,
+            SystemSolution::Underdetermined(vec!["y".to_string()])
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert!(solve_system("x * y = 1; x = 2").is_err());
+        assert!(solve_system("x^2 = 1").is_err());
+        assert!(solve_system("x / (y - y) = 1").is_err());
+        assert!(solve_system("x + 1; x = 2").is_err());
+        assert!(solve_system(";").is_err());
+    }
+}
@@ -0,0 +1,338 @@
+use super::number::Number;
+use std::fmt::Display;
+
+// Matrix of numbers, stored row by row. Its determinant, inverse and rank are computed
+// with fraction-free eliminations, so they are exact for integers and fractions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Matrix<N> {
+    rows: usize,
+    cols: usize,
+    entries: Vec<N>,
+}
+
+impl<N: Number> Matrix<N> {
+    pub fn new(rows: Vec<Vec<N>>) -> Result<Matrix<N>, String> {
+        let cols = rows.first().map(Vec::len).unwrap_or(0);
+        if cols == 0 {
+            return Err("A matrix needs at least one row and one column".to_string());
+        }
+        if let Some(row) = rows.iter().find(|row| row.len() != cols) {
+            return Err(format!(
+                "The rows of a matrix must have the same length ({} and {})",
+                cols,
+                row.len()
+            ));
+        }
+
+        Ok(Matrix {
+            rows: rows.len(),
+            cols,
+            entries: rows.into_iter().flatten().collect(),
+        })
+    }
+
+    pub fn identity(size: usize) -> Matrix<N> {
+        let mut entries = vec![N::from_litteral(0); size * size];
+        for i in 0..size {
+            entries[i * size + i] = N::from_litteral(1);
+        }
+        Matrix {
+            rows: size,
+            cols: size,
+            entries,
+        }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> N {
+        self.entries[row * self.cols + col]
+    }
+
+    pub fn to_rows(&self) -> Vec<Vec<N>> {
+        self.entries
+            .chunks(self.cols)
+            .map(|row| row.to_vec())
+            .collect()
+    }
+
+    pub fn transpose(&self) -> Matrix<N> {
+        let mut entries = Vec::with_capacity(self.entries.len());
+        for col in 0..self.cols {
+            for row in 0..self.rows {
+                entries.push(self.get(row, col));
+            }
+        }
+        Matrix {
+            rows: self.cols,
+            cols: self.rows,
+            entries,
+        }
+    }
+
+    pub fn mul(&self, other: &Matrix<N>) -> Result<Matrix<N>, String> {
+        if self.cols != other.rows {
+            return Err(format!("Cannot multiply a {self} by a {other}"));
+        }
+
+        let mut entries = Vec::with_capacity(self.rows * other.cols);
+        for row in 0..self.rows {
+            for col in 0..other.cols {
+                let mut sum = N::from_litteral(0);
+                for k in 0..self.cols {
+                    sum = sum.add(self.get(row, k).mul(other.get(k, col))?)?;
+                }
+                entries.push(sum);
+            }
+        }
+        Ok(Matrix {
+            rows: self.rows,
+            cols: other.cols,
+            entries,
+        })
+    }
+
+    // Repeated products by squaring, the negative powers being powers of the inverse
+    pub fn pow(&self, exponent: i32) -> Result<Matrix<N>, String> {
+        self.check_square("^")?;
+        let mut base = match exponent < 0 {
+            true => self.inverse()?,
+            false => self.clone(),
+        };
+        let mut exponent = exponent.unsigned_abs();
+        let mut result = Matrix::identity(self.rows);
+        while exponent > 0 {
+            if exponent % 2 == 1 {
+                result = result.mul(&base)?;
+            }
+            exponent /= 2;
+            if exponent > 0 {
+                base = base.mul(&base)?;
+            }
+        }
+        Ok(result)
+    }
+
+    pub fn det(&self) -> Result<N, String> {
+        self.check_square("det")?;
+        let (echelon, swaps) = self.echelon()?;
+        let last = echelon.rows - 1;
+        let det = echelon.get(last, last);
+        if swaps % 2 == 1 {
+            return N::from_litteral(0).sub(det);
+        }
+        Ok(det)
+    }
+
+    pub fn rank(&self) -> Result<usize, String> {
+        let (echelon, _) = self.echelon()?;
+        let zero = N::from_litteral(0);
+        Ok((0..echelon.rows)
+            .filter(|&row| (0..echelon.cols).any(|col| echelon.get(row, col) != zero))
+            .count())
+    }
+
+    // Adjugate divided by the determinant, the cofactors being determinants themselves
+    pub fn inverse(&self) -> Result<Matrix<N>, String> {
+        self.check_square("inverse")?;
+        let det = self.det()?;
+        if det == N::from_litteral(0) {
+            return Err(format!("Cannot invert a singular {self}"));
+        }
+
+        let size = self.rows;
+        let mut entries = Vec::with_capacity(size * size);
+        for row in 0..size {
+            for col in 0..size {
+                let cofactor = match size {
+                    1 => N::from_litteral(1),
+                    _ => self.minor(col, row).det()?,
+                };
+                let cofactor = match (row + col) % 2 {
+                    0 => cofactor,
+                    _ => N::from_litteral(0).sub(cofactor)?,
+                };
+                entries.push(cofactor.div_exact(det).map_err(|_| {
+                    format!(
+                        "The inverse of this {self} has fractions (its determinant is {det}), \
+                         evaluate it with rationals"
+                    )
+                })?);
+            }
+        }
+        Ok(Matrix {
+            rows: size,
+            cols: size,
+            entries,
+        })
+    }
+
+    fn check_square(&self, function: &str) -> Result<(), String> {
+        if self.rows != self.cols {
+            return Err(format!("{function} expects a square matrix, got a {self}"));
+        }
+        Ok(())
+    }
+
+    // Matrix without the given row and column
+    fn minor(&self, row: usize, col: usize) -> Matrix<N> {
+        let entries = (0..self.rows)
+            .filter(|&i| i != row)
+            .flat_map(|i| {
+                (0..self.cols)
+                    .filter(move |&j| j != col)
+                    .map(move |j| self.get(i, j))
+            })
+            .collect();
+        Matrix {
+            rows: self.rows - 1,
+            cols: self.cols - 1,
+            entries,
+        }
+    }
+
+    // Row echelon form computed with the Bareiss algorithm, along with the number of
+    // row swaps. Every division is exact, and the last pivot of a square matrix is its
+    // determinant.
+    fn echelon(&self) -> Result<(Matrix<N>, usize), String> {
+        let zero = N::from_litteral(0);
+        let mut matrix = self.clone();
+        let mut swaps = 0;
+        let mut previous = N::from_litteral(1);
+        let mut pivot_row = 0;
+
+        for col in 0..matrix.cols {
+            if pivot_row == matrix.rows {
+                break;
+            }
+            let Some(row) = (pivot_row..matrix.rows).find(|&row| matrix.get(row, col) != zero)
+            else {
+                continue;
+            };
+            if row != pivot_row {
+                matrix.swap_rows(row, pivot_row);
+                swaps += 1;
+            }
+
+            let pivot = matrix.get(pivot_row, col);
+            for row in pivot_row + 1..matrix.rows {
+                let factor = matrix.get(row, col);
+                for j in col + 1..matrix.cols {
+                    let value = pivot
+                        .mul(matrix.get(row, j))?
+                        .sub(factor.mul(matrix.get(pivot_row, j))?)?
+                        .div_exact(previous)?;
+                    matrix.entries[row * matrix.cols + j] = value;
+                }
+                matrix.entries[row * matrix.cols + col] = zero;
+            }
+            previous = pivot;
+            pivot_row += 1;
+        }
+
+        Ok((matrix, swaps))
+    }
+
+    fn swap_rows(&mut self, first: usize, second: usize) {
+        for col in 0..self.cols {
+            self.entries
+                .swap(first * self.cols + col, second * self.cols + col);
+        }
+    }
+}
+
+// Shape of the matrix, for the errors
+impl<N> Display for Matrix<N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{} matrix", self.rows, self.cols)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Rational, T};
+
+    fn matrix(rows: &[&[T]]) -> Matrix<T> {
+        Matrix::new(rows.iter().map(|row| row.to_vec()).collect()).unwrap()
+    }
+
+    #[test]
+    fn shapes() {
+        let m = matrix(&[&[1, 2, 3], &[4, 5, 6]]);
+        assert_eq!(m.to_string(), "2x3 matrix");
+        assert_eq!(
+            m.transpose().to_rows(),
+            vec![vec![1, 4], vec![2, 5], vec![3, 6]]
+        );
+        assert_eq!(
+            m.mul(&m.transpose()).unwrap().to_rows(),
+            vec![vec![14, 32], vec![32, 77]]
+        );
+        assert_eq!(
+            m.mul(&m).unwrap_err(),
+            "Cannot multiply a 2x3 matrix by a 2x3 matrix"
+        );
+        assert_eq!(
+            Matrix::new(vec![vec![1], vec![2, 3]]).unwrap_err(),
+            "The rows of a matrix must have the same length (1 and 2)"
+        );
+        assert_eq!(
+            Matrix::<T>::identity(2).to_rows(),
+            vec![vec![1, 0], vec![0, 1]]
+        );
+    }
+
+    #[test]
+    fn determinant_and_rank() {
+        assert_eq!(matrix(&[&[1, 2], &[3, 4]]).det(), Ok(-2));
+        assert_eq!(matrix(&[&[0, 1], &[1, 0]]).det(), Ok(-1));
+        assert_eq!(matrix(&[&[2, 0, 1], &[1, 3, 2], &[1, 1, 2]]).det(), Ok(6));
+        assert_eq!(matrix(&[&[5]]).det(), Ok(5));
+        assert_eq!(
+            matrix(&[&[1, 2, 3], &[4, 5, 6]]).det().unwrap_err(),
+            "det expects a square matrix, got a 2x3 matrix"
+        );
+
+        assert_eq!(matrix(&[&[1, 2], &[2, 4]]).rank(), Ok(1));
+        assert_eq!(matrix(&[&[0, 0, 1], &[0, 2, 0], &[0, 4, 1]]).rank(), Ok(2));
+        assert_eq!(matrix(&[&[1, 2, 3], &[4, 5, 6]]).rank(), Ok(2));
+        assert_eq!(matrix(&[&[0, 0], &[0, 0]]).rank(), Ok(0));
+    }
+
+    #[test]
+    fn inverse() {
+        let unimodular = matrix(&[&[2, 1], &[1, 1]]);
+        assert_eq!(
+            unimodular.inverse().unwrap().to_rows(),
+            vec![vec![1, -1], vec![-1, 2]]
+        );
+        assert_eq!(
+            matrix(&[&[1, 2], &[3, 4]]).inverse().unwrap_err(),
+            "The inverse of this 2x2 matrix has fractions (its determinant is -2), evaluate it with rationals"
+        );
+        assert_eq!(
+            matrix(&[&[1, 2], &[2, 4]]).inverse().unwrap_err(),
+            "Cannot invert a singular 2x2 matrix"
+        );
+
+        let r = |n| Rational::integer(n);
+        let m = Matrix::new(vec![vec![r(1), r(2)], vec![r(3), r(4)]]).unwrap();
+        let inverse = m.inverse().unwrap();
+        let half = Rational::new(1, 2).unwrap();
+        assert_eq!(
+            inverse.to_rows(),
+            vec![
+                vec![r(-2), r(1)],
+                vec![r(3).mul(half).unwrap(), r(-1).mul(half).unwrap()]
+            ]
+        );
+        assert_eq!(m.mul(&inverse), Ok(Matrix::identity(2)));
+    }
+}
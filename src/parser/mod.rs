@@ -1,9 +1,55 @@
+mod batch;
+mod calculus;
+mod compile;
+mod complex;
+mod config;
+mod context;
+mod decimal;
 mod expr;
+mod formulas;
+mod functions;
+mod interval;
+mod limits;
+mod linear;
+mod matrix;
+mod money;
+mod number;
+mod polynomial;
+mod pratt;
 mod preprocessor;
+mod quantity;
+mod rational;
+mod session;
+mod sheet;
+mod solver;
+mod time;
 mod tokenizer;
-
+mod value;
+mod visit;
+
+pub use batch::eval_batch;
+pub use compile::Program;
+pub use complex::Complex;
+pub use config::{Operation, Operator, ParserConfig, DEFAULT_CONFIG};
+pub use decimal::{Decimal, DecimalContext, Rounding};
 pub use expr::Expr;
-pub use expr::OPERATORS_PRECEDENCE;
+pub use formulas::FormulaSet;
+pub use functions::{Function, Functions};
+pub use interval::Interval;
+pub use limits::Limits;
+pub use linear::{solve_system, SystemSolution};
+pub use matrix::Matrix;
+pub use money::{Currency, Money, RateTable};
+pub use number::Number;
+pub use polynomial::{Factorization, Polynomial};
+pub use quantity::Quantity;
+pub use rational::Rational;
+pub use session::{Session, Statement};
+pub use sheet::Sheet;
+pub use solver::{solve, solve_in, Equation, Solution};
+pub use time::{Calendar, Date, Time};
+pub use value::{Lambda, Value};
+pub use visit::{visit_node, walk_expr, walk_fold, Folder, Visitor, Walk};
 
 pub type T = i128;
 
@@ -114,6 +160,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::identity_op, clippy::erasing_op)]
     fn complex2() {
         let input = "2*3*4/8 -   5/2*4 +  6 + 0/3 ";
         let res: T = 2 * 3 * 4 / 8 - 5 / 2 * 4 + 6 + 0 / 3;
@@ -188,6 +235,34 @@ mod tests {
         assert_eq!(expr.eval().unwrap(), result);
     }
 
+    #[test]
+    fn variables() {
+        let input = "2*x + y";
+        let mut variables = std::collections::HashMap::new();
+        variables.insert("x".to_string(), 3);
+        variables.insert("y".to_string(), 4);
+        let expr = Expr::parse(input).unwrap();
+
+        assert_eq!(expr.eval_with(&variables).unwrap(), 10);
+        assert!(expr.eval().is_err());
+    }
+
+    #[test]
+    fn function_call() {
+        let input = "1 + diff(x^2, x, 1 + 2) * 2";
+        let expr = Expr::parse(input).unwrap();
+
+        assert_eq!(expr.eval().unwrap(), 13);
+    }
+
+    #[test]
+    fn crash_function_call() {
+        assert!(Expr::parse("diff(x^2, x,)").is_err());
+        assert!(Expr::parse("diff(x^2 2)").is_err());
+        assert!(Expr::parse("(1, 2)").is_err());
+        assert!(Expr::parse("unknown(1)").unwrap().eval().is_err());
+    }
+
     #[test]
     fn helper_parse() {
         let input = "(2) + (17*2-30) * (5)+2 - (8/2)*4";
@@ -0,0 +1,500 @@
+use super::decimal::{with_point, DecimalContext, Rounding};
+use super::expr::Expr;
+use super::number::Number;
+use super::rational::Rational;
+use super::T;
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Display;
+use std::path::Path;
+
+// Symbols written before an amount, `$3` being `3 USD`
+pub(super) const CURRENCY_SYMBOLS: [(char, &str); 4] =
+    [('$', "USD"), ('€', "EUR"), ('£', "GBP"), ('¥', "JPY")];
+
+thread_local! {
+    // Rates used by the evaluations running in `RateTable::scope`
+    static RATES: RefCell<Option<RateTable>> = const { RefCell::new(None) };
+}
+
+// ISO 4217 code, three capital letters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Currency([u8; 3]);
+
+impl Currency {
+    pub fn new(code: &str) -> Result<Currency, String> {
+        match code.as_bytes() {
+            &[a, b, c] if code.bytes().all(|c| c.is_ascii_uppercase()) => Ok(Currency([a, b, c])),
+            _ => Err(format!("Invalid currency code {code}")),
+        }
+    }
+}
+
+impl Display for Currency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", String::from_utf8_lossy(&self.0))
+    }
+}
+
+// Exchange rates given by the embedding application or read from a file, there is no
+// network access. A rate can be used in both directions and through a third currency,
+// so a table with the rates of a single currency converts between all of them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RateTable {
+    rates: HashMap<(Currency, Currency), Rational>,
+}
+
+impl RateTable {
+    pub fn new() -> Self {
+        RateTable::default()
+    }
+
+    // 1 `from` is worth `rate` `to`, the rate is an exact decimal such as "1.0832"
+    pub fn set(&mut self, from: &str, to: &str, rate: &str) -> Result<(), String> {
+        let (from, to) = (Currency::new(from)?, Currency::new(to)?);
+        let rate = Expr::parse(rate)
+            .and_then(|expr| expr.eval_with::<Rational>(&HashMap::new()))
+            .map_err(|_| format!("Invalid rate {rate}"))?;
+        if rate.numerator() <= 0 {
+            return Err(format!("The rate from {from} to {to} must be positive"));
+        }
+        self.rates.insert((from, to), rate);
+        Ok(())
+    }
+
+    // One rate per line, such as `EUR USD 1.0832`, lines starting with '#' are comments
+    pub fn parse(text: &str) -> Result<RateTable, String> {
+        let mut table = RateTable::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let result = match line.split_whitespace().collect::<Vec<_>>()[..] {
+                [from, to, rate] => table.set(from, to, rate),
+                _ => Err("expected 'FROM TO RATE'".to_string()),
+            };
+            result.map_err(|err| format!("Invalid rate on line {}: {err}", number + 1))?;
+        }
+        Ok(table)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<RateTable, String> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| format!("Cannot read {}: {err}", path.display()))?;
+        RateTable::parse(&text)
+    }
+
+    // Value of 1 `from` in `to`
+    pub fn rate(&self, from: Currency, to: Currency) -> Option<Rational> {
+        if from == to {
+            return Some(Rational::ONE);
+        }
+        // The pivots are tried in the order of their codes, so that a table whose rates
+        // disagree still always gives the same conversions
+        self.direct_rate(from, to).or_else(|| {
+            let pivots: BTreeSet<Currency> = self.rates.keys().flat_map(|&(a, b)| [a, b]).collect();
+            pivots.into_iter().find_map(|via| {
+                let first = self.direct_rate(from, via)?;
+                first.mul(self.direct_rate(via, to)?).ok()
+            })
+        })
+    }
+
+    fn direct_rate(&self, from: Currency, to: Currency) -> Option<Rational> {
+        match self.rates.get(&(from, to)) {
+            Some(rate) => Some(*rate),
+            None => self.rates.get(&(to, from))?.recip().ok(),
+        }
+    }
+
+    // Runs `f` with these rates, the evaluations of `Money` convert with them
+    pub fn scope<R>(&self, f: impl FnOnce() -> R) -> R {
+        let previous = RATES.with(|rates| rates.replace(Some(self.clone())));
+        let result = f();
+        RATES.with(|rates| rates.replace(previous));
+        result
+    }
+}
+
+// Exact amount of a currency, or a plain number such as a quantity or a ratio. Amounts
+// of different currencies are combined with the rates of the current `RateTable`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Money {
+    amount: Rational,
+    currency: Option<Currency>,
+}
+
+impl Money {
+    pub fn new(amount: Rational, currency: &str) -> Result<Money, String> {
+        Ok(Money {
+            amount,
+            currency: Some(Currency::new(currency)?),
+        })
+    }
+
+    pub fn amount(&self) -> Rational {
+        self.amount
+    }
+
+    pub fn currency(&self) -> Option<Currency> {
+        self.currency
+    }
+
+    fn number(amount: Rational) -> Money {
+        Money {
+            amount,
+            currency: None,
+        }
+    }
+
+    // The amount in `currency`
+    fn converted(self, currency: Currency, error: impl Fn() -> String) -> Result<Money, String> {
+        let from = self.currency.unwrap();
+        let rate = RATES
+            .with(|rates| rates.borrow().as_ref()?.rate(from, currency))
+            .ok_or_else(|| {
+                format!(
+                    "{}, there is no exchange rate from {from} to {currency}",
+                    error()
+                )
+            })?;
+        Ok(Money {
+            amount: self.amount.mul(rate)?,
+            currency: Some(currency),
+        })
+    }
+
+    // Both amounts in the same currency, a zero without currency fits with any of them
+    // so that -x = 0 - x
+    fn same_currency(
+        self,
+        other: Money,
+        error: impl Fn() -> String,
+    ) -> Result<(Money, Money), String> {
+        match (self.currency, other.currency) {
+            (Some(left), Some(right)) if left != right => Ok((self, other.converted(left, error)?)),
+            (Some(_), Some(_)) | (None, None) => Ok((self, other)),
+            (Some(currency), None) if other.amount.is_zero() => Ok((
+                self,
+                Money {
+                    currency: Some(currency),
+                    ..other
+                },
+            )),
+            (None, Some(currency)) if self.amount.is_zero() => Ok((
+                Money {
+                    currency: Some(currency),
+                    ..self
+                },
+                other,
+            )),
+            _ => Err(format!(
+                "{}, only one of them is an amount of money",
+                error()
+            )),
+        }
+    }
+
+    fn check_plain(self, operation: &str) -> Result<Rational, String> {
+        match self.currency {
+            None => Ok(self.amount),
+            Some(_) => Err(format!("Cannot take the {operation} of {self}")),
+        }
+    }
+}
+
+// Exact decimal digits of the amount, with at least `min_decimals` of them, if its
+// denominator only has 2 and 5 as prime factors
+fn decimal(amount: Rational, min_decimals: usize) -> Option<String> {
+    let mut rest = amount.denominator();
+    let (mut twos, mut fives) = (0, 0);
+    while rest % 2 == 0 {
+        (rest, twos) = (rest / 2, twos + 1);
+    }
+    while rest % 5 == 0 {
+        (rest, fives) = (rest / 5, fives + 1);
+    }
+    if rest != 1 {
+        return None;
+    }
+
+    let decimals = usize::max(twos.max(fives), min_decimals);
+    let scale = T::checked_pow(10, decimals as u32)? / amount.denominator();
+    let digits = amount.numerator().checked_mul(scale)?;
+    Some(with_point(digits, decimals))
+}
+
+impl Display for Money {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Some(currency) = self.currency else {
+            return match decimal(self.amount, 0) {
+                Some(amount) => write!(f, "{amount}"),
+                None => write!(f, "{}", self.amount),
+            };
+        };
+        match decimal(self.amount, 2) {
+            Some(amount) => write!(f, "{amount} {currency}"),
+            // Rounded to the cent for the display only, the amount stays exact, and shown as
+            // a fraction when the cents don't fit
+            None => {
+                let cents = self.amount.mul(Rational::integer(100)).and_then(|cents| {
+                    Rounding::HalfEven.divide(cents.numerator(), cents.denominator())
+                });
+                match cents {
+                    Ok(cents) => write!(f, "≈{} {currency}", with_point(cents, 2)),
+                    Err(_) => write!(f, "{} {currency}", self.amount),
+                }
+            }
+        }
+    }
+}
+
+impl Number for Money {
+    fn from_litteral(value: T) -> Self {
+        Money::number(Rational::integer(value))
+    }
+
+    fn from_f64(value: f64) -> Result<Self, String> {
+        Ok(Money::number(Rational::from_f64(value)?))
+    }
+
+    fn to_f64(self) -> f64 {
+        self.amount.to_f64()
+    }
+
+    fn add(self, other: Self) -> Result<Self, String> {
+        let (left, right) =
+            self.same_currency(other, || format!("Cannot add {self} and {other}"))?;
+        Ok(Money {
+            amount: left.amount.add(right.amount)?,
+            ..left
+        })
+    }
+
+    fn sub(self, other: Self) -> Result<Self, String> {
+        let (left, right) =
+            self.same_currency(other, || format!("Cannot subtract {other} from {self}"))?;
+        Ok(Money {
+            amount: left.amount.sub(right.amount)?,
+            ..left
+        })
+    }
+
+    fn mul(self, other: Self) -> Result<Self, String> {
+        let currency = match (self.currency, other.currency) {
+            (Some(_), Some(_)) => return Err(format!("Cannot multiply {self} by {other}")),
+            (currency, None) | (None, currency) => currency,
+        };
+        Ok(Money {
+            amount: self.amount.mul(other.amount)?,
+            currency,
+        })
+    }
+
+    // An amount divided by another one is a plain ratio
+    fn div(self, other: Self) -> Result<Self, String> {
+        let (left, right) = match (self.currency, other.currency) {
+            (None, Some(_)) => return Err(format!("Cannot divide {self} by {other}")),
+            (Some(_), Some(_)) => {
+                let (left, right) =
+                    self.same_currency(other, || format!("Cannot divide {self} by {other}"))?;
+                (Money::number(left.amount), right)
+            }
+            _ => (self, other),
+        };
+        Ok(Money {
+            amount: left.amount.div(right.amount)?,
+            ..left
+        })
+    }
+
+    fn rem(self, other: Self) -> Result<Self, String> {
+        let (left, right) = match other.currency {
+            None => (self, other),
+            Some(_) => self.same_currency(other, || format!("Cannot compute {self} % {other}"))?,
+        };
+        Ok(Money {
+            amount: left.amount.rem(right.amount)?,
+            ..left
+        })
+    }
+
+    fn pow(self, other: Self) -> Result<Self, String> {
+        if self.currency.is_some() || other.currency.is_some() {
+            return Err(format!("Cannot raise {self} to the power {other}"));
+        }
+
+        Ok(Money::number(self.amount.pow(other.amount)?))
+    }
+
+    fn constant(name: &str) -> Option<Self> {
+        Some(Money {
+            amount: Rational::ONE,
+            currency: Some(Currency::new(name).ok()?),
+        })
+    }
+
+    fn sqrt(self) -> Result<Self, String> {
+        Ok(Money::number(self.check_plain("square root")?.sqrt()?))
+    }
+
+    fn ln(self) -> Result<Self, String> {
+        Ok(Money::number(self.check_plain("logarithm")?.ln()?))
+    }
+
+    // With the rounding mode of the current `DecimalContext`
+    fn round(self, digits: i32) -> Result<Self, String> {
+        let rounding = DecimalContext::current().rounding;
+        let power = Rational::integer(10).pow(Rational::integer(digits.into()))?;
+        let scaled = self.amount.mul(power)?;
+        let rounded = rounding.divide(scaled.numerator(), scaled.denominator())?;
+        Ok(Money {
+            amount: Rational::integer(rounded).div(power)?,
+            ..self
+        })
+    }
+
+    fn convert(self, unit: Self) -> Result<Self, String> {
+        let Some(currency) = unit.currency else {
+            return Err(format!(
+                "Cannot convert {self} to {unit}, which isn't a currency"
+            ));
+        };
+        if self.currency.is_none() {
+            return Err(format!(
+                "Cannot convert {self} to {currency}, which isn't an amount"
+            ));
+        }
+
+        self.converted(currency, || format!("Cannot convert {self} to {currency}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Session;
+
+    fn rates() -> RateTable {
+        RateTable::parse("# Rates of the euro\nEUR USD 1.08\nEUR GBP 0.85\n").unwrap()
+    }
+
+    fn eval(input: &str) -> Result<String, String> {
+        let mut session = Session::<Money>::new();
+        rates().scope(|| session.run(input).map(|result| result.unwrap().to_string()))
+    }
+
+    #[test]
+    fn exact_amounts() {
+        assert_eq!(eval("0.10 EUR + 0.20 EUR"), Ok("0.30 EUR".to_string()));
+        assert_eq!(eval("12.50 EUR * 3"), Ok("37.50 EUR".to_string()));
+        assert_eq!(eval("$3 + 2.5 USD"), Ok("5.50 USD".to_string()));
+        assert_eq!(eval("-€1.25"), Ok("-1.25 EUR".to_string()));
+        assert_eq!(eval("0.125 EUR"), Ok("0.125 EUR".to_string()));
+        assert_eq!(eval("sum(i, 1, 10, 0.1 EUR)"), Ok("1.00 EUR".to_string()));
+        assert_eq!(eval("(10 EUR) / (4 EUR)"), Ok("2.5".to_string()));
+        // Only the display is rounded
+        assert_eq!(eval("10 EUR / 3"), Ok("≈3.33 EUR".to_string()));
+        assert_eq!(eval("10 EUR / 3 * 3"), Ok("10.00 EUR".to_string()));
+        assert_eq!(eval("round(10 EUR / 3, 2)"), Ok("3.33 EUR".to_string()));
+        assert_eq!(eval("round(0.125 EUR, 2)"), Ok("0.12 EUR".to_string()));
+        // Too many cents to round them
+        assert_eq!(
+            eval("100000000000000000000000000000000000000 EUR / 3"),
+            Ok("100000000000000000000000000000000000000/3 EUR".to_string())
+        );
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(eval("12.50 EUR to USD"), Ok("13.50 USD".to_string()));
+        assert_eq!(eval("13.50 USD in EUR"), Ok("12.50 EUR".to_string()));
+        assert_eq!(eval("10 EUR + 1.08 USD"), Ok("11.00 EUR".to_string()));
+        // Through the euro
+        assert_eq!(eval("108 USD to GBP"), Ok("85.00 GBP".to_string()));
+        assert_eq!(
+            eval("1 EUR + 1 CHF"),
+            Err(
+                "Cannot add 1.00 EUR and 1.00 CHF, there is no exchange rate from CHF to EUR"
+                    .to_string()
+            )
+        );
+        // Without a rate table
+        let mut session = Session::<Money>::new();
+        assert_eq!(
+            session.run("2 USD to EUR"),
+            Err(
+                "Cannot convert 2.00 USD to EUR, there is no exchange rate from USD to EUR"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            session.run("2 USD + 2 USD").unwrap().unwrap().to_string(),
+            "4.00 USD"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            eval("(2 EUR) * (3 EUR)"),
+            Err("Cannot multiply 2.00 EUR by 3.00 EUR".to_string())
+        );
+        assert_eq!(
+            eval("2 EUR + 1"),
+            Err("Cannot add 2.00 EUR and 1, only one of them is an amount of money".to_string())
+        );
+        assert_eq!(
+            eval("2 to EUR"),
+            Err("Cannot convert 2 to EUR, which isn't an amount".to_string())
+        );
+        assert_eq!(eval("Eur"), Err("Unknown variable Eur".to_string()));
+    }
+
+    #[test]
+    fn rate_tables() {
+        let table = rates();
+        let (eur, usd) = (Currency::new("EUR").unwrap(), Currency::new("USD").unwrap());
+        assert_eq!(table.rate(eur, usd), Rational::new(27, 25).ok());
+        assert_eq!(table.rate(usd, eur), Rational::new(25, 27).ok());
+        assert_eq!(
+            RateTable::parse("EUR USD\n").unwrap_err(),
+            "Invalid rate on line 1: expected 'FROM TO RATE'"
+        );
+        assert_eq!(
+            RateTable::parse("EUR usd 1.1").unwrap_err(),
+            "Invalid rate on line 1: Invalid currency code usd"
+        );
+        assert_eq!(
+            RateTable::parse("EUR USD -1").unwrap_err(),
+            "Invalid rate on line 1: The rate from EUR to USD must be positive"
+        );
+
+        // Through GBP (1.02) rather than USD (0.972), whatever the order of the map, which
+        // changes from one table to the next
+        let chf = Currency::new("CHF").unwrap();
+        for _ in 0..10 {
+            let table = RateTable::parse("EUR USD 1.08\nEUR GBP 0.85\nUSD CHF 0.9\nGBP CHF 1.2");
+            assert_eq!(table.unwrap().rate(eur, chf), Rational::new(51, 50).ok());
+        }
+
+        // Unique to this run, so that parallel runs don't share the file
+        let name = format!(
+            "money_rate_table_test_{}_{:?}.txt",
+            std::process::id(),
+            std::thread::current().id()
+        );
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, "EUR USD 1.08").unwrap();
+        assert_eq!(
+            RateTable::load(&path),
+            Ok(RateTable::parse("EUR USD 1.08").unwrap())
+        );
+        std::fs::remove_file(&path).unwrap();
+        assert!(RateTable::load(&path)
+            .unwrap_err()
+            .starts_with("Cannot read"));
+    }
+}
@@ -0,0 +1,287 @@
+use super::decimal::with_point;
+use super::time::Date;
+use super::T;
+use std::fmt::{Debug, Display};
+
+const OVERFLOW: &str = "Integer overflow";
+
+// Numeric backend used by `Expr::eval_with`. `T` is the default one, `f64` is used by
+// the functions that need to evaluate an expression at non integer points.
+pub trait Number: Copy + PartialEq + Debug + Display {
+    fn from_litteral(value: T) -> Self;
+    fn from_f64(value: f64) -> Result<Self, String>;
+    fn to_f64(self) -> f64;
+
+    fn add(self, other: Self) -> Result<Self, String>;
+    fn sub(self, other: Self) -> Result<Self, String>;
+    fn mul(self, other: Self) -> Result<Self, String>;
+    fn div(self, other: Self) -> Result<Self, String>;
+    fn rem(self, other: Self) -> Result<Self, String>;
+    fn pow(self, other: Self) -> Result<Self, String>;
+
+    // Decimal litteral digits / 10^scale, such as 2.5 for (25, 1). 10^scale fits in T.
+    fn from_decimal(digits: T, scale: u32) -> Result<Self, String> {
+        Self::from_litteral(digits).div(Self::from_litteral(T::pow(10, scale)))
+    }
+
+    // Division which fails rather than rounding its result
+    fn div_exact(self, other: Self) -> Result<Self, String> {
+        self.div(other)
+    }
+
+    // Value of an argument which must be a real number, such as a bound or an index
+    fn to_real(self) -> Result<f64, String> {
+        Ok(self.to_f64())
+    }
+
+    // Checked by the numerical methods such as `integrate`, whose results are approximations
+    fn approximation(_function: &str) -> Result<(), String> {
+        Ok(())
+    }
+
+    // Value of the variables which aren't defined, such as the imaginary unit `i`
+    fn constant(_name: &str) -> Option<Self> {
+        None
+    }
+
+    // Functions of the calculator, which the real backends compute through f64
+    fn sqrt(self) -> Result<Self, String> {
+        if self.to_f64() < 0.0 {
+            return Err(format!("sqrt({self}) is not a real number"));
+        }
+
+        Self::from_f64(self.to_f64().sqrt())
+    }
+
+    fn ln(self) -> Result<Self, String> {
+        if self.to_f64() == 0.0 {
+            return Err("ln(0) is undefined".to_string());
+        }
+        if self.to_f64() < 0.0 {
+            return Err(format!("ln({self}) is not a real number"));
+        }
+
+        Self::from_f64(self.to_f64().ln())
+    }
+
+    fn abs(self) -> Result<Self, String> {
+        if self.to_f64() < 0.0 {
+            return Self::from_litteral(0).sub(self);
+        }
+
+        Ok(self)
+    }
+
+    fn arg(self) -> Result<Self, String> {
+        if self.to_f64() < 0.0 {
+            return Self::from_f64(std::f64::consts::PI);
+        }
+
+        Ok(Self::from_litteral(0))
+    }
+
+    fn re(self) -> Self {
+        self
+    }
+
+    fn im(self) -> Self {
+        Self::from_litteral(0)
+    }
+
+    fn conj(self) -> Self {
+        self
+    }
+
+    // Rounded to `digits` decimals, half away from zero
+    fn round(self, digits: i32) -> Result<Self, String> {
+        let scale = 10f64.powi(digits);
+        Self::from_f64((self.to_f64() * scale).round() / scale)
+    }
+
+    // `self to unit`, only the backends with units can convert
+    fn convert(self, unit: Self) -> Result<Self, String> {
+        Err(format!(
+            "Cannot convert {self} to {unit}, evaluate it with quantities"
+        ))
+    }
+
+    // Date litteral such as 2026-10-18, a subtraction but for the backend with dates
+    fn from_iso_date(year: T, month: T, day: T) -> Result<Self, String> {
+        let [year, month, day] = [year, month, day].map(Self::from_litteral);
+        year.sub(month)?.sub(day)
+    }
+
+    // Dates and the functions of the calendar, only the backend with dates has them
+    fn date(date: Date) -> Result<Self, String> {
+        Err(format!("{date} is a date, evaluate it with dates"))
+    }
+
+    fn weekday(self) -> Result<Self, String> {
+        Err(format!("weekday expects a date, got {self}"))
+    }
+
+    fn add_months(self, _months: i32) -> Result<Self, String> {
+        Err(format!("add_months expects a date, got {self}"))
+    }
+}
+
+impl Number for T {
+    fn from_litteral(value: T) -> Self {
+        value
+    }
+
+    fn from_f64(value: f64) -> Result<Self, String> {
+        if !value.is_finite() {
+            return Err(format!("{value} is not a finite number"));
+        }
+
+        Ok(value.round() as T)
+    }
+
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    // Only the decimals which are integers, such as 2.0, are numbers
+    fn from_decimal(digits: T, scale: u32) -> Result<Self, String> {
+        let scale_factor = T::pow(10, scale);
+        if digits % scale_factor != 0 {
+            return Err(format!(
+                "Decimals such as {} need a non-integer backend",
+                with_point(digits, scale as usize)
+            ));
+        }
+
+        Ok(digits / scale_factor)
+    }
+
+    fn add(self, other: Self) -> Result<Self, String> {
+        self.checked_add(other).ok_or(OVERFLOW.to_string())
+    }
+
+    fn sub(self, other: Self) -> Result<Self, String> {
+        self.checked_sub(other).ok_or(OVERFLOW.to_string())
+    }
+
+    fn mul(self, other: Self) -> Result<Self, String> {
+        self.checked_mul(other).ok_or(OVERFLOW.to_string())
+    }
+
+    fn div(self, other: Self) -> Result<Self, String> {
+        if other == 0 {
+            return Err(format!("Cannot divide {} by zero", self));
+        }
+
+        self.checked_div(other).ok_or(OVERFLOW.to_string())
+    }
+
+    fn rem(self, other: Self) -> Result<Self, String> {
+        if other == 0 {
+            return Err(format!("Cannot divide {} by zero", self));
+        }
+
+        self.checked_rem(other).ok_or(OVERFLOW.to_string())
+    }
+
+    fn pow(self, other: Self) -> Result<Self, String> {
+        if self == 0 && other == 0 {
+            return Err("0^0 is undefined".to_string());
+        }
+
+        if other.is_negative() {
+            return Err("Cannot raise to a negative power".to_string());
+        }
+
+        let exponent = u32::try_from(other).map_err(|_| OVERFLOW)?;
+        self.checked_pow(exponent).ok_or(OVERFLOW.to_string())
+    }
+
+    fn div_exact(self, other: Self) -> Result<Self, String> {
+        if other != 0 && self.checked_rem(other).unwrap_or(0) != 0 {
+            return Err(format!("{self} / {other} is not an integer"));
+        }
+
+        Number::div(self, other)
+    }
+}
+
+impl Number for f64 {
+    fn from_litteral(value: T) -> Self {
+        value as f64
+    }
+
+    fn from_f64(value: f64) -> Result<Self, String> {
+        Ok(value)
+    }
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    fn add(self, other: Self) -> Result<Self, String> {
+        Ok(self + other)
+    }
+
+    fn sub(self, other: Self) -> Result<Self, String> {
+        Ok(self - other)
+    }
+
+    fn mul(self, other: Self) -> Result<Self, String> {
+        Ok(self * other)
+    }
+
+    fn div(self, other: Self) -> Result<Self, String> {
+        if other == 0.0 {
+            return Err(format!("Cannot divide {} by zero", self));
+        }
+
+        Ok(self / other)
+    }
+
+    fn rem(self, other: Self) -> Result<Self, String> {
+        if other == 0.0 {
+            return Err(format!("Cannot divide {} by zero", self));
+        }
+
+        Ok(self % other)
+    }
+
+    fn pow(self, other: Self) -> Result<Self, String> {
+        if self == 0.0 && other == 0.0 {
+            return Err("0^0 is undefined".to_string());
+        }
+
+        let result = self.powf(other);
+        if result.is_nan() {
+            return Err(format!("{self}^{other} is not a real number"));
+        }
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::{Expr, T};
+
+    #[test]
+    fn overflow() {
+        let eval = |input| Expr::parse(input).unwrap().eval();
+        assert_eq!(
+            eval("99999999999999999999*99999999999999999999"),
+            Err("Integer overflow".to_string())
+        );
+        assert_eq!(eval("2^200"), Err("Integer overflow".to_string()));
+        assert_eq!(
+            eval("(0-170141183460469231731687303715884105727-1)/(0-1)"),
+            Err("Integer overflow".to_string())
+        );
+        assert_eq!(
+            eval("(0-170141183460469231731687303715884105727-1)%(0-1)"),
+            Err("Integer overflow".to_string())
+        );
+        assert_eq!(eval("2^4294967296"), Err("Integer overflow".to_string()));
+        assert!(eval("170141183460469231731687303715884105727+1").is_err());
+        assert_eq!(eval("(2^126 - 1) * 2 + 1"), Ok(T::MAX));
+    }
+}
@@ -0,0 +1,863 @@
+use super::expr::Expr;
+use super::number::Number;
+use super::rational::Rational;
+use super::T;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+
+const MAX_EXPANDED_POWER: T = 64;
+// Bounds on the brute force parts of the factorization
+const MAX_DIVISORS_SEARCH: T = 1_000_000_000_000;
+const MAX_ROOT_CANDIDATES: usize = 20_000;
+const MAX_KRONECKER_CANDIDATES: usize = 200_000;
+const BISECTION_STEPS: usize = 200;
+
+// Polynomial in a single variable with exact coefficients, lowest degree first and
+// without trailing zeros.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polynomial {
+    variable: String,
+    coefficients: Vec<Rational>,
+}
+
+impl Polynomial {
+    pub fn new(variable: &str, mut coefficients: Vec<Rational>) -> Polynomial {
+        while coefficients.last().map(|c| c.is_zero()).unwrap_or(false) {
+            coefficients.pop();
+        }
+
+        Polynomial {
+            variable: variable.to_string(),
+            coefficients,
+        }
+    }
+
+    pub fn constant(variable: &str, value: Rational) -> Polynomial {
+        Polynomial::new(variable, vec![value])
+    }
+
+    pub fn parse(input: &str) -> Result<Polynomial, String> {
+        Polynomial::from_expr(&Expr::parse(input)?)
+    }
+
+    // Expands `expr`, which must contain at most one variable ("x" is used if it
+    // doesn't contain any).
+    pub fn from_expr(expr: &Expr) -> Result<Polynomial, String> {
+        let variables = expr.free_variables();
+        if variables.len() > 1 {
+            return Err(format!(
+                "Expected a polynomial in a single variable, found {}",
+                variables.into_iter().collect::<Vec<_>>().join(", ")
+            ));
+        }
+
+        let variable = variables.into_iter().next().unwrap_or("x".to_string());
+        Polynomial::from_expr_in(expr, &variable)
+    }
+
+    pub fn from_expr_in(expr: &Expr, variable: &str) -> Result<Polynomial, String> {
+        if let Some(other) = expr
+            .free_variables()
+            .into_iter()
+            .find(|name| name != variable)
+        {
+            return Err(format!(
+                "Expected a polynomial in {variable}, found the variable {other}"
+            ));
+        }
+
+        Polynomial::expand(expr, variable)
+    }
+
+    // Expansion of an expression whose only free variable is `variable`
+    fn expand(expr: &Expr, variable: &str) -> Result<Polynomial, String> {
+        let not_polynomial = || Err(format!("{:?} is not a polynomial in {variable}", expr));
+
+        let expand = |expr| Polynomial::expand(expr, variable);
+        match expr {
+            Expr::Litteral(value) => Ok(Polynomial::constant(variable, Rational::integer(*value))),
+            Expr::Variable(_) => Ok(Polynomial::new(
+                variable,
+                vec![Rational::ZERO, Rational::ONE],
+            )),
+            Expr::Add(left, right) => expand(left)?.add(&expand(right)?),
+            Expr::Sub(left, right) => expand(left)?.sub(&expand(right)?),
+            Expr::Prod(left, right) => expand(left)?.mul(&expand(right)?),
+            Expr::Div(left, right) => match expand(right)?.as_constant() {
+                Some(divisor) if divisor.is_zero() => {
+                    Err(format!("Cannot divide {:?} by zero", left))
+                }
+                Some(divisor) => expand(left)?.scale(divisor.recip()?),
+                None => not_polynomial(),
+            },
+            Expr::Pow(base, exponent) => {
+                let (base, exponent) = (expand(base)?, expand(exponent)?.as_constant());
+                match (base.as_constant(), exponent) {
+                    (Some(base), Some(exponent)) => {
+                        Ok(Polynomial::constant(variable, base.pow(exponent)?))
+                    }
+                    (_, Some(exponent))
+                        if exponent.is_integer()
+                            && (0..=MAX_EXPANDED_POWER).contains(&exponent.numerator()) =>
+                    {
+                        (0..exponent.numerator())
+                            .try_fold(Polynomial::constant(variable, Rational::ONE), |acc, _| {
+                                acc.mul(&base)
+                            })
+                    }
+                    _ => not_polynomial(),
+                }
+            }
+            // Calls, lists... are constants when they don't depend on the variable, their
+            // free variables are only looked for here so that the expansion stays linear
+            _ if !expr.free_variables().contains(variable) => Ok(Polynomial::constant(
+                variable,
+                expr.eval_with::<Rational>(&HashMap::new())?,
+            )),
+            _ => not_polynomial(),
+        }
+    }
+
+    pub fn variable(&self) -> &str {
+        &self.variable
+    }
+
+    pub fn coefficients(&self) -> &[Rational] {
+        &self.coefficients
+    }
+
+    // None for the zero polynomial
+    pub fn degree(&self) -> Option<usize> {
+        self.coefficients.len().checked_sub(1)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.coefficients.is_empty()
+    }
+
+    pub fn as_constant(&self) -> Option<Rational> {
+        match self.coefficients[..] {
+            [] => Some(Rational::ZERO),
+            [value] => Some(value),
+            _ => None,
+        }
+    }
+
+    fn leading(&self) -> Rational {
+        self.coefficients.last().copied().unwrap_or_default()
+    }
+
+    pub fn eval(&self, x: Rational) -> Result<Rational, String> {
+        self.coefficients
+            .iter()
+            .rev()
+            .try_fold(Rational::ZERO, |acc, c| acc.mul(x)?.add(*c))
+    }
+
+    pub fn eval_f64(&self, x: f64) -> f64 {
+        self.coefficients
+            .iter()
+            .rev()
+            .fold(0.0, |acc, c| acc * x + c.to_f64())
+    }
+
+    fn common_variable(&self, other: &Polynomial) -> Result<String, String> {
+        match (self.degree(), other.degree()) {
+            (Some(1..), Some(1..)) if self.variable != other.variable => Err(format!(
+                "Cannot combine polynomials in {} and {}",
+                self.variable, other.variable
+            )),
+            (Some(1..), _) => Ok(self.variable.clone()),
+            _ => Ok(other.variable.clone()),
+        }
+    }
+
+    pub fn add(&self, other: &Polynomial) -> Result<Polynomial, String> {
+        let variable = self.common_variable(other)?;
+        let coefficients = (0..self.coefficients.len().max(other.coefficients.len()))
+            .map(|i| {
+                let left = self.coefficients.get(i).copied().unwrap_or_default();
+                let right = other.coefficients.get(i).copied().unwrap_or_default();
+                left.add(right)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Polynomial::new(&variable, coefficients))
+    }
+
+    pub fn sub(&self, other: &Polynomial) -> Result<Polynomial, String> {
+        self.add(&other.scale(Rational::integer(-1))?)
+    }
+
+    pub fn scale(&self, factor: Rational) -> Result<Polynomial, String> {
+        let coefficients = self
+            .coefficients
+            .iter()
+            .map(|c| c.mul(factor))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Polynomial::new(&self.variable, coefficients))
+    }
+
+    pub fn mul(&self, other: &Polynomial) -> Result<Polynomial, String> {
+        let variable = self.common_variable(other)?;
+        if self.is_zero() || other.is_zero() {
+            return Ok(Polynomial::new(&variable, vec![]));
+        }
+
+        let mut coefficients =
+            vec![Rational::ZERO; self.coefficients.len() + other.coefficients.len() - 1];
+        for (i, left) in self.coefficients.iter().enumerate() {
+            for (j, right) in other.coefficients.iter().enumerate() {
+                coefficients[i + j] = coefficients[i + j].add(left.mul(*right)?)?;
+            }
+        }
+        Ok(Polynomial::new(&variable, coefficients))
+    }
+
+    // Euclidean division, returns the quotient and the remainder
+    pub fn div_rem(&self, other: &Polynomial) -> Result<(Polynomial, Polynomial), String> {
+        let variable = self.common_variable(other)?;
+        let Some(divisor_degree) = other.degree() else {
+            return Err(format!("Cannot divide {} by zero", self));
+        };
+
+        let mut remainder = self.coefficients.clone();
+        let mut quotient = vec![Rational::ZERO; remainder.len().saturating_sub(divisor_degree)];
+        while remainder.len() > divisor_degree {
+            let shift = remainder.len() - 1 - divisor_degree;
+            let factor = remainder.last().unwrap().div(other.leading())?;
+            quotient[shift] = factor;
+            for (i, c) in other.coefficients.iter().enumerate() {
+                remainder[shift + i] = remainder[shift + i].sub(factor.mul(*c)?)?;
+            }
+            remainder.pop();
+        }
+
+        Ok((
+            Polynomial::new(&variable, quotient),
+            Polynomial::new(&variable, remainder),
+        ))
+    }
+
+    pub fn derivative(&self) -> Result<Polynomial, String> {
+        let coefficients = self
+            .coefficients
+            .iter()
+            .enumerate()
+            .skip(1)
+            .map(|(i, c)| c.mul(Rational::integer(i as T)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Polynomial::new(&self.variable, coefficients))
+    }
+
+    // Factorization over the rationals, in irreducible factors with integer coefficients
+    pub fn factor(&self) -> Result<Factorization, String> {
+        let (factorization, complete) = self.partial_factor()?;
+        if !complete {
+            return Err(format!(
+                "Cannot factor {self}, its coefficients have too many divisors to search"
+            ));
+        }
+        Ok(factorization)
+    }
+
+    // Factorization whose factors may be reducible when the divisors of their coefficients
+    // are out of reach, with whether they are all irreducible
+    fn partial_factor(&self) -> Result<(Factorization, bool), String> {
+        if self.is_zero() {
+            return Err("Cannot factor the zero polynomial".to_string());
+        }
+
+        let mut remaining = self.primitive()?;
+        let mut factors = vec![];
+        let mut complete = true;
+
+        // Roots in 0, then the other rational roots p/q with p | a0 and q | an
+        let zeros = remaining
+            .coefficients
+            .iter()
+            .take_while(|c| c.is_zero())
+            .count();
+        if zeros > 0 {
+            remaining = Polynomial::new(&self.variable, remaining.coefficients[zeros..].to_vec());
+            factors.push((
+                Polynomial::new(&self.variable, vec![Rational::ZERO, Rational::ONE]),
+                zeros,
+            ));
+        }
+        // Without the divisors, the roots of a quadratic are found through its discriminant
+        let candidates = remaining
+            .rational_root_candidates()
+            .or_else(|| remaining.quadratic_rational_roots());
+        complete &= candidates.is_some();
+        for root in candidates.unwrap_or_default() {
+            let linear = Polynomial::new(
+                &self.variable,
+                vec![
+                    Rational::integer(-root.numerator()),
+                    Rational::integer(root.denominator()),
+                ],
+            );
+            let multiplicity = remaining.divide_out(&linear)?;
+            if multiplicity > 0 {
+                factors.push((linear, multiplicity));
+            }
+        }
+
+        // Without rational roots, polynomials of degree 2 and 3 are irreducible
+        let mut pending = vec![remaining];
+        while let Some(mut polynomial) = pending.pop() {
+            match polynomial.degree() {
+                Some(0) | None => {}
+                Some(1..=3) => factors.push((polynomial, 1)),
+                Some(_) => match polynomial.kronecker_factor()? {
+                    Kronecker::Factor(factor) => {
+                        let multiplicity = polynomial.divide_out(&factor)?;
+                        factors.push((factor, multiplicity));
+                        pending.push(polynomial);
+                    }
+                    Kronecker::Irreducible => factors.push((polynomial, 1)),
+                    Kronecker::SearchLimit => {
+                        complete = false;
+                        factors.push((polynomial, 1));
+                    }
+                },
+            }
+        }
+
+        factors.sort_by(|(left, _), (right, _)| {
+            let key = |p: &Polynomial| -> Vec<f64> {
+                p.coefficients.iter().rev().map(|c| c.to_f64()).collect()
+            };
+            let monomial =
+                |p: &Polynomial| p.coefficients.iter().filter(|c| !c.is_zero()).count() == 1;
+            left.degree()
+                .cmp(&right.degree())
+                .then(monomial(right).cmp(&monomial(left)))
+                .then(key(left).partial_cmp(&key(right)).unwrap())
+        });
+
+        let mut constant = self.leading();
+        for (factor, multiplicity) in &factors {
+            constant = constant.div(
+                factor
+                    .leading()
+                    .pow(Rational::integer(*multiplicity as T))?,
+            )?;
+        }
+
+        Ok((Factorization { constant, factors }, complete))
+    }
+
+    // Same polynomial with coprime integer coefficients and a positive leading one
+    fn primitive(&self) -> Result<Polynomial, String> {
+        let mut multiplier = Rational::ONE;
+        for c in &self.coefficients {
+            let den = Rational::integer(c.denominator());
+            let gcd = Rational::integer(gcd(multiplier.numerator(), c.denominator()));
+            multiplier = multiplier.mul(den)?.div(gcd)?;
+        }
+        let integers = self.scale(multiplier)?;
+
+        let content = integers
+            .coefficients
+            .iter()
+            .fold(0, |acc, c| gcd(acc, c.numerator()));
+        let sign = self.leading().numerator().signum();
+        integers.scale(Rational::new(sign, content)?)
+    }
+
+    // Roots p/q with p | a0 and q | an. When there are too many of these fractions, only
+    // the ones closest to the real roots computed with floats are tried. None when a0 or an
+    // is too large to look for its divisors.
+    fn rational_root_candidates(&self) -> Option<Vec<Rational>> {
+        let (Some(first), Some(last)) = (self.coefficients.first(), self.coefficients.last())
+        else {
+            return Some(vec![]);
+        };
+        let (ps, qs) = (divisors(first.numerator())?, divisors(last.numerator())?);
+
+        let mut fractions = vec![];
+        if ps.len().saturating_mul(qs.len()) <= MAX_ROOT_CANDIDATES {
+            for p in &ps {
+                for q in &qs {
+                    fractions.extend([(*p, *q), (-p, *q)]);
+                }
+            }
+        } else {
+            let coefficients: Vec<f64> = self.coefficients.iter().map(|c| c.to_f64()).collect();
+            for root in real_roots(&coefficients) {
+                for q in &qs {
+                    let p = (root * *q as f64).round() as T;
+                    if p != 0 && first.numerator() % p == 0 {
+                        fractions.push((p, *q));
+                    }
+                }
+            }
+        }
+
+        let mut seen = HashSet::new();
+        let candidates = fractions
+            .into_iter()
+            .filter_map(|(p, q)| Rational::new(p, q).ok())
+            .filter(|candidate| seen.insert(*candidate))
+            .collect();
+        Some(candidates)
+    }
+
+    // Rational roots of a quadratic with integer coefficients, (-b ± s) / 2a when its
+    // discriminant is the square s^2. None for the other degrees or on overflow.
+    fn quadratic_rational_roots(&self) -> Option<Vec<Rational>> {
+        let [c, b, a] = self.coefficients[..] else {
+            return None;
+        };
+        let (a, b, c) = (a.numerator(), b.numerator(), c.numerator());
+        let discriminant = b
+            .checked_mul(b)?
+            .checked_sub(a.checked_mul(c)?.checked_mul(4)?)?;
+        if discriminant < 0 {
+            return Some(vec![]);
+        }
+        let s = discriminant.isqrt();
+        if s * s != discriminant {
+            return Some(vec![]);
+        }
+        [
+            b.checked_neg()?.checked_sub(s)?,
+            b.checked_neg()?.checked_add(s)?,
+        ]
+        .into_iter()
+        .map(|p| Rational::new(p, a.checked_mul(2)?).ok())
+        .collect()
+    }
+
+    // Divides self by `factor` as many times as possible, returns how many times
+    fn divide_out(&mut self, factor: &Polynomial) -> Result<usize, String> {
+        let mut multiplicity = 0;
+        loop {
+            let (quotient, remainder) = self.div_rem(factor)?;
+            if !remainder.is_zero() {
+                return Ok(multiplicity);
+            }
+            *self = quotient;
+            multiplicity += 1;
+        }
+    }
+
+    // Kronecker's method: a factor g of degree d is determined by its values at d + 1
+    // points, which must divide the values of self at these points.
+    fn kronecker_factor(&self) -> Result<Kronecker, String> {
+        let degree = self.degree().unwrap_or(0);
+        let points: Vec<T> = (0..=(degree / 2) as T).flat_map(|i| [i, -i - 1]).collect();
+
+        for d in 2..=degree / 2 {
+            let points = &points[..=d];
+            let mut choices = vec![];
+            for (i, point) in points.iter().enumerate() {
+                let value = self.eval(Rational::integer(*point))?;
+                let Some(mut divisors) = divisors(value.numerator()) else {
+                    return Ok(Kronecker::SearchLimit);
+                };
+                // g and -g are both factors, so the sign of g(x0) can be fixed
+                if i > 0 {
+                    divisors.extend(divisors.clone().into_iter().map(|x| -x));
+                }
+                choices.push(divisors);
+            }
+
+            let candidates = choices
+                .iter()
+                .try_fold(1usize, |acc, c| acc.checked_mul(c.len()));
+            if candidates
+                .map(|c| c > MAX_KRONECKER_CANDIDATES)
+                .unwrap_or(true)
+            {
+                return Ok(Kronecker::SearchLimit);
+            }
+
+            let mut indexes = vec![0; choices.len()];
+            loop {
+                let values: Vec<T> = indexes.iter().zip(&choices).map(|(i, c)| c[*i]).collect();
+                let candidate = self.interpolate(points, &values)?;
+                if candidate.degree() == Some(d)
+                    && candidate.coefficients.iter().all(|c| c.is_integer())
+                {
+                    let (_, remainder) = self.div_rem(&candidate)?;
+                    if remainder.is_zero() {
+                        return Ok(Kronecker::Factor(candidate.primitive()?));
+                    }
+                }
+
+                // Next combination of divisors
+                let Some(position) =
+                    (0..indexes.len()).find(|&i| indexes[i] + 1 < choices[i].len())
+                else {
+                    break;
+                };
+                indexes[position] += 1;
+                indexes[..position].iter_mut().for_each(|i| *i = 0);
+            }
+        }
+
+        Ok(Kronecker::Irreducible)
+    }
+
+    // Lagrange interpolation through (points[i], values[i])
+    fn interpolate(&self, points: &[T], values: &[T]) -> Result<Polynomial, String> {
+        let mut result = Polynomial::new(&self.variable, vec![]);
+        for (i, (xi, yi)) in points.iter().zip(values).enumerate() {
+            let mut term = Polynomial::constant(&self.variable, Rational::integer(*yi));
+            for (j, xj) in points.iter().enumerate() {
+                if i == j {
+                    continue;
+                }
+                let linear =
+                    Polynomial::new(&self.variable, vec![Rational::integer(-xj), Rational::ONE]);
+                term = term.mul(&linear)?.scale(Rational::new(1, xi - xj)?)?;
+            }
+            result = result.add(&term)?;
+        }
+        Ok(result)
+    }
+
+    // Distinct real roots, sorted
+    pub fn roots(&self) -> Result<Vec<f64>, String> {
+        if self.is_zero() {
+            return Err(format!("Every value of {} is a root of 0", self.variable));
+        }
+
+        let mut roots = vec![];
+        // Reducible factors are solved numerically like the irreducible ones
+        let (factorization, _) = self.partial_factor()?;
+        for (factor, _) in factorization.factors {
+            match factor.coefficients[..] {
+                [b, a] => roots.push(b.checked_neg()?.div(a)?.to_f64()),
+                _ => {
+                    let coefficients: Vec<f64> =
+                        factor.coefficients.iter().map(|c| c.to_f64()).collect();
+                    roots.extend(real_roots(&coefficients));
+                }
+            }
+        }
+
+        roots.sort_by(|a, b| a.total_cmp(b));
+        roots.dedup();
+        Ok(roots)
+    }
+}
+
+impl Display for Polynomial {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+
+        let mut first = true;
+        for (degree, c) in self.coefficients.iter().enumerate().rev() {
+            if c.is_zero() {
+                continue;
+            }
+
+            let negative = c.numerator() < 0;
+            match (first, negative) {
+                (true, true) => write!(f, "-")?,
+                (true, false) => {}
+                (false, true) => write!(f, " - ")?,
+                (false, false) => write!(f, " + ")?,
+            }
+            first = false;
+
+            let abs = Rational::new(c.numerator().abs(), c.denominator()).unwrap();
+            if degree == 0 || abs != Rational::ONE {
+                write!(f, "{}", abs)?;
+                if degree > 0 && !abs.is_integer() {
+                    write!(f, " ")?;
+                }
+            }
+            match degree {
+                0 => {}
+                1 => write!(f, "{}", self.variable)?,
+                _ => write!(f, "{}^{}", self.variable, degree)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Factorization {
+    pub constant: Rational,
+    // Irreducible factors and their multiplicity
+    pub factors: Vec<(Polynomial, usize)>,
+}
+
+impl Display for Factorization {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.factors.is_empty() {
+            return write!(f, "{}", self.constant);
+        }
+
+        if self.constant == Rational::integer(-1) {
+            write!(f, "-")?;
+        } else if self.constant != Rational::ONE {
+            write!(f, "{}", self.constant)?;
+        }
+
+        let single = self.factors.len() == 1 && self.factors[0].1 == 1;
+        for (factor, multiplicity) in &self.factors {
+            let monomial = factor.coefficients.len() == 2 && factor.coefficients[0].is_zero();
+            if (single && self.constant == Rational::ONE) || monomial {
+                write!(f, "{}", factor)?;
+            } else {
+                write!(f, "({})", factor)?;
+            }
+            if *multiplicity > 1 {
+                write!(f, "^{}", multiplicity)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn gcd(mut a: T, mut b: T) -> T {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a.abs()
+}
+
+// Outcome of Kronecker's method, which gives up when the values have too many divisors
+enum Kronecker {
+    Factor(Polynomial),
+    Irreducible,
+    SearchLimit,
+}
+
+// Positive divisors of n, or None if n is too large to look for them
+fn divisors(n: T) -> Option<Vec<T>> {
+    let n = n.abs();
+    if n == 0 || n > MAX_DIVISORS_SEARCH {
+        return None;
+    }
+
+    let mut small = vec![];
+    let mut large = vec![];
+    let mut i = 1;
+    while i * i <= n {
+        if n % i == 0 {
+            small.push(i);
+            if i * i != n {
+                large.push(n / i);
+            }
+        }
+        i += 1;
+    }
+    small.extend(large.into_iter().rev());
+    Some(small)
+}
+
+fn quadratic_roots(a: f64, b: f64, c: f64) -> Vec<f64> {
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return vec![];
+    }
+    if discriminant == 0.0 {
+        return vec![-b / (2.0 * a)];
+    }
+
+    // Avoids the cancellation of -b + sqrt(discriminant) when b is large
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    let mut roots = vec![q / a, c / q];
+    roots.sort_by(|a, b| a.total_cmp(b));
+    roots
+}
+
+// Real roots of a square free polynomial: they are isolated by the roots of its
+// derivative, so the polynomial is monotonic and changes sign around each of them.
+fn real_roots(coefficients: &[f64]) -> Vec<f64> {
+    match coefficients {
+        [] | [_] => return vec![],
+        [b, a] => return vec![-b / a],
+        [c, b, a] => return quadratic_roots(*a, *b, *c),
+        _ => {}
+    }
+
+    let p = |x: f64| coefficients.iter().rev().fold(0.0, |acc, c| acc * x + c);
+    let leading = coefficients.last().unwrap();
+    let bound = 1.0
+        + coefficients
+            .iter()
+            .map(|c| (c / leading).abs())
+            .fold(0.0, f64::max);
+
+    let derivative: Vec<f64> = coefficients
+        .iter()
+        .enumerate()
+        .skip(1)
+        .map(|(i, c)| c * i as f64)
+        .collect();
+    let mut points = vec![-bound];
+    points.extend(real_roots(&derivative));
+    points.push(bound);
+
+    let mut roots = vec![];
+    for window in points.windows(2) {
+        let (mut a, mut b) = (window[0], window[1]);
+        let (fa, fb) = (p(a), p(b));
+        if fa == 0.0 {
+            roots.push(a);
+            continue;
+        }
+        if fa.signum() == fb.signum() {
+            continue;
+        }
+
+        for _ in 0..BISECTION_STEPS {
+            let m = (a + b) / 2.0;
+            if m == a || m == b {
+                break;
+            }
+            if p(m).signum() == fa.signum() {
+                a = m;
+            } else {
+                b = m;
+            }
+        }
+        roots.push((a + b) / 2.0);
+    }
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn p(input: &str) -> Polynomial {
+        Polynomial::parse(input).unwrap()
+    }
+
+    #[test]
+    fn expansion() {
+        assert_eq!(p("(x + 1)^2").to_string(), "x^2 + 2x + 1");
+        assert_eq!(p("(2y - 3)(y + 1) - y").to_string(), "2y^2 - 2y - 3");
+        assert_eq!(p("x^3/2 - x/3").to_string(), "1/2 x^3 - 1/3 x");
+        assert_eq!(p("(x - 1)(x + 1) - x^2 + 1").to_string(), "0");
+        assert_eq!(p("2^3 + 1").to_string(), "9");
+    }
+
+    #[test]
+    fn not_polynomials() {
+        assert!(Polynomial::parse("1/x").is_err());
+        assert!(Polynomial::parse("x^x").is_err());
+        assert!(Polynomial::parse("x + y").is_err());
+        assert!(Polynomial::parse("x^(1/2)").is_err());
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(p("x + 1").add(&p("x^2 - 1")).unwrap(), p("x^2 + x"));
+        assert_eq!(p("x + 1").sub(&p("3")).unwrap(), p("x - 2"));
+        assert_eq!(p("x + 1").mul(&p("x - 1")).unwrap(), p("x^2 - 1"));
+        assert!(p("x + 1").add(&p("y")).is_err());
+
+        let (quotient, remainder) = p("x^3 - 2x + 5").div_rem(&p("x - 1")).unwrap();
+        assert_eq!(quotient, p("x^2 + x - 1"));
+        assert_eq!(remainder, p("4"));
+        assert!(p("x").div_rem(&p("0")).is_err());
+    }
+
+    #[test]
+    fn factor() {
+        assert_eq!(p("x^2 - 1").factor().unwrap().to_string(), "(x - 1)(x + 1)");
+        assert_eq!(
+            p("2x^3 - 4x^2 + 2x").factor().unwrap().to_string(),
+            "2x(x - 1)^2"
+        );
+        assert_eq!(
+            p("6x^2 + x - 1").factor().unwrap().to_string(),
+            "(2x + 1)(3x - 1)"
+        );
+        assert_eq!(
+            p("x^2/2 - 2").factor().unwrap().to_string(),
+            "1/2(x - 2)(x + 2)"
+        );
+        assert_eq!(p("x^2 + 1").factor().unwrap().to_string(), "x^2 + 1");
+        assert_eq!(p("-x^2 - 1").factor().unwrap().to_string(), "-(x^2 + 1)");
+    }
+
+    #[test]
+    fn factor_without_rational_roots() {
+        assert_eq!(
+            p("x^4 + 4").factor().unwrap().to_string(),
+            "(x^2 - 2x + 2)(x^2 + 2x + 2)"
+        );
+        assert_eq!(
+            p("(x^2 - 2)^2 (x^2 + x + 1)").factor().unwrap().to_string(),
+            "(x^2 - 2)^2(x^2 + x + 1)"
+        );
+    }
+
+    #[test]
+    fn factor_with_many_divisors() {
+        // 963761198400 has 6720 divisors, the candidate roots come from the real roots
+        assert_eq!(
+            p("963761198400x^3 + x + 963761198400")
+                .factor()
+                .unwrap()
+                .to_string(),
+            "963761198400x^3 + x + 963761198400"
+        );
+        assert_eq!(
+            p("(2x - 3)(7207200x^2 + 7207200)")
+                .factor()
+                .unwrap()
+                .to_string(),
+            "7207200(2x - 3)(x^2 + 1)"
+        );
+    }
+
+    #[test]
+    fn factor_past_the_search_limit() {
+        assert_eq!(
+            p("100000000000000000000x^2 - 1")
+                .factor()
+                .unwrap()
+                .to_string(),
+            "(10000000000x - 1)(10000000000x + 1)"
+        );
+        assert_eq!(
+            p("100000000000000000000x^2 + 1")
+                .factor()
+                .unwrap()
+                .to_string(),
+            "100000000000000000000x^2 + 1"
+        );
+        assert_eq!(
+            p("100000000000000000000x^3 - 1").factor().unwrap_err(),
+            "Cannot factor 100000000000000000000x^3 - 1, its coefficients have too many divisors to search"
+        );
+        // The roots are still found numerically
+        let roots = p("100000000000000000000x^3 - 1").roots().unwrap();
+        assert_eq!(roots.len(), 1);
+        assert!((roots[0] - 1e-20f64.cbrt()).abs() < 1e-18);
+    }
+
+    #[test]
+    fn roots() {
+        assert_eq!(
+            p("x^3 - 6x^2 + 11x - 6").roots().unwrap(),
+            vec![1.0, 2.0, 3.0]
+        );
+        assert_eq!(p("x^2 + 1").roots().unwrap(), vec![]);
+
+        let roots = p("x^5 - x - 1").roots().unwrap();
+        assert_eq!(roots.len(), 1);
+        assert!((roots[0] - 1.1673039782614187).abs() < 1e-12);
+
+        let roots = p("(x^2 - 2)(x^3 - 3)").roots().unwrap();
+        let expected = [-(2f64.sqrt()), 2f64.sqrt(), 3f64.cbrt()];
+        assert_eq!(roots.len(), 3);
+        for (root, expected) in roots.iter().zip(expected) {
+            assert!((root - expected).abs() < 1e-12);
+        }
+    }
+}
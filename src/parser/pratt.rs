@@ -0,0 +1,466 @@
+use super::config::ParserConfig;
+use super::expr::Expr;
+use super::tokenizer::{is_identifier, is_number};
+use super::T;
+
+// Keywords of the unit conversions `x to unit` and `x in unit`, which bind looser than
+// every operator
+pub(super) const CONVERSIONS: [&str; 2] = ["to", "in"];
+
+// Left and right binding powers of an infix operator, derived from its precedence. The
+// higher binding power is on the side the operator associates to.
+fn infix_binding_power(config: &ParserConfig, operator: &str) -> Option<(usize, usize)> {
+    let operator = config.get(operator)?;
+    let precedence = operator.precedence;
+    if operator.right_associative {
+        Some((2 * precedence + 2, 2 * precedence + 1))
+    } else {
+        Some((2 * precedence + 1, 2 * precedence + 2))
+    }
+}
+
+// Prefix operators bind tighter than every infix operator but the right associative
+// ones, so that -2^2 = -(2^2) and 2^-1 = 2^(-1)
+fn prefix_binding_power(config: &ParserConfig) -> usize {
+    let operators = || config.symbols().filter_map(|symbol| config.get(symbol));
+    operators()
+        .filter(|operator| operator.right_associative)
+        .map(|operator| 2 * operator.precedence + 1)
+        .min()
+        .unwrap_or(
+            2 * operators()
+                .map(|operator| operator.precedence)
+                .max()
+                .unwrap_or(0)
+                + 3,
+        )
+}
+
+pub struct Parser<'a> {
+    tokens: Vec<String>,
+    // Index of the next token
+    position: usize,
+    config: &'a ParserConfig,
+    depth: usize,
+    nodes: usize,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(tokens: Vec<String>, config: &'a ParserConfig) -> Parser<'a> {
+        Parser {
+            tokens,
+            position: 0,
+            config,
+            depth: 0,
+            nodes: 0,
+        }
+    }
+
+    pub fn parse(mut self) -> Result<Expr, String> {
+        if self.peek().is_none() {
+            return Err("Expected an expression".to_string());
+        }
+
+        let expr = self.parse_expr(0, None)?;
+        match self.next_token() {
+            None => Ok(expr),
+            Some(token) if token == ")" => Err("Unmatched ')'".to_string()),
+            Some(token) if token == "]" => Err("Unmatched ']'".to_string()),
+            Some(token) if token == "," => {
+                Err("Unexpected ',' outside of a function call or a list".to_string())
+            }
+            Some(token) => Err(format!("Unexpected '{token}'")),
+        }
+    }
+
+    // Parses operators binding at least as tight as `min_binding_power`, `after` is the
+    // operator preceding the expression if any
+    fn parse_expr(
+        &mut self,
+        min_binding_power: usize,
+        after: Option<&str>,
+    ) -> Result<Expr, String> {
+        self.depth += 1;
+        self.config.limits.check_depth(self.depth)?;
+        let mut left = self.parse_operand(after)?;
+
+        while let Some(token) = self.peek() {
+            if token == ")" || token == "]" || token == "," {
+                break;
+            }
+            if CONVERSIONS.contains(&token.as_str()) {
+                if min_binding_power > 0 {
+                    break;
+                }
+                let keyword = self.next_token().unwrap();
+                let unit = self.parse_expr(1, Some(&keyword))?;
+                self.add_nodes(1)?;
+                left = Expr::Call("to".to_string(), vec![left, unit]);
+                continue;
+            }
+
+            let Some((left_power, right_power)) = infix_binding_power(self.config, token) else {
+                return Err(format!("Expected an operator before '{token}'"));
+            };
+            if left_power < min_binding_power {
+                break;
+            }
+
+            let operator = self.next_token().unwrap();
+            let right = self.parse_expr(right_power, Some(&operator))?;
+            self.add_nodes(1)?;
+            let operation = &self.config.get(&operator).unwrap().operation;
+            left = operation.build(left, right);
+        }
+
+        self.depth -= 1;
+        Ok(left)
+    }
+
+    fn peek(&self) -> Option<&String> {
+        self.tokens.get(self.position)
+    }
+
+    fn next_token(&mut self) -> Option<String> {
+        let token = std::mem::take(self.tokens.get_mut(self.position)?);
+        self.position += 1;
+        Some(token)
+    }
+
+    fn next_if(&mut self, expected: &str) -> bool {
+        let found = self.peek().is_some_and(|token| token == expected);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn add_nodes(&mut self, nodes: usize) -> Result<(), String> {
+        self.nodes += nodes;
+        self.config.limits.check_nodes(self.nodes)
+    }
+
+    // Operand followed by its indices, such as `v[0][1]`
+    fn parse_operand(&mut self, after: Option<&str>) -> Result<Expr, String> {
+        let mut operand = self.parse_primary(after)?;
+        while self.next_if("[") {
+            let index = self.parse_expr(0, Some("["))?;
+            self.expect_closing("]", "'['")?;
+            self.add_nodes(1)?;
+            operand = Expr::Index(Box::new(operand), Box::new(index));
+        }
+        Ok(operand)
+    }
+
+    fn parse_primary(&mut self, after: Option<&str>) -> Result<Expr, String> {
+        let expected = |found: Option<&str>| {
+            let found = match found {
+                Some(token) => format!("found '{token}'"),
+                None => "reached the end of the input".to_string(),
+            };
+            match after {
+                Some(operator) => format!("Expected an operand after '{operator}', {found}"),
+                None => format!("Expected an operand, {found}"),
+            }
+        };
+
+        let Some(token) = self.next_token() else {
+            return Err(expected(None));
+        };
+
+        if let Ok(value) = token.parse::<T>() {
+            self.add_nodes(1)?;
+            return Ok(Expr::Litteral(value));
+        }
+
+        // Dates are calls to `iso_date`, 2026-10-18 being iso_date(2026, 10, 18), so that
+        // each backend reads them as a date or as a subtraction
+        if is_number(&token) && token.contains('-') {
+            let mut parts = vec![];
+            for part in token.split('-') {
+                let value = part
+                    .parse::<T>()
+                    .map_err(|_| format!("Invalid date {token}"))?;
+                parts.push(Expr::Litteral(value));
+            }
+            self.add_nodes(4)?;
+            return Ok(Expr::Call("iso_date".to_string(), parts));
+        }
+
+        // Decimals are calls to `decimal` with their digits and their number of decimals,
+        // 2.5 being decimal(25, 1), so that each backend builds them exactly
+        if let Some((integer, decimals)) = token.split_once('.') {
+            let value = format!("{integer}{decimals}").parse::<T>();
+            let scale = decimals.len() as u32;
+            let (Ok(value), Some(_)) = (value, T::checked_pow(10, scale)) else {
+                return Err(format!("Invalid number {token}"));
+            };
+            self.add_nodes(3)?;
+            let parts = vec![Expr::Litteral(value), Expr::Litteral(scale as T)];
+            return Ok(Expr::Call("decimal".to_string(), parts));
+        }
+
+        if is_identifier(&token) {
+            if self.next_if("->") {
+                return self.parse_lambda(vec![token]);
+            }
+            self.add_nodes(1)?;
+            if self.next_if("(") {
+                let args = self.parse_items(")", &format!("the call to {token}"))?;
+                return Ok(Expr::Call(token, args));
+            }
+            return Ok(Expr::Variable(token));
+        }
+
+        if token == "(" {
+            if let Some(params) = self.lambda_params() {
+                return self.parse_lambda(params);
+            }
+            let expr = self.parse_expr(0, Some("("))?;
+            self.expect_closing(")", "'('")?;
+            return Ok(expr);
+        }
+
+        if token == "[" {
+            let items = self.parse_items("]", "a list")?;
+            self.add_nodes(1)?;
+            return Ok(Expr::List(items));
+        }
+
+        // The negation of the config is applied to 0, `-x` being `0 - x`
+        let negation = self.config.get_negation();
+        if token == "+" || Some(token.as_str()) == negation {
+            let operand = self.parse_expr(prefix_binding_power(self.config), Some(&token))?;
+            let Some(operator) = negation.filter(|negation| *negation == token) else {
+                return Ok(operand);
+            };
+            self.add_nodes(2)?;
+            let operation = &self.config.get(operator).unwrap().operation;
+            return Ok(operation.build(Expr::Litteral(0), operand));
+        }
+
+        Err(expected(Some(&token)))
+    }
+
+    // Parameters of a lambda such as `(x, y) -> x * y`, after its opening parenthesis. The
+    // tokens up to the arrow are consumed if they are ones.
+    fn lambda_params(&mut self) -> Option<Vec<String>> {
+        let mut tokens = self.tokens[self.position..].iter().map(String::as_str);
+        let mut params = vec![];
+        let mut token = tokens.next()?;
+        while token != ")" {
+            if !is_identifier(token) {
+                return None;
+            }
+            params.push(token.to_string());
+            token = match tokens.next()? {
+                "," => tokens.next().filter(|token| is_identifier(token))?,
+                ")" => ")",
+                _ => return None,
+            };
+        }
+        if tokens.next()? != "->" {
+            return None;
+        }
+
+        // The parameters with their separators, the closing parenthesis and the arrow
+        self.position += (2 * params.len()).max(1) + 1;
+        Some(params)
+    }
+
+    // Body of a lambda, after its arrow. It extends as far as possible to the right.
+    fn parse_lambda(&mut self, params: Vec<String>) -> Result<Expr, String> {
+        for (i, param) in params.iter().enumerate() {
+            if params[..i].contains(param) {
+                return Err(format!("Duplicate parameter {param} in a lambda"));
+            }
+        }
+        let body = self.parse_expr(0, Some("->"))?;
+        self.add_nodes(1)?;
+        Ok(Expr::Lambda(params, Box::new(body)))
+    }
+
+    // Arguments of a call or items of a list, after its opening bracket. `what` is the
+    // call or the list in the errors.
+    fn parse_items(&mut self, close: &str, what: &str) -> Result<Vec<Expr>, String> {
+        let mut items = vec![];
+        if self.next_if(close) {
+            return Ok(items);
+        }
+
+        let open = if close == ")" { "(" } else { "[" };
+        loop {
+            items.push(self.parse_expr(0, Some(if items.is_empty() { open } else { "," }))?);
+            match self.next_token() {
+                Some(token) if token == "," => {}
+                Some(token) if token == close => return Ok(items),
+                Some(token) => {
+                    return Err(format!(
+                        "Expected ',' or '{close}' in {what}, found '{token}'"
+                    ))
+                }
+                None => {
+                    return Err(format!(
+                        "Expected '{close}' to close {what}, reached the end of the input"
+                    ))
+                }
+            }
+        }
+    }
+
+    fn expect_closing(&mut self, close: &str, what: &str) -> Result<(), String> {
+        match self.next_token() {
+            Some(token) if token == close => Ok(()),
+            Some(token) => Err(format!(
+                "Expected '{close}' to close {what}, found '{token}'"
+            )),
+            None => Err(format!(
+                "Expected '{close}' to close {what}, reached the end of the input"
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> Result<String, String> {
+        Expr::parse(input).map(|expr| format!("{:?}", expr))
+    }
+
+    #[test]
+    fn precedence_and_associativity() {
+        assert_eq!(parse("1 + 2 * 3").unwrap(), "Add(1, Prod(2, 3))");
+        assert_eq!(parse("1 - 2 - 3").unwrap(), "Sub(Sub(1, 2), 3)");
+        assert_eq!(parse("2 ^ 3 ^ 2").unwrap(), "Pow(2, Pow(3, 2))");
+        assert_eq!(parse("8 / 4 % 3").unwrap(), "Mod(Div(8, 4), 3)");
+    }
+
+    #[test]
+    fn prefix_operators() {
+        assert_eq!(parse("-2 ^ 2").unwrap(), "Sub(0, Pow(2, 2))");
+        assert_eq!(parse("2 * -3").unwrap(), "Prod(2, Sub(0, 3))");
+        assert_eq!(parse("2 ^ -x").unwrap(), "Pow(2, Sub(0, x))");
+        assert_eq!(parse("+3 - -3").unwrap(), "Sub(3, Sub(0, 3))");
+    }
+
+    #[test]
+    fn calls() {
+        assert_eq!(parse("f()").unwrap(), "f()");
+        assert_eq!(parse("f(1, g(x) + 2)").unwrap(), "f(1, Add(g(x), 2))");
+    }
+
+    #[test]
+    fn lambdas() {
+        assert_eq!(parse("x -> x + 1").unwrap(), "x -> Add(x, 1)");
+        assert_eq!(parse("(x, y) -> x * y").unwrap(), "(x, y) -> Prod(x, y)");
+        assert_eq!(parse("() -> 1").unwrap(), "() -> 1");
+        assert_eq!(parse("map(x -> x, l)").unwrap(), "map(x -> x, l)");
+        assert_eq!(parse("(x) + 1").unwrap(), "Add(x, 1)");
+        assert_eq!(
+            parse("(x, y) -> ").unwrap_err(),
+            "Expected an operand after '->', reached the end of the input"
+        );
+        assert_eq!(
+            parse("(x, x) -> x").unwrap_err(),
+            "Duplicate parameter x in a lambda"
+        );
+        assert_eq!(
+            parse("2 -> 3").unwrap_err(),
+            "Expected an operator before '->'"
+        );
+    }
+
+    #[test]
+    fn lists() {
+        assert_eq!(parse("[1, x + 1, []]").unwrap(), "[1, Add(x, 1), []]");
+        assert_eq!(
+            parse("v[0][i + 1] * 2").unwrap(),
+            "Prod(v[0][Add(i, 1)], 2)"
+        );
+        assert_eq!(parse("-f(x)[0]").unwrap(), "Sub(0, f(x)[0])");
+        assert_eq!(
+            parse("[1, 2").unwrap_err(),
+            "Expected ']' to close a list, reached the end of the input"
+        );
+        assert_eq!(
+            parse("[1 2]").unwrap_err(),
+            "Expected an operator before '2'"
+        );
+        assert_eq!(
+            parse("v[1)").unwrap_err(),
+            "Expected ']' to close '[', found ')'"
+        );
+        assert_eq!(parse("1]").unwrap_err(), "Unmatched ']'");
+    }
+
+    #[test]
+    fn decimals_and_conversions() {
+        assert_eq!(
+            parse("2.5 + 0.75").unwrap(),
+            "Add(decimal(25, 1), decimal(75, 2))"
+        );
+        // Integers can't hold decimals, rather than rounding them
+        let eval = |input| Expr::parse(input).unwrap().eval();
+        assert_eq!(
+            eval("2.5 + 0.75").unwrap_err(),
+            "Decimals such as 2.5 need a non-integer backend"
+        );
+        assert_eq!(
+            eval("2 * 0.01").unwrap_err(),
+            "Decimals such as 0.01 need a non-integer backend"
+        );
+        assert_eq!(eval("2.00 * 2 + 1.0"), Ok(5));
+        // Dates are subtractions but for the backend with dates
+        assert_eq!(parse("2026-10-18").unwrap(), "iso_date(2026, 10, 18)");
+        assert_eq!(eval("2026-10-18 * 2"), Ok(3996));
+        let expr = Expr::parse("2.5 + 0.75").unwrap();
+        assert_eq!(expr.eval_with::<f64>(&Default::default()), Ok(3.25));
+        assert_eq!(
+            parse("3 m + 20 cm to cm").unwrap(),
+            "to(Add(Prod(3, m), Prod(20, cm)), cm)"
+        );
+        assert_eq!(
+            parse("(90 km/h in m/s) * 2").unwrap(),
+            "Prod(to(Div(Prod(90, km), h), Div(m, s)), 2)"
+        );
+        assert_eq!(parse("x to y to z").unwrap(), "to(to(x, y), z)");
+        assert_eq!(
+            parse("3 m to").unwrap_err(),
+            "Expected an operand after 'to', reached the end of the input"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            parse("3 +").unwrap_err(),
+            "Expected an operand after '+', reached the end of the input"
+        );
+        assert_eq!(
+            parse("3*(4+(5+)").unwrap_err(),
+            "Expected an operand after '+', found ')'"
+        );
+        assert_eq!(
+            parse("(1 + 2").unwrap_err(),
+            "Expected ')' to close '(', reached the end of the input"
+        );
+        assert_eq!(parse("1 + 2)").unwrap_err(), "Unmatched ')'");
+        assert_eq!(parse("* 2").unwrap_err(), "Expected an operand, found '*'");
+        assert_eq!(parse("x y").unwrap_err(), "Expected an operator before 'y'");
+        assert_eq!(
+            parse("f(1 2)").unwrap_err(),
+            "Expected an operator before '2'"
+        );
+        assert_eq!(
+            parse("f(1,)").unwrap_err(),
+            "Expected an operand after ',', found ')'"
+        );
+        assert_eq!(
+            parse("(1, 2)").unwrap_err(),
+            "Expected ')' to close '(', found ','"
+        );
+        assert_eq!(parse("").unwrap_err(), "Expected an expression");
+    }
+}
@@ -1,36 +1,101 @@
-use super::T;
+use super::config::ParserConfig;
+use super::money::CURRENCY_SYMBOLS;
+use super::pratt::CONVERSIONS;
+use super::time::DURATION_UNITS;
+use super::tokenizer::{is_identifier, is_number};
+use std::iter::Peekable;
+use std::slice::Iter;
 
-pub fn preprocess_tokens(tokens: Vec<String>) -> Result<Vec<String>, String> {
+pub fn preprocess_tokens(
+    tokens: Vec<String>,
+    config: &ParserConfig,
+) -> Result<Vec<String>, String> {
+    let negation = config.get_negation();
     let mut preprocessed_tokens = vec![];
     let mut it = tokens.iter().peekable();
 
     while let Some(token) = it.next() {
         match token.as_str() {
+            // Handle case ... number unit number unit => ... ( number * unit ( unit ) +
+            // number * unit ( unit ) ), for durations such as 3h 20min, whose units can't be
+            // variables
+            token if is_number(token) && duration_run(token, it.clone()) > 1 => {
+                preprocessed_tokens.push("(".to_string());
+                for i in 0..duration_run(token, it.clone()) {
+                    let number = if i == 0 { token } else { it.next().unwrap() };
+                    let unit = it.next().unwrap();
+                    if i > 0 {
+                        preprocessed_tokens.push("+".to_string());
+                    }
+                    preprocessed_tokens
+                        .extend([number, "*", "unit", "(", unit, ")"].map(String::from));
+                }
+                preprocessed_tokens.push(")".to_string());
+            }
+            // Handle case ... number i => ... ( number * i ), the imaginary suffix binds
+            // like a litteral so that 1/2i = 1/(2i)
+            token if is_number(token) && it.peek() == Some(&&"i".to_string()) => {
+                it.next();
+                preprocessed_tokens.extend(["(", token, "*", "i", ")"].map(String::from));
+            }
+            // Handle case ... $ number => ... ( number * USD )
+            token if currency(token).is_some() && it.peek().is_some_and(|x| is_number(x)) => {
+                let (number, code) = (it.next().unwrap(), currency(token).unwrap());
+                preprocessed_tokens.extend(["(", number, "*", code, ")"].map(String::from));
+            }
             // Handle case ... number ( expr ) => ... number * ( expr )
+            token if (is_number(token) || token == ")") && it.peek() == Some(&&"(".to_string()) => {
+                preprocessed_tokens.push(token.to_string());
+                preprocessed_tokens.push("*".to_string());
+            }
+            // Handle case ... number x => ... ( number * x ), the unit binds like a litteral
+            // with its exponent, so that 6 kg / 2 m^3 = (6 kg) / (2 m^3), unless the number
+            // is itself an exponent as in 2^3 x
+            token
+                if is_number(token)
+                    && !preprocessed_tokens
+                        .last()
+                        .is_some_and(|x| binds_tighter(x, config))
+                    && unit_tokens(it.clone(), config).is_some() =>
+            {
+                let unit = unit_tokens(it.clone(), config).unwrap();
+                preprocessed_tokens.extend(["(", token, "*"].map(String::from));
+                preprocessed_tokens.extend(unit.iter().map(|x| x.to_string()));
+                preprocessed_tokens.push(")".to_string());
+                for _ in 0..unit.len() {
+                    it.next();
+                }
+                continue;
+            }
+            // Handle case ... number x => ... number * x otherwise, and ... ) x => ... ) * x,
+            // but not before the keyword of a conversion such as `(3 m) to cm`
             token
-                if (token.parse::<T>().is_ok() || token == ")")
-                    && it.peek() == Some(&&"(".to_string()) =>
+                if (is_number(token) || token == ")")
+                    && it
+                        .peek()
+                        .map(|x| is_identifier(x) && !CONVERSIONS.contains(&x.as_str()))
+                        .unwrap_or(false) =>
             {
                 preprocessed_tokens.push(token.to_string());
                 preprocessed_tokens.push("*".to_string());
             }
             // Handle case ... ) number => ... ) * number
-            ")" if it.peek().map(|x| x.parse::<T>().is_ok()).unwrap_or(false) => {
+            ")" if it.peek().map(|x| is_number(x)).unwrap_or(false) => {
                 preprocessed_tokens.push(")".to_string());
                 preprocessed_tokens.push("*".to_string());
             }
-            // Handle inputs like "-12" => "0-12"
-            "-" if preprocessed_tokens.is_empty() => {
+            // Handle inputs like "-12" => "0-12", with the negation operator of the config
+            token if Some(token) == negation && preprocessed_tokens.is_empty() => {
                 preprocessed_tokens.push("0".to_string());
-                preprocessed_tokens.push(token.clone());
+                preprocessed_tokens.push(token.to_string());
             }
-            // Handle inputs like "1+(-12)" => "1+(-12)"
-            "-" => {
+            // Handle inputs like "1+(-12)" => "1+(0-12)" and "f(1, -12)" => "f(1, 0-12)"
+            token if Some(token) == negation => {
                 let previous_token = preprocessed_tokens.last().unwrap();
-                if previous_token == "(" {
+                if previous_token == "(" || previous_token == "," {
                     preprocessed_tokens.push("0".to_string());
                 }
-                preprocessed_tokens.push(token.clone());
+                preprocessed_tokens.push(token.to_string());
             }
             // General case
             _ => {
@@ -42,7 +107,64 @@ pub fn preprocess_tokens(tokens: Vec<String>) -> Result<Vec<String>, String> {
     Ok(preprocessed_tokens)
 }
 
+// Unit following a number, such as `m` or `m ^ 3`, but not the keyword of a conversion such
+// as `3 m to cm`, a call such as `2 sin(x)` or a power such as `2 x^(1/2)`
+fn unit_tokens<'a>(
+    mut it: Peekable<Iter<'a, String>>,
+    config: &ParserConfig,
+) -> Option<Vec<&'a String>> {
+    let unit = it.next_if(|x| is_identifier(x) && !CONVERSIONS.contains(&x.as_str()))?;
+    if it.peek().is_some_and(|x| *x == "(") {
+        return None;
+    }
+
+    // The operators which bind tighter than the product, with simple operands
+    config.get("*")?;
+    let mut tokens = vec![unit];
+    while let Some(operator) = it.next_if(|x| binds_tighter(x, config)) {
+        let operand = it.next_if(|x| is_number(x) || is_identifier(x))?;
+        if it.peek().is_some_and(|x| *x == "(") {
+            return None;
+        }
+        tokens.extend([operator, operand]);
+    }
+
+    Some(tokens)
+}
+
+fn binds_tighter(token: &str, config: &ParserConfig) -> bool {
+    match (config.get(token), config.get("*")) {
+        (Some(operator), Some(product)) => operator.precedence > product.precedence,
+        _ => false,
+    }
+}
+
+// Number of pairs of a number and a duration unit starting with `number`, such as 2 for
+// 3h 20min
+fn duration_run(number: &str, mut it: Peekable<Iter<String>>) -> usize {
+    let mut pairs = 0;
+    let mut number = Some(number);
+    while number.is_some_and(is_number) && it.next_if(|x| is_duration_unit(x)).is_some() {
+        pairs += 1;
+        number = it.next().map(String::as_str);
+    }
+    pairs
+}
+
+fn is_duration_unit(token: &str) -> bool {
+    DURATION_UNITS.iter().any(|(unit, _)| *unit == token)
+}
+
+// Code of the currency written with the symbol `token`
+fn currency(token: &str) -> Option<&'static str> {
+    CURRENCY_SYMBOLS
+        .iter()
+        .find(|(symbol, _)| token.chars().eq([*symbol]))
+        .map(|(_, code)| *code)
+}
+
 #[cfg(test)]
+#[allow(clippy::useless_vec)]
 mod tests {
     use super::*;
 
@@ -52,7 +174,7 @@ mod tests {
             .iter()
             .map(|x| x.to_string())
             .collect();
-        let preprocessed_tokens = preprocess_tokens(tokens).unwrap();
+        let preprocessed_tokens = preprocess_tokens(tokens, &ParserConfig::default()).unwrap();
         assert_eq!(
             preprocessed_tokens,
             vec!["12", "+", "(", "1", "*", "7", ")"]
@@ -62,7 +184,7 @@ mod tests {
     #[test]
     fn negative_value() {
         let tokens = vec!["-", "12"].iter().map(|x| x.to_string()).collect();
-        let preprocessed_tokens = preprocess_tokens(tokens).unwrap();
+        let preprocessed_tokens = preprocess_tokens(tokens, &ParserConfig::default()).unwrap();
         assert_eq!(preprocessed_tokens, vec!["0", "-", "12"]);
     }
 
@@ -72,20 +194,116 @@ mod tests {
             .iter()
             .map(|x| x.to_string())
             .collect();
-        let preprocessed_tokens = preprocess_tokens(tokens).unwrap();
+        let preprocessed_tokens = preprocess_tokens(tokens, &ParserConfig::default()).unwrap();
         assert_eq!(
             preprocessed_tokens,
             vec!["4", "+", "(", "0", "-", "12", ")"]
         );
     }
 
+    #[test]
+    fn negative_argument() {
+        let tokens = ["f", "(", "1", ",", "-", "2", ")"]
+            .iter()
+            .map(|x| x.to_string())
+            .collect();
+        let preprocessed_tokens = preprocess_tokens(tokens, &ParserConfig::default()).unwrap();
+        assert_eq!(
+            preprocessed_tokens,
+            vec!["f", "(", "1", ",", "0", "-", "2", ")"]
+        );
+    }
+
+    #[test]
+    fn pre_variable() {
+        let tokens = ["2", "x", "+", "(", "1", ")", "y"]
+            .iter()
+            .map(|x| x.to_string())
+            .collect();
+        let preprocessed_tokens = preprocess_tokens(tokens, &ParserConfig::default()).unwrap();
+        assert_eq!(
+            preprocessed_tokens,
+            vec!["(", "2", "*", "x", ")", "+", "(", "1", ")", "*", "y"]
+        );
+    }
+
+    #[test]
+    fn imaginary_suffix() {
+        let tokens = ["3", "+", "4", "i"].iter().map(|x| x.to_string()).collect();
+        let preprocessed_tokens = preprocess_tokens(tokens, &ParserConfig::default()).unwrap();
+        assert_eq!(preprocessed_tokens, vec!["3", "+", "(", "4", "*", "i", ")"]);
+    }
+
+    #[test]
+    fn units() {
+        let tokens = ["2.5", "km", "to", "m"]
+            .iter()
+            .map(|x| x.to_string())
+            .collect();
+        let preprocessed_tokens = preprocess_tokens(tokens, &ParserConfig::default()).unwrap();
+        assert_eq!(
+            preprocessed_tokens,
+            vec!["(", "2.5", "*", "km", ")", "to", "m"]
+        );
+
+        let tokens = ["6", "kg", "/", "2", "m", "^", "3"]
+            .iter()
+            .map(|x| x.to_string())
+            .collect();
+        let preprocessed_tokens = preprocess_tokens(tokens, &ParserConfig::default()).unwrap();
+        assert_eq!(
+            preprocessed_tokens,
+            vec!["(", "6", "*", "kg", ")", "/", "(", "2", "*", "m", "^", "3", ")"]
+        );
+
+        // Calls and complex exponents aren't units
+        let tokens = ["2", "x", "^", "(", "1", ")", "/", "2", "f", "(", "x", ")"]
+            .iter()
+            .map(|x| x.to_string())
+            .collect();
+        let preprocessed_tokens = preprocess_tokens(tokens, &ParserConfig::default()).unwrap();
+        assert_eq!(
+            preprocessed_tokens,
+            vec!["2", "*", "x", "^", "(", "1", ")", "/", "2", "*", "f", "(", "x", ")"]
+        );
+    }
+
+    #[test]
+    fn durations() {
+        let tokens = ["3", "h", "20", "min", "-", "1", "s"]
+            .iter()
+            .map(|x| x.to_string())
+            .collect();
+        let preprocessed_tokens = preprocess_tokens(tokens, &ParserConfig::default()).unwrap();
+        assert_eq!(
+            preprocessed_tokens,
+            vec![
+                "(", "3", "*", "unit", "(", "h", ")", "+", "20", "*", "unit", "(", "min", ")", ")",
+                "-", "(", "1", "*", "s", ")"
+            ]
+        );
+    }
+
+    #[test]
+    fn currency_symbols() {
+        let tokens = ["$", "3", "+", "€", "2.5"]
+            .iter()
+            .map(|x| x.to_string())
+            .collect();
+        let preprocessed_tokens = preprocess_tokens(tokens, &ParserConfig::default()).unwrap();
+        assert_eq!(
+            preprocessed_tokens,
+            vec!["(", "3", "*", "USD", ")", "+", "(", "2.5", "*", "EUR", ")"]
+        );
+    }
+
     #[test]
     fn pre_parentheses() {
         let tokens = vec!["3", "(", "12", ")"]
             .iter()
             .map(|x| x.to_string())
             .collect();
-        let preprocess_tokens = preprocess_tokens(tokens).unwrap();
+        let preprocess_tokens = preprocess_tokens(tokens, &ParserConfig::default()).unwrap();
         assert_eq!(preprocess_tokens, vec!["3", "*", "(", "12", ")"]);
     }
 
@@ -95,7 +313,7 @@ mod tests {
             .iter()
             .map(|x| x.to_string())
             .collect();
-        let preprocess_tokens = preprocess_tokens(tokens).unwrap();
+        let preprocess_tokens = preprocess_tokens(tokens, &ParserConfig::default()).unwrap();
         assert_eq!(preprocess_tokens, vec!["(", "12", ")", "*", "3"]);
     }
 }
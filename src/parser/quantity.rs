@@ -0,0 +1,556 @@
+use super::number::Number;
+use super::T;
+use std::fmt::Display;
+
+// Exponents of the base dimensions: length, mass, time, electric current, temperature,
+// amount of substance and luminous intensity
+type Dimensions = [i8; 7];
+
+const NONE: Dimensions = [0, 0, 0, 0, 0, 0, 0];
+const LENGTH: Dimensions = [1, 0, 0, 0, 0, 0, 0];
+const MASS: Dimensions = [0, 1, 0, 0, 0, 0, 0];
+const TIME: Dimensions = [0, 0, 1, 0, 0, 0, 0];
+const VOLUME: Dimensions = [3, 0, 0, 0, 0, 0, 0];
+const FORCE: Dimensions = [1, 1, -2, 0, 0, 0, 0];
+const ENERGY: Dimensions = [2, 1, -2, 0, 0, 0, 0];
+const POWER: Dimensions = [2, 1, -3, 0, 0, 0, 0];
+const PRESSURE: Dimensions = [-1, 1, -2, 0, 0, 0, 0];
+
+struct Unit {
+    name: &'static str,
+    // Value of the unit in base units
+    scale: f64,
+    dimensions: Dimensions,
+}
+
+const fn unit(name: &'static str, scale: f64, dimensions: Dimensions) -> Unit {
+    Unit {
+        name,
+        scale,
+        dimensions,
+    }
+}
+
+// The base units come first, in the order of the dimensions. `in` is the keyword of the
+// conversions, the inch is `inch`.
+const UNITS: [Unit; 35] = [
+    unit("m", 1.0, LENGTH),
+    unit("kg", 1.0, MASS),
+    unit("s", 1.0, TIME),
+    unit("A", 1.0, [0, 0, 0, 1, 0, 0, 0]),
+    unit("K", 1.0, [0, 0, 0, 0, 1, 0, 0]),
+    unit("mol", 1.0, [0, 0, 0, 0, 0, 1, 0]),
+    unit("cd", 1.0, [0, 0, 0, 0, 0, 0, 1]),
+    unit("km", 1e3, LENGTH),
+    unit("cm", 1e-2, LENGTH),
+    unit("mm", 1e-3, LENGTH),
+    unit("inch", 0.0254, LENGTH),
+    unit("ft", 0.3048, LENGTH),
+    unit("mi", 1609.344, LENGTH),
+    unit("g", 1e-3, MASS),
+    unit("mg", 1e-6, MASS),
+    unit("lb", 0.45359237, MASS),
+    unit("ms", 1e-3, TIME),
+    unit("min", 60.0, TIME),
+    unit("h", 3600.0, TIME),
+    unit("day", 86400.0, TIME),
+    unit("L", 1e-3, VOLUME),
+    unit("mL", 1e-6, VOLUME),
+    unit("Hz", 1.0, [0, 0, -1, 0, 0, 0, 0]),
+    unit("N", 1.0, FORCE),
+    unit("kN", 1e3, FORCE),
+    unit("J", 1.0, ENERGY),
+    unit("kJ", 1e3, ENERGY),
+    unit("kWh", 3.6e6, ENERGY),
+    unit("W", 1.0, POWER),
+    unit("kW", 1e3, POWER),
+    unit("Pa", 1.0, PRESSURE),
+    unit("kPa", 1e3, PRESSURE),
+    unit("bar", 1e5, PRESSURE),
+    unit("V", 1.0, [2, 1, -3, -1, 0, 0, 0]),
+    unit("ohm", 1.0, [2, 1, -3, -2, 0, 0, 0]),
+];
+
+// Units a quantity is displayed in, as powers of the units of the table
+#[derive(Debug, Clone, Copy)]
+struct Units {
+    factors: [(usize, i8); 7],
+    len: usize,
+}
+
+impl Units {
+    const NONE: Units = Units {
+        factors: [(0, 0); 7],
+        len: 0,
+    };
+
+    fn single(unit: usize) -> Units {
+        let mut units = Units::NONE;
+        units.push(unit, 1);
+        units
+    }
+
+    // Base units of the dimensions, for the units which can't be kept
+    fn base(dimensions: Dimensions) -> Units {
+        let mut units = Units::NONE;
+        for (unit, &power) in dimensions.iter().enumerate() {
+            if power != 0 {
+                units.push(unit, power);
+            }
+        }
+        units
+    }
+
+    fn factors(&self) -> &[(usize, i8)] {
+        &self.factors[..self.len]
+    }
+
+    fn push(&mut self, unit: usize, power: i8) {
+        self.factors[self.len] = (unit, power);
+        self.len += 1;
+    }
+
+    fn scale(&self) -> f64 {
+        self.factors()
+            .iter()
+            .map(|&(unit, power)| UNITS[unit].scale.powi(power.into()))
+            .product()
+    }
+
+    // Product of the units by the others raised to `sign`, a unit having the dimensions
+    // of one already there is expressed with it, so that m * cm = m^2
+    fn mul(self, other: Units, sign: i8) -> Option<Units> {
+        let mut units = self;
+        for &(unit, power) in other.factors() {
+            let same = units.factors().iter().position(|&(existing, _)| {
+                existing == unit || UNITS[existing].dimensions == UNITS[unit].dimensions
+            });
+            match same {
+                Some(i) => {
+                    let (existing, existing_power) = units.factors[i];
+                    let power = existing_power.checked_add(power.checked_mul(sign)?)?;
+                    units.factors[i] = (existing, power);
+                }
+                None if units.len == units.factors.len() => return None,
+                None => units.push(unit, power.checked_mul(sign)?),
+            }
+        }
+
+        let mut kept = Units::NONE;
+        for &(unit, power) in units.factors() {
+            if power != 0 {
+                kept.push(unit, power);
+            }
+        }
+        Some(kept)
+    }
+
+    fn pow(self, exponent: i8) -> Option<Units> {
+        let mut units = self;
+        for factor in &mut units.factors[..self.len] {
+            factor.1 = factor.1.checked_mul(exponent)?;
+        }
+        Some(units)
+    }
+}
+
+impl Display for Units {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let factor = |unit: usize, power: i8| match power {
+            1 => UNITS[unit].name.to_string(),
+            power => format!("{}^{power}", UNITS[unit].name),
+        };
+        let (numerator, denominator): (Vec<_>, Vec<_>) =
+            self.factors().iter().partition(|(_, power)| *power > 0);
+        // Negative powers when there is nothing to divide, such as s^-1
+        if numerator.is_empty() {
+            let factors: Vec<_> = denominator.iter().map(|&&(u, p)| factor(u, p)).collect();
+            return write!(f, "{}", factors.join("*"));
+        }
+
+        let numerator: Vec<_> = numerator.iter().map(|&&(u, p)| factor(u, p)).collect();
+        let denominator: Vec<_> = denominator.iter().map(|&&(u, p)| factor(u, -p)).collect();
+        match denominator.len() {
+            0 => write!(f, "{}", numerator.join("*")),
+            1 => write!(f, "{}/{}", numerator.join("*"), denominator[0]),
+            _ => write!(f, "{}/({})", numerator.join("*"), denominator.join("*")),
+        }
+    }
+}
+
+// Number with a unit, the value being kept in base units. Operations check the
+// dimensions, and the result is displayed in the units of its operands.
+#[derive(Debug, Clone, Copy)]
+pub struct Quantity {
+    value: f64,
+    dimensions: Dimensions,
+    units: Units,
+}
+
+impl Quantity {
+    pub fn new(value: f64) -> Quantity {
+        Quantity {
+            value,
+            dimensions: NONE,
+            units: Units::NONE,
+        }
+    }
+
+    // `value` is expressed in the unit with the given name
+    pub fn with_unit(value: f64, unit: &str) -> Result<Quantity, String> {
+        let unit = Quantity::unit(unit).ok_or_else(|| format!("Unknown unit {unit}"))?;
+        Ok(Quantity {
+            value: value * unit.value,
+            ..unit
+        })
+    }
+
+    // Value in the units the quantity is displayed in
+    pub fn value(&self) -> f64 {
+        self.value / self.units.scale()
+    }
+
+    pub fn is_dimensionless(&self) -> bool {
+        self.dimensions == NONE
+    }
+
+    fn unit(name: &str) -> Option<Quantity> {
+        let index = UNITS.iter().position(|unit| unit.name == name)?;
+        Some(Quantity {
+            value: UNITS[index].scale,
+            dimensions: UNITS[index].dimensions,
+            units: Units::single(index),
+        })
+    }
+
+    fn from_parts(value: f64, dimensions: Dimensions, units: Option<Units>) -> Quantity {
+        let units = match units {
+            _ if dimensions == NONE => Units::NONE,
+            Some(units) => units,
+            None => Units::base(dimensions),
+        };
+        Quantity {
+            value,
+            dimensions,
+            units,
+        }
+    }
+
+    // A zero without unit is compatible with every dimension, so that -x = 0 - x
+    fn is_bare_zero(&self) -> bool {
+        self.value == 0.0 && self.is_dimensionless()
+    }
+
+    // Dimensions and units of the sum or difference of both quantities
+    fn same_dimensions(
+        self,
+        other: Quantity,
+        error: impl Fn() -> String,
+    ) -> Result<(Dimensions, Units), String> {
+        if self.dimensions == other.dimensions && !self.is_dimensionless() {
+            return Ok((self.dimensions, self.units));
+        }
+        if self.is_bare_zero() || self.dimensions == other.dimensions {
+            return Ok((other.dimensions, other.units));
+        }
+        if other.is_bare_zero() {
+            return Ok((self.dimensions, self.units));
+        }
+        Err(format!("{}, which have different dimensions", error()))
+    }
+
+    fn product_dimensions(self, other: Quantity, sign: i8) -> Result<Dimensions, String> {
+        let mut dimensions = self.dimensions;
+        for (dimension, power) in dimensions.iter_mut().zip(other.dimensions) {
+            *dimension = power
+                .checked_mul(sign)
+                .and_then(|power| dimension.checked_add(power))
+                .ok_or_else(|| format!("The units of {self} and {other} are too large"))?;
+        }
+        Ok(dimensions)
+    }
+}
+
+// Rounded to 12 significant digits, which hides the errors of the conversions
+fn round(value: f64) -> f64 {
+    if value == 0.0 || !value.is_finite() {
+        return value;
+    }
+    let scale = 10f64.powi(12 - value.abs().log10().ceil() as i32);
+    let rounded = (value * scale).round() / scale;
+    if rounded.is_finite() {
+        rounded
+    } else {
+        value
+    }
+}
+
+impl Display for Quantity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Adding 0 turns -0 into 0
+        let value = round(self.value()) + 0.0;
+        match self.units.len {
+            0 => write!(f, "{value}"),
+            _ => write!(f, "{value} {}", self.units),
+        }
+    }
+}
+
+impl PartialEq for Quantity {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value && self.dimensions == other.dimensions
+    }
+}
+
+impl Number for Quantity {
+    fn from_litteral(value: T) -> Self {
+        Quantity::new(value as f64)
+    }
+
+    fn from_f64(value: f64) -> Result<Self, String> {
+        Ok(Quantity::new(value))
+    }
+
+    // Value in base units
+    fn to_f64(self) -> f64 {
+        self.value
+    }
+
+    fn add(self, other: Self) -> Result<Self, String> {
+        let (dimensions, units) =
+            self.same_dimensions(other, || format!("Cannot add {self} and {other}"))?;
+        Ok(Quantity::from_parts(
+            self.value + other.value,
+            dimensions,
+            Some(units),
+        ))
+    }
+
+    fn sub(self, other: Self) -> Result<Self, String> {
+        let (dimensions, units) =
+            self.same_dimensions(other, || format!("Cannot subtract {other} from {self}"))?;
+        Ok(Quantity::from_parts(
+            self.value - other.value,
+            dimensions,
+            Some(units),
+        ))
+    }
+
+    fn mul(self, other: Self) -> Result<Self, String> {
+        Ok(Quantity::from_parts(
+            self.value * other.value,
+            self.product_dimensions(other, 1)?,
+            self.units.mul(other.units, 1),
+        ))
+    }
+
+    fn div(self, other: Self) -> Result<Self, String> {
+        if other.value == 0.0 {
+            return Err(format!("Cannot divide {} by zero", self));
+        }
+
+        Ok(Quantity::from_parts(
+            self.value / other.value,
+            self.product_dimensions(other, -1)?,
+            self.units.mul(other.units, -1),
+        ))
+    }
+
+    fn rem(self, other: Self) -> Result<Self, String> {
+        let (dimensions, units) =
+            self.same_dimensions(other, || format!("Cannot compute {self} % {other}"))?;
+        if other.value == 0.0 {
+            return Err(format!("Cannot divide {} by zero", self));
+        }
+
+        Ok(Quantity::from_parts(
+            self.value % other.value,
+            dimensions,
+            Some(units),
+        ))
+    }
+
+    fn pow(self, other: Self) -> Result<Self, String> {
+        if !other.is_dimensionless() {
+            return Err(format!("Cannot raise {self} to {other}, which has a unit"));
+        }
+        if self.is_dimensionless() {
+            return Ok(Quantity::new(Number::pow(self.value, other.value)?));
+        }
+
+        let exponent = other.value;
+        if exponent.fract() != 0.0 || exponent.abs() > i8::MAX as f64 {
+            return Err(format!(
+                "Cannot raise {self} to the non integer power {other}"
+            ));
+        }
+        let exponent = exponent as i8;
+        let mut dimensions = self.dimensions;
+        for dimension in &mut dimensions {
+            *dimension = dimension
+                .checked_mul(exponent)
+                .ok_or_else(|| format!("The unit of {self}^{other} is too large"))?;
+        }
+        Ok(Quantity::from_parts(
+            self.value.powi(exponent.into()),
+            dimensions,
+            self.units.pow(exponent),
+        ))
+    }
+
+    fn constant(name: &str) -> Option<Self> {
+        Quantity::unit(name)
+    }
+
+    fn sqrt(self) -> Result<Self, String> {
+        if self.value < 0.0 {
+            return Err(format!("sqrt({self}) is not a real number"));
+        }
+        if self.dimensions.iter().any(|dimension| dimension % 2 != 0) {
+            return Err(format!("Cannot take the square root of the unit of {self}"));
+        }
+
+        let dimensions = self.dimensions.map(|dimension| dimension / 2);
+        let mut units = self.units;
+        let even = units.factors().iter().all(|(_, power)| power % 2 == 0);
+        for factor in &mut units.factors[..self.units.len] {
+            factor.1 /= 2;
+        }
+        Ok(Quantity::from_parts(
+            self.value.sqrt(),
+            dimensions,
+            even.then_some(units),
+        ))
+    }
+
+    fn ln(self) -> Result<Self, String> {
+        if !self.is_dimensionless() {
+            return Err(format!(
+                "Cannot take the logarithm of {self}, which has a unit"
+            ));
+        }
+
+        Ok(Quantity::new(Number::ln(self.value)?))
+    }
+
+    // In the units of the quantity, 1.234 km being rounded to 1.23 km
+    fn round(self, digits: i32) -> Result<Self, String> {
+        let value = Number::round(self.value(), digits)?;
+        Ok(Quantity {
+            value: value * self.units.scale(),
+            ..self
+        })
+    }
+
+    fn convert(self, unit: Self) -> Result<Self, String> {
+        if self.dimensions != unit.dimensions && !self.is_bare_zero() {
+            return Err(format!(
+                "Cannot convert {self} to {}, which have different dimensions",
+                unit.units
+            ));
+        }
+
+        Ok(Quantity {
+            value: self.value,
+            dimensions: unit.dimensions,
+            units: unit.units,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Expr, Session};
+    use std::collections::HashMap;
+
+    fn eval(input: &str) -> Result<String, String> {
+        let variables: HashMap<String, Quantity> = HashMap::new();
+        Ok(Expr::parse(input)?.eval_with(&variables)?.to_string())
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(eval("3 m + 20 cm").unwrap(), "3.2 m");
+        assert_eq!(eval("20 cm + 3 m").unwrap(), "320 cm");
+        assert_eq!(eval("9.81 m/s^2 * 2 s").unwrap(), "19.62 m/s");
+        assert_eq!(eval("5 kg * 3 m/s^2").unwrap(), "15 kg*m/s^2");
+        assert_eq!(eval("3 m * 20 cm").unwrap(), "0.6 m^2");
+        assert_eq!(eval("(6 m) / (2 m)").unwrap(), "3");
+        assert_eq!(eval("2 / (4 s)").unwrap(), "0.5 s^-1");
+        assert_eq!(eval("100 J / (2 s * 5 kg)").unwrap(), "10 J/(s*kg)");
+        assert_eq!(eval("-(2 h)").unwrap(), "-2 h");
+        // A unit binds like its number
+        assert_eq!(eval("10 m / 2 s").unwrap(), "5 m/s");
+        assert_eq!(eval("6 kg / 2 m^3").unwrap(), "3 kg/m^3");
+        assert_eq!(eval("1 / 2 s").unwrap(), "0.5 s^-1");
+        assert_eq!(eval("sqrt(16 m^2)").unwrap(), "4 m");
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(eval("5 kg * 3 m/s^2 to N").unwrap(), "15 N");
+        assert_eq!(eval("3 m + 20 cm in cm").unwrap(), "320 cm");
+        assert_eq!(eval("90 km/h to m/s").unwrap(), "25 m/s");
+        assert_eq!(eval("100 km / 2 h to m/s").unwrap(), "13.8888888889 m/s");
+        assert_eq!(eval("1 inch to mm").unwrap(), "25.4 mm");
+        assert_eq!(eval("2 kWh to J to kJ").unwrap(), "7200 kJ");
+        assert_eq!(eval("round(1234 m to km, 1)").unwrap(), "1.2 km");
+
+        let mut session = Session::<Quantity>::new();
+        session.run("g0 = 9.81 m/s^2; mass = 70 kg").unwrap();
+        assert_eq!(
+            session.run("mass * g0 to kN").unwrap().unwrap().to_string(),
+            "0.6867 kN"
+        );
+        // Lists are converted item by item
+        assert_eq!(
+            session
+                .run("[1, 2] * km to m")
+                .unwrap()
+                .unwrap()
+                .to_string(),
+            "[1000 m, 2000 m]"
+        );
+        assert_eq!(
+            Quantity::with_unit(2.5, "km").unwrap().to_string(),
+            "2.5 km"
+        );
+    }
+
+    #[test]
+    fn dimension_errors() {
+        assert_eq!(
+            eval("3 m + 2 s").unwrap_err(),
+            "Cannot add 3 m and 2 s, which have different dimensions"
+        );
+        assert_eq!(
+            eval("3 m - 2").unwrap_err(),
+            "Cannot subtract 2 from 3 m, which have different dimensions"
+        );
+        assert_eq!(
+            eval("3 m to s").unwrap_err(),
+            "Cannot convert 3 m to s, which have different dimensions"
+        );
+        assert_eq!(
+            eval("2^(3 s)").unwrap_err(),
+            "Cannot raise 2 to 3 s, which has a unit"
+        );
+        assert_eq!(
+            eval("ln(2 m)").unwrap_err(),
+            "Cannot take the logarithm of 2 m, which has a unit"
+        );
+        assert_eq!(eval("3 furlong").unwrap_err(), "Unknown variable furlong");
+
+        // Numbers have no units
+        assert_eq!(
+            Expr::parse("3 m to cm").unwrap().eval().unwrap_err(),
+            "Unknown variable m"
+        );
+        let variables = HashMap::from([("m".to_string(), 2.0), ("cm".to_string(), 1.0)]);
+        assert_eq!(
+            Expr::parse("3 m to cm").unwrap().eval_with(&variables),
+            Err("Cannot convert 6 to 1, evaluate it with quantities".to_string())
+        );
+    }
+}
@@ -0,0 +1,264 @@
+use super::number::Number;
+use super::T;
+use std::fmt::Display;
+
+const OVERFLOW: &str = "Integer overflow in exact arithmetic";
+const MAX_APPROXIMATION_DENOMINATOR: T = 1_000_000_000_000_000;
+
+// Exact fraction, always kept normalized: the denominator is positive and coprime with
+// the numerator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rational {
+    num: T,
+    den: T,
+}
+
+// Computed on the magnitudes, which fails for gcd(T::MIN, 0) = 2^127 only
+fn gcd(a: T, b: T) -> Result<T, String> {
+    let (mut a, mut b) = (a.unsigned_abs(), b.unsigned_abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    T::try_from(a).map_err(|_| OVERFLOW.to_string())
+}
+
+impl Rational {
+    pub const ZERO: Rational = Rational { num: 0, den: 1 };
+    pub const ONE: Rational = Rational { num: 1, den: 1 };
+
+    pub fn new(num: T, den: T) -> Result<Rational, String> {
+        if den == 0 {
+            return Err(format!("Cannot divide {} by zero", num));
+        }
+        if num == 0 {
+            return Ok(Rational::ZERO);
+        }
+
+        let divisor = gcd(num, den)?;
+        let sign = den.signum();
+        Ok(Rational {
+            num: num
+                .checked_div(divisor)
+                .and_then(|n| n.checked_mul(sign))
+                .ok_or(OVERFLOW)?,
+            den: (den / divisor).checked_mul(sign).ok_or(OVERFLOW)?,
+        })
+    }
+
+    pub fn integer(value: T) -> Rational {
+        Rational { num: value, den: 1 }
+    }
+
+    pub fn numerator(&self) -> T {
+        self.num
+    }
+
+    pub fn denominator(&self) -> T {
+        self.den
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.num == 0
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.den == 1
+    }
+
+    pub fn checked_neg(self) -> Result<Rational, String> {
+        Ok(Rational {
+            num: self.num.checked_neg().ok_or(OVERFLOW)?,
+            den: self.den,
+        })
+    }
+
+    pub fn recip(self) -> Result<Rational, String> {
+        Rational::new(self.den, self.num)
+    }
+
+    pub fn trunc(self) -> T {
+        self.num / self.den
+    }
+}
+
+impl Default for Rational {
+    fn default() -> Self {
+        Rational::ZERO
+    }
+}
+
+impl Display for Rational {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.den == 1 {
+            write!(f, "{}", self.num)
+        } else {
+            write!(f, "{}/{}", self.num, self.den)
+        }
+    }
+}
+
+impl Number for Rational {
+    fn from_litteral(value: T) -> Self {
+        Rational::integer(value)
+    }
+
+    // Closest fraction found by continued fractions
+    fn from_f64(value: f64) -> Result<Self, String> {
+        if !value.is_finite() {
+            return Err(format!("{value} is not a finite number"));
+        }
+
+        let (mut h0, mut h1): (T, T) = (0, 1);
+        let (mut k0, mut k1): (T, T) = (1, 0);
+        let mut x = value;
+        loop {
+            let a = x.floor();
+            if a.abs() > T::MAX as f64 / 2.0 {
+                return Err(OVERFLOW.to_string());
+            }
+            let a = a as T;
+
+            let h2 = a.checked_mul(h1).and_then(|h| h.checked_add(h0));
+            let k2 = a.checked_mul(k1).and_then(|k| k.checked_add(k0));
+            let (Some(h2), Some(k2)) = (h2, k2) else {
+                break;
+            };
+            if k2 > MAX_APPROXIMATION_DENOMINATOR {
+                break;
+            }
+            (h0, h1, k0, k1) = (h1, h2, k1, k2);
+
+            let approximation = h1 as f64 / k1 as f64;
+            if (approximation - value).abs() <= 1e-12 * value.abs().max(1.0) || x == a as f64 {
+                break;
+            }
+            x = 1.0 / (x - a as f64);
+        }
+
+        Rational::new(h1, k1)
+    }
+
+    fn to_f64(self) -> f64 {
+        self.num as f64 / self.den as f64
+    }
+
+    fn add(self, other: Self) -> Result<Self, String> {
+        let lcm = (self.den / gcd(self.den, other.den)?)
+            .checked_mul(other.den)
+            .ok_or(OVERFLOW)?;
+        let left = self.num.checked_mul(lcm / self.den).ok_or(OVERFLOW)?;
+        let right = other.num.checked_mul(lcm / other.den).ok_or(OVERFLOW)?;
+        Rational::new(left.checked_add(right).ok_or(OVERFLOW)?, lcm)
+    }
+
+    fn sub(self, other: Self) -> Result<Self, String> {
+        self.add(other.checked_neg()?)
+    }
+
+    fn mul(self, other: Self) -> Result<Self, String> {
+        // Cross reduction first to keep the intermediate values small
+        let a = gcd(self.num, other.den)?.max(1);
+        let b = gcd(other.num, self.den)?.max(1);
+        let num = (self.num / a).checked_mul(other.num / b).ok_or(OVERFLOW)?;
+        let den = (self.den / b).checked_mul(other.den / a).ok_or(OVERFLOW)?;
+        Rational::new(num, den)
+    }
+
+    fn div(self, other: Self) -> Result<Self, String> {
+        if other.is_zero() {
+            return Err(format!("Cannot divide {} by zero", self));
+        }
+
+        self.mul(other.recip()?)
+    }
+
+    fn rem(self, other: Self) -> Result<Self, String> {
+        if other.is_zero() {
+            return Err(format!("Cannot divide {} by zero", self));
+        }
+
+        let quotient = Rational::integer(self.div(other)?.trunc());
+        self.sub(quotient.mul(other)?)
+    }
+
+    fn pow(self, other: Self) -> Result<Self, String> {
+        if self.is_zero() && other.is_zero() {
+            return Err("0^0 is undefined".to_string());
+        }
+
+        if !other.is_integer() {
+            return Err(format!(
+                "Cannot raise {self} to the non integer power {other}"
+            ));
+        }
+
+        let exponent = u32::try_from(other.num.unsigned_abs()).map_err(|_| OVERFLOW)?;
+        let result = Rational::new(
+            self.num.checked_pow(exponent).ok_or(OVERFLOW)?,
+            self.den.checked_pow(exponent).ok_or(OVERFLOW)?,
+        )?;
+
+        if other.num < 0 {
+            if result.is_zero() {
+                return Err(format!("Cannot raise {self} to a negative power"));
+            }
+            return result.recip();
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn r(num: T, den: T) -> Rational {
+        Rational::new(num, den).unwrap()
+    }
+
+    #[test]
+    fn normalization() {
+        assert_eq!(r(2, 4), r(1, 2));
+        assert_eq!(r(3, -6), r(-1, 2));
+        assert_eq!(r(0, -5), Rational::ZERO);
+        assert!(Rational::new(1, 0).is_err());
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(r(1, 2).add(r(1, 3)).unwrap(), r(5, 6));
+        assert_eq!(r(1, 2).sub(r(1, 3)).unwrap(), r(1, 6));
+        assert_eq!(r(2, 3).mul(r(9, 4)).unwrap(), r(3, 2));
+        assert_eq!(r(2, 3).div(r(4, 3)).unwrap(), r(1, 2));
+        assert_eq!(r(7, 2).rem(r(1, 1)).unwrap(), r(1, 2));
+        assert_eq!(r(2, 3).pow(r(-2, 1)).unwrap(), r(9, 4));
+        assert!(r(2, 1).pow(r(1, 2)).is_err());
+        assert!(r(1, 1).div(Rational::ZERO).is_err());
+    }
+
+    #[test]
+    fn overflow() {
+        assert!(Rational::integer(T::MAX).add(Rational::ONE).is_err());
+        assert_eq!(Rational::new(0, T::MIN), Ok(Rational::ZERO));
+        assert_eq!(Rational::new(T::MIN, 2), Ok(r(T::MIN / 2, 1)));
+        assert!(Rational::new(1, T::MIN).is_err());
+        assert!(Rational::new(T::MIN, T::MIN).is_err());
+        assert!(Rational::integer(T::MIN)
+            .mul(Rational::integer(-1))
+            .is_err());
+    }
+
+    #[test]
+    fn from_f64() {
+        assert_eq!(Rational::from_f64(0.75).unwrap(), r(3, 4));
+        assert_eq!(Rational::from_f64(-2.0).unwrap(), r(-2, 1));
+        assert_eq!(Rational::from_f64(1.0 / 3.0).unwrap(), r(1, 3));
+        assert_eq!(Rational::from_f64(12.000000000001).unwrap(), r(12, 1));
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(r(6, 3).to_string(), "2");
+        assert_eq!(r(-1, 3).to_string(), "-1/3");
+    }
+}
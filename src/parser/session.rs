@@ -0,0 +1,222 @@
+use super::config::ParserConfig;
+use super::expr::Expr;
+use super::functions::{Function, Functions};
+use super::number::Number;
+use super::value::Value;
+use super::T;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+pub enum Statement {
+    Assignment(String, Expr),
+    Definition(Function),
+    Expr(Expr),
+}
+
+// Runs statements such as `x = 3; y = x^2 + 1; y * 2` or `f(x, y) = x^2 + y^2`, keeping
+// the assigned variables and the defined functions from one run to the next.
+#[derive(Debug, Clone, Default)]
+pub struct Session<N: Number = T> {
+    config: ParserConfig,
+    variables: HashMap<String, Value<N>>,
+    functions: Functions,
+}
+
+impl<N: Number> Session<N> {
+    pub fn new() -> Self {
+        Session {
+            config: ParserConfig::default(),
+            variables: HashMap::new(),
+            functions: Functions::new(),
+        }
+    }
+
+    pub fn with_config(config: ParserConfig) -> Self {
+        Session {
+            config,
+            variables: HashMap::new(),
+            functions: Functions::new(),
+        }
+    }
+
+    pub fn config(&self) -> &ParserConfig {
+        &self.config
+    }
+
+    pub fn variables(&self) -> &HashMap<String, Value<N>> {
+        &self.variables
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Value<N>> {
+        self.variables.get(name)
+    }
+
+    // Sorted by name
+    pub fn functions(&self) -> impl Iterator<Item = &Function> {
+        self.functions.values()
+    }
+
+    pub fn function(&self, name: &str) -> Option<&Function> {
+        self.functions.get(name)
+    }
+
+    pub fn set(&mut self, name: &str, value: impl Into<Value<N>>) {
+        self.variables.insert(name.to_string(), value.into());
+    }
+
+    pub fn clear(&mut self) {
+        self.variables.clear();
+        self.functions.clear();
+    }
+
+    // Value of the last statement, the assigned value for an assignment and nothing for
+    // a definition. None of the statements are kept if one of them fails.
+    pub fn run(&mut self, input: &str) -> Result<Option<Value<N>>, String> {
+        let statements = self.config.parse_statements(input)?;
+        let mut variables = self.variables.clone();
+        let mut functions = self.functions.clone();
+        let mut last = None;
+        for statement in statements {
+            let limits = &self.config.limits;
+            last = match statement {
+                Statement::Assignment(name, expr) => {
+                    let value = expr.eval_value_with_functions(&variables, &functions, limits)?;
+                    variables.insert(name, value.clone());
+                    Some(value)
+                }
+                Statement::Definition(function) => {
+                    functions.insert(function.name().to_string(), function);
+                    None
+                }
+                Statement::Expr(expr) => {
+                    Some(expr.eval_value_with_functions(&variables, &functions, limits)?)
+                }
+            };
+        }
+
+        self.variables = variables;
+        self.functions = functions;
+        Ok(last)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn number(value: T) -> Result<Option<Value<T>>, String> {
+        Ok(Some(Value::Number(value)))
+    }
+
+    #[test]
+    fn statements() {
+        let mut session = Session::<T>::new();
+        assert_eq!(session.run("x = 3; y = x^2 + 1; y * 2"), number(20));
+        assert_eq!(session.get("y"), Some(&Value::Number(10)));
+        assert_eq!(session.run("z = -x;"), number(-3));
+    }
+
+    #[test]
+    fn variables_are_kept() {
+        let mut session = Session::<T>::new();
+        session.run("rate = 5").unwrap();
+        session.run("price = 40").unwrap();
+        assert_eq!(session.run("price * rate / 100"), number(2));
+
+        session.clear();
+        assert!(session.run("price").is_err());
+    }
+
+    #[test]
+    fn errors() {
+        let mut session = Session::<T>::new();
+        assert!(session.run("x = 1; y = 1 / 0").is_err());
+        assert_eq!(session.get("x"), None);
+
+        assert_eq!(
+            session.run("1 + 2 = 3").unwrap_err(),
+            "Expected an operator before '='"
+        );
+        assert_eq!(session.run("x = ").unwrap_err(), "Expected an expression");
+        assert_eq!(session.run(" ; ").unwrap_err(), "Expected an expression");
+        assert!(Expr::parse("x = 3").is_err());
+        assert!(Expr::parse("1; 2").is_err());
+    }
+
+    #[test]
+    fn functions() {
+        let mut session = Session::<T>::new();
+        assert_eq!(session.run("f(x, y) = x^2 + y^2"), Ok(None));
+        assert_eq!(session.run("f(3, 4)"), number(25));
+        assert_eq!(session.run("g() = 7; h(t) = f(t, g()) - t"), Ok(None));
+        assert_eq!(session.run("h(1)"), number(49));
+
+        let names: Vec<String> = session.functions().map(|f| f.to_string()).collect();
+        assert_eq!(
+            names,
+            vec!["f(x, y) = x ^ 2 + y ^ 2", "g() = 7", "h(t) = f(t, g()) - t"]
+        );
+    }
+
+    #[test]
+    fn function_scopes() {
+        let mut session = Session::<T>::new();
+        session.run("x = 10; y = 100").unwrap();
+        session.run("f(x) = x + y").unwrap();
+        // The parameter of g can't be seen from f
+        session.run("g(y) = f(y)").unwrap();
+        assert_eq!(session.run("f(1)"), number(101));
+        assert_eq!(session.run("g(2)"), number(102));
+        assert_eq!(session.get("x"), Some(&Value::Number(10)));
+
+        session
+            .run("d(x) = diff(x^2, x, x) + integrate(x, x, 0, 2)")
+            .unwrap();
+        assert_eq!(session.run("d(3)"), number(8));
+
+        // The parameter of a lambda shadows the one of the function
+        session
+            .run("s(x, k) = map(x -> x * k, [x + 1])[0]")
+            .unwrap();
+        assert_eq!(session.run("s(3, 2)"), number(8));
+        session.run("t(x) = map(k -> k + x, [x])[0]").unwrap();
+        assert_eq!(session.run("t(4)"), number(8));
+
+        // The variables bound by the callers can't be seen from the body either
+        session.run("u(x) = x + i").unwrap();
+        let unknown = Err("Unknown variable i".to_string());
+        assert_eq!(session.run("u(0)"), unknown);
+        assert_eq!(session.run("sum(i, 1, 3, u(0))"), unknown);
+        assert_eq!(session.run("map(i -> u(0), [5])"), unknown);
+        assert_eq!(session.run("integrate(u(0), i, 0, 1)"), unknown);
+        assert_eq!(
+            session.run("sum(i, 1, 3, integrate(u(x), x, 0, 1))"),
+            unknown
+        );
+        session.run("i = 1").unwrap();
+        assert_eq!(session.run("sum(i, 1, 3, u(0))"), number(3));
+        assert_eq!(session.run("map(i -> u(i), [5])[0]"), number(6));
+    }
+
+    #[test]
+    fn function_errors() {
+        let mut session = Session::<T>::new();
+        session.run("f(x, y) = x * y").unwrap();
+        assert_eq!(
+            session.run("f(1)").unwrap_err(),
+            "f expects 2 arguments (x, y), got 1"
+        );
+        assert_eq!(
+            session.run("r(n) = r(n - 1) + 1; r(1)").unwrap_err(),
+            "Too many nested calls in r (the limit is 100)"
+        );
+        assert!(session.function("r").is_none());
+
+        assert!(session.run("f(x, x) = x").is_err());
+        assert!(session.run("f(1) = 2").is_err());
+        assert!(session.run("f(x = 2").is_err());
+        assert!(session.run("diff(x) = 2").is_err());
+        assert!(session.run("k(x) = ").is_err());
+        assert!(Expr::parse("f(1)").unwrap().eval().is_err());
+    }
+}
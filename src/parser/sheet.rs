@@ -0,0 +1,286 @@
+use super::formulas::FormulaSet;
+use super::number::Number;
+use std::collections::{BTreeSet, HashMap};
+
+type Listener<N> = Box<dyn FnMut(&str, &Result<N, String>)>;
+
+// Formula set keeping the value of every formula, and recomputing only the formulas
+// affected by a change: when an input or a formula changes, the formulas depending on it
+// are marked dirty, and re-evaluated in dependency order. A formula whose value didn't
+// change doesn't mark its own dependents.
+pub struct Sheet<N: Number> {
+    formulas: FormulaSet,
+    order: Vec<String>,
+    // Formulas referred to by each formula, and formulas referring to each name
+    dependencies: HashMap<String, BTreeSet<String>>,
+    dependents: HashMap<String, BTreeSet<String>>,
+    inputs: HashMap<String, N>,
+    // Inputs and values of the formulas without errors, given to the evaluation
+    variables: HashMap<String, N>,
+    values: HashMap<String, Result<N, String>>,
+    dirty: BTreeSet<String>,
+    listeners: Vec<Listener<N>>,
+}
+
+impl<N: Number> Sheet<N> {
+    pub fn new(formulas: FormulaSet, inputs: HashMap<String, N>) -> Result<Self, String> {
+        let mut sheet = Sheet {
+            dirty: formulas.names().map(|name| name.to_string()).collect(),
+            formulas,
+            order: vec![],
+            dependencies: HashMap::new(),
+            dependents: HashMap::new(),
+            variables: inputs.clone(),
+            inputs,
+            values: HashMap::new(),
+            listeners: vec![],
+        };
+        sheet.rebuild()?;
+        sheet.recompute();
+        Ok(sheet)
+    }
+
+    // Called with the name and the new value of every formula whose value changes
+    pub fn on_change(&mut self, listener: impl FnMut(&str, &Result<N, String>) + 'static) {
+        self.listeners.push(Box::new(listener));
+    }
+
+    pub fn value(&self, name: &str) -> Option<&Result<N, String>> {
+        self.values.get(name)
+    }
+
+    pub fn formulas(&self) -> &FormulaSet {
+        &self.formulas
+    }
+
+    // The following methods return the formulas they re-evaluated, in evaluation order
+
+    pub fn set_input(&mut self, name: &str, value: N) -> Vec<String> {
+        if self.inputs.get(name) == Some(&value) {
+            return vec![];
+        }
+
+        self.inputs.insert(name.to_string(), value);
+        if self.formulas.get(name).is_none() {
+            self.variables.insert(name.to_string(), value);
+            self.mark_dependents(name);
+        }
+        self.recompute()
+    }
+
+    // Leaves the sheet unchanged on error (invalid formula, circular reference)
+    pub fn set_formula(&mut self, name: &str, input: &str) -> Result<Vec<String>, String> {
+        let previous = self.formulas.get(name).cloned();
+        self.formulas.insert(name, input)?;
+        if let Err(e) = self.rebuild() {
+            match previous {
+                Some(expr) => self.formulas.insert_expr(name, expr),
+                None => {
+                    self.formulas.remove(name);
+                }
+            }
+            self.rebuild()?;
+            return Err(e);
+        }
+
+        self.dirty.insert(name.to_string());
+        Ok(self.recompute())
+    }
+
+    pub fn remove_formula(&mut self, name: &str) -> Vec<String> {
+        if self.formulas.remove(name).is_none() {
+            return vec![];
+        }
+        // Removing a formula can't create a cycle
+        let _ = self.rebuild();
+
+        self.values.remove(name);
+        match self.inputs.get(name) {
+            Some(value) => self.variables.insert(name.to_string(), *value),
+            None => self.variables.remove(name),
+        };
+        self.mark_dependents(name);
+        self.recompute()
+    }
+
+    fn rebuild(&mut self) -> Result<(), String> {
+        self.order = self.formulas.order()?;
+        self.dependencies.clear();
+        self.dependents.clear();
+        for name in self.formulas.names() {
+            let expr = self.formulas.get(name).unwrap();
+            for variable in expr.free_variables() {
+                self.dependents
+                    .entry(variable)
+                    .or_default()
+                    .insert(name.to_string());
+            }
+            self.dependencies
+                .insert(name.to_string(), self.formulas.dependencies(name));
+        }
+        Ok(())
+    }
+
+    fn mark_dependents(&mut self, name: &str) {
+        if let Some(dependents) = self.dependents.get(name) {
+            self.dirty.extend(dependents.iter().cloned());
+        }
+    }
+
+    fn recompute(&mut self) -> Vec<String> {
+        let mut evaluated = vec![];
+        for i in 0..self.order.len() {
+            if !self.dirty.remove(&self.order[i]) {
+                continue;
+            }
+            let name = self.order[i].clone();
+
+            let failed = self.dependencies[&name]
+                .iter()
+                .find(|dependency| matches!(self.values.get(*dependency), Some(Err(_))));
+            let value = match failed {
+                Some(dependency) => Err(format!(
+                    "{name}: depends on {dependency}, which has an error"
+                )),
+                None => self
+                    .formulas
+                    .get(&name)
+                    .unwrap()
+                    .eval_with(&self.variables)
+                    .map_err(|e| format!("{name}: {e}")),
+            };
+            evaluated.push(name.clone());
+
+            if self.values.get(&name) == Some(&value) {
+                continue;
+            }
+            match value {
+                Ok(value) => self.variables.insert(name.clone(), value),
+                Err(_) => self.variables.remove(&name),
+            };
+            self.mark_dependents(&name);
+            for listener in &mut self.listeners {
+                listener(&name, &value);
+            }
+            self.values.insert(name, value);
+        }
+        evaluated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::T;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn invoice() -> Sheet<T> {
+        let mut formulas = FormulaSet::new();
+        formulas
+            .insert("total", "subtotal + tax - discount")
+            .unwrap();
+        formulas.insert("tax", "subtotal / 5").unwrap();
+        formulas.insert("subtotal", "price * quantity").unwrap();
+        formulas.insert("thousands", "subtotal / 1000").unwrap();
+        formulas.insert("report", "thousands * 1000").unwrap();
+
+        let inputs = [("price", 25), ("quantity", 4), ("discount", 0)]
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value))
+            .collect();
+        Sheet::new(formulas, inputs).unwrap()
+    }
+
+    #[test]
+    fn initial_values() {
+        let sheet = invoice();
+        assert_eq!(sheet.value("total"), Some(&Ok(120)));
+        assert_eq!(sheet.value("report"), Some(&Ok(0)));
+        assert_eq!(sheet.value("price"), None);
+    }
+
+    #[test]
+    fn only_dependents_are_recomputed() {
+        let mut sheet = invoice();
+        assert_eq!(sheet.set_input("discount", 10), vec!["total"]);
+        assert_eq!(sheet.value("total"), Some(&Ok(110)));
+
+        assert_eq!(sheet.set_input("discount", 10), Vec::<String>::new());
+
+        // `thousands` stays at 0, so `report` isn't recomputed
+        assert_eq!(
+            sheet.set_input("price", 50),
+            vec!["subtotal", "thousands", "tax", "total"]
+        );
+        assert_eq!(sheet.value("total"), Some(&Ok(230)));
+    }
+
+    #[test]
+    fn formulas_changes() {
+        let mut sheet = invoice();
+        assert_eq!(
+            sheet.set_formula("tax", "subtotal / 10").unwrap(),
+            vec!["tax", "total"]
+        );
+        assert_eq!(sheet.value("total"), Some(&Ok(110)));
+
+        assert_eq!(
+            sheet.set_formula("subtotal", "total * 2").unwrap_err(),
+            "Circular reference: subtotal -> total -> subtotal"
+        );
+        assert_eq!(
+            sheet.set_input("price", 50),
+            vec!["subtotal", "thousands", "tax", "total"]
+        );
+
+        sheet.set_input("tax", 1);
+        assert_eq!(sheet.remove_formula("tax"), vec!["total"]);
+        assert_eq!(sheet.value("total"), Some(&Ok(201)));
+    }
+
+    #[test]
+    fn errors() {
+        let mut sheet = invoice();
+        sheet.set_input("quantity", 0);
+        sheet.set_formula("ratio", "price / quantity").unwrap();
+        sheet.set_formula("double", "ratio * 2").unwrap();
+        assert_eq!(
+            sheet.value("ratio"),
+            Some(&Err("ratio: Cannot divide 25 by zero".to_string()))
+        );
+        assert_eq!(
+            sheet.value("double"),
+            Some(&Err(
+                "double: depends on ratio, which has an error".to_string()
+            ))
+        );
+
+        sheet.set_input("quantity", 5);
+        assert_eq!(sheet.value("double"), Some(&Ok(10)));
+    }
+
+    #[test]
+    fn listeners() {
+        let mut sheet = invoice();
+        let changes = Rc::new(RefCell::new(vec![]));
+        let recorded = changes.clone();
+        sheet.on_change(move |name, value| {
+            recorded
+                .borrow_mut()
+                .push((name.to_string(), value.clone()))
+        });
+
+        sheet.set_input("discount", 20);
+        sheet.set_input("quantity", 2);
+        assert_eq!(
+            *changes.borrow(),
+            vec![
+                ("total".to_string(), Ok(100)),
+                ("subtotal".to_string(), Ok(50)),
+                ("tax".to_string(), Ok(10)),
+                ("total".to_string(), Ok(40)),
+            ]
+        );
+    }
+}
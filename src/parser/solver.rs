@@ -0,0 +1,283 @@
+use super::expr::Expr;
+use super::polynomial::Polynomial;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Display;
+
+pub const SOLVE_RANGE: (f64, f64) = (-100.0, 100.0);
+const SOLVE_SAMPLES: usize = 10_000;
+const ROOT_TOLERANCE: f64 = 1e-12;
+
+pub struct Equation {
+    pub left: Expr,
+    pub right: Expr,
+}
+
+impl Equation {
+    pub fn parse(input: &str) -> Result<Equation, String> {
+        let sides: Vec<&str> = input.split('=').collect();
+        let [left, right] = sides[..] else {
+            return Err(format!("Expected a single '=' in the equation {input}"));
+        };
+
+        Ok(Equation {
+            left: Expr::parse(left)?,
+            right: Expr::parse(right)?,
+        })
+    }
+
+    pub fn unknowns(&self) -> BTreeSet<String> {
+        let mut unknowns = self.left.free_variables();
+        unknowns.extend(self.right.free_variables());
+        unknowns
+    }
+
+    // left - right, as a function of `var`
+    fn residual(&self, var: &str, x: f64) -> Result<f64, String> {
+        let mut variables = HashMap::new();
+        variables.insert(var.to_string(), x);
+        Ok(self.left.eval_with(&variables)? - self.right.eval_with(&variables)?)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Solution {
+    pub variable: String,
+    pub roots: Vec<f64>,
+}
+
+impl Display for Solution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.roots.is_empty() {
+            return write!(f, "No solution for {}", self.variable);
+        }
+
+        for (i, root) in self.roots.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{} = {}", self.variable, root)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn solve(input: &str) -> Result<Solution, String> {
+    solve_in(input, SOLVE_RANGE.0, SOLVE_RANGE.1)
+}
+
+// Solves an equation in a single unknown. Polynomial equations are solved through their
+// factorization, other ones numerically by looking for roots in [min, max].
+pub fn solve_in(input: &str, min: f64, max: f64) -> Result<Solution, String> {
+    let equation = Equation::parse(input)?;
+    let unknowns = equation.unknowns();
+    if unknowns.len() != 1 {
+        return Err(format!(
+            "Expected a single unknown, found {}",
+            unknowns.len()
+        ));
+    }
+    let variable = unknowns.into_iter().next().unwrap();
+
+    let polynomial = Polynomial::from_expr_in(&equation.left, &variable)
+        .and_then(|left| left.sub(&Polynomial::from_expr_in(&equation.right, &variable)?));
+
+    let mut roots = match polynomial {
+        Ok(polynomial) if polynomial.is_zero() => {
+            return Err(format!("Every value of {variable} is a solution"))
+        }
+        Ok(polynomial) => polynomial.roots()?,
+        Err(_) => numeric_roots(&equation, &variable, min, max)?,
+    };
+
+    roots.iter_mut().for_each(|root| *root = clean(*root));
+    roots.sort_by(|a, b| a.total_cmp(b));
+    roots.dedup_by(|a, b| (*a - *b).abs() <= 1e-9 * b.abs().max(1.0));
+
+    Ok(Solution { variable, roots })
+}
+
+fn numeric_roots(equation: &Equation, var: &str, min: f64, max: f64) -> Result<Vec<f64>, String> {
+    if min >= max || !min.is_finite() || !max.is_finite() {
+        return Err(format!("Invalid range [{min}, {max}]"));
+    }
+
+    let f = |x: f64| equation.residual(var, x);
+    let step = (max - min) / SOLVE_SAMPLES as f64;
+    let mut roots = vec![];
+    let mut previous: Option<(f64, f64)> = None;
+    let (mut evaluated, mut zeros) = (0, 0);
+
+    for i in 0..=SOLVE_SAMPLES {
+        let x = min + step * i as f64;
+        // Points where the equation can't be evaluated split the range
+        let Ok(y) = f(x) else {
+            previous = None;
+            continue;
+        };
+
+        evaluated += 1;
+        if y == 0.0 {
+            zeros += 1;
+            roots.push(x);
+        } else if let Some((px, py)) = previous {
+            if py != 0.0 && py.signum() != y.signum() {
+                if let Some(root) = brent(&f, px, x, py, y) {
+                    // Discards poles, where the sign changes without crossing zero
+                    if f(root).map(|r| r.abs() < 1e-6).unwrap_or(false) {
+                        roots.push(root);
+                    }
+                }
+            }
+        }
+        previous = Some((x, y));
+    }
+
+    // Identities such as x/x = 1, which may only be undefined at a few points
+    if evaluated > 0 && zeros == evaluated {
+        return Err(format!("Every value of {var} is a solution"));
+    }
+
+    Ok(roots)
+}
+
+// Brent's method on [a, b], with f(a) and f(b) of opposite signs
+fn brent(
+    f: &impl Fn(f64) -> Result<f64, String>,
+    mut a: f64,
+    mut b: f64,
+    mut fa: f64,
+    mut fb: f64,
+) -> Option<f64> {
+    if fa.abs() < fb.abs() {
+        std::mem::swap(&mut a, &mut b);
+        std::mem::swap(&mut fa, &mut fb);
+    }
+    let (mut c, mut fc) = (a, fa);
+    let mut d = b - a;
+    let mut bisected = true;
+
+    for _ in 0..200 {
+        if fb == 0.0 || (b - a).abs() < ROOT_TOLERANCE * b.abs().max(1.0) {
+            return Some(b);
+        }
+
+        let mut s = if fa != fc && fb != fc {
+            // Inverse quadratic interpolation
+            a * fb * fc / ((fa - fb) * (fa - fc))
+                + b * fa * fc / ((fb - fa) * (fb - fc))
+                + c * fa * fb / ((fc - fa) * (fc - fb))
+        } else {
+            // Secant
+            b - fb * (b - a) / (fb - fa)
+        };
+
+        let lower = (3.0 * a + b) / 4.0;
+        let out_of_bounds = !((lower < s && s < b) || (b < s && s < lower));
+        let slow = if bisected {
+            (s - b).abs() >= (b - c).abs() / 2.0
+        } else {
+            (s - b).abs() >= (c - d).abs() / 2.0
+        };
+        bisected = out_of_bounds || slow;
+        if bisected {
+            s = (a + b) / 2.0;
+        }
+
+        let fs = f(s).ok()?;
+        d = c;
+        c = b;
+        fc = fb;
+        if fa.signum() != fs.signum() {
+            b = s;
+            fb = fs;
+        } else {
+            a = s;
+            fa = fs;
+        }
+
+        if fa.abs() < fb.abs() {
+            std::mem::swap(&mut a, &mut b);
+            std::mem::swap(&mut fa, &mut fb);
+        }
+    }
+
+    Some(b)
+}
+
+// Removes the floating point noise around integers, and negative zeros
+fn clean(root: f64) -> f64 {
+    if (root - root.round()).abs() < 1e-9 {
+        return root.round() + 0.0;
+    }
+    root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roots(input: &str) -> Vec<f64> {
+        solve(input).unwrap().roots
+    }
+
+    #[test]
+    fn linear() {
+        assert_eq!(roots("2x + 3 = 11"), vec![4.0]);
+        assert_eq!(roots("3 = 2(y - 1)"), vec![2.5]);
+    }
+
+    #[test]
+    fn quadratic() {
+        let r = roots("x^2 - 2 = 0");
+        assert_eq!(r.len(), 2);
+        assert!((r[0] + 2f64.sqrt()).abs() < 1e-12);
+        assert!((r[1] - 2f64.sqrt()).abs() < 1e-12);
+
+        assert_eq!(roots("(x - 3)(x + 1) = 0"), vec![-1.0, 3.0]);
+        assert_eq!(roots("x^2 + 1 = 0"), vec![]);
+        assert_eq!(roots("x^2 = 2x - 1"), vec![1.0]);
+    }
+
+    #[test]
+    fn numeric() {
+        assert_eq!(roots("x^3 - 6x^2 + 11x - 6 = 0"), vec![1.0, 2.0, 3.0]);
+        assert_eq!(roots("integrate(t, t, 0, x) = 2"), vec![-2.0, 2.0]);
+        assert_eq!(roots("1/x = 0"), vec![]);
+    }
+
+    #[test]
+    fn numeric_in_range() {
+        let solution = solve_in("integrate(t, t, 0, x) = 2", 0.5, 10.0).unwrap();
+        assert_eq!(solution.roots, vec![2.0]);
+    }
+
+    #[test]
+    fn polynomial() {
+        assert_eq!(roots("x^4 = 5x^2 - 4"), vec![-2.0, -1.0, 1.0, 2.0]);
+        assert_eq!(roots("x^3 = 1000000"), vec![100.0]);
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(solve("2x = 8").unwrap().to_string(), "x = 4");
+        assert_eq!(solve("x^2 = 4").unwrap().to_string(), "x = -2, x = 2");
+        assert_eq!(solve("0x = 1").unwrap().to_string(), "No solution for x");
+    }
+
+    #[test]
+    fn errors() {
+        assert!(solve("x = x").is_err());
+        assert_eq!(
+            solve("x/x = 1").unwrap_err(),
+            "Every value of x is a solution"
+        );
+        assert_eq!(
+            solve("abs(x)^2 = x^2").unwrap_err(),
+            "Every value of x is a solution"
+        );
+        assert!(solve("x + y = 1").is_err());
+        assert!(solve("1 = 1").is_err());
+        assert!(solve("x = 1 = 2").is_err());
+        assert!(solve("x + 1").is_err());
+    }
+}
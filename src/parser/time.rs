@@ -0,0 +1,551 @@
+use super::number::Number;
+use super::T;
+use std::cell::Cell;
+use std::fmt::Display;
+
+const DAY: f64 = 86400.0;
+
+// Supported years, from -9999-01-01 to 9999-12-31
+const MAX_YEAR: i32 = 9999;
+
+// Units of the durations in seconds, `3h 20min` being `3h + 20min`
+pub(super) const DURATION_UNITS: [(&str, f64); 7] = [
+    ("s", 1.0),
+    ("min", 60.0),
+    ("h", 3600.0),
+    ("day", DAY),
+    ("days", DAY),
+    ("week", 7.0 * DAY),
+    ("weeks", 7.0 * DAY),
+];
+
+thread_local! {
+    // Date of `today()` in the evaluations running in `Calendar::scope`
+    static TODAY: Cell<Option<Date>> = const { Cell::new(None) };
+}
+
+// Day of the proleptic Gregorian calendar
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date {
+    year: i32,
+    month: u32,
+    day: u32,
+}
+
+impl Date {
+    pub fn new(year: i32, month: u32, day: u32) -> Result<Date, String> {
+        let date = Date { year, month, day };
+        if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+            return Err(format!("{date} is not a valid date"));
+        }
+        if year.abs() > MAX_YEAR {
+            return Err(out_of_range(&date));
+        }
+
+        Ok(date)
+    }
+
+    pub fn year(&self) -> i32 {
+        self.year
+    }
+
+    pub fn month(&self) -> u32 {
+        self.month
+    }
+
+    pub fn day(&self) -> u32 {
+        self.day
+    }
+
+    // Days since 1970-01-01
+    pub fn days(&self) -> i64 {
+        let year = self.year as i64 - i64::from(self.month <= 2);
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        // Days since the 1st of March, the leap day being the last one of the year
+        let day_of_year = (153 * ((self.month as i64 + 9) % 12) + 2) / 5 + self.day as i64 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146097 + day_of_era - 719468
+    }
+
+    pub fn from_days(days: i64) -> Result<Date, String> {
+        let first = Date::new(-MAX_YEAR, 1, 1)?;
+        let last = Date::new(MAX_YEAR, 12, 31)?;
+        if !(first.days()..=last.days()).contains(&days) {
+            return Err(out_of_range(&"The date"));
+        }
+
+        let days = days + 719468;
+        let era = days.div_euclid(146097);
+        let day_of_era = days - era * 146097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * month + 2) / 5 + 1) as u32;
+        let month = if month < 10 { month + 3 } else { month - 9 } as u32;
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+        Ok(Date {
+            year: year as i32,
+            month,
+            day,
+        })
+    }
+
+    // ISO weekday, from 1 for Monday to 7 for Sunday
+    pub fn weekday(&self) -> u32 {
+        // 1970-01-01 was a Thursday
+        (self.days() + 3).rem_euclid(7) as u32 + 1
+    }
+
+    // Same day `months` months later, or the last day of the month if it is shorter, so
+    // that a month after 2026-01-31 is 2026-02-28
+    pub fn add_months(&self, months: i32) -> Result<Date, String> {
+        let months = self.year as i64 * 12 + self.month as i64 - 1 + months as i64;
+        let (year, month) = (
+            months.div_euclid(12) as i32,
+            months.rem_euclid(12) as u32 + 1,
+        );
+        Date::new(year, month, self.day.min(days_in_month(year, month)))
+    }
+}
+
+impl Display for Date {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+fn out_of_range(date: &dyn Display) -> String {
+    format!("{date} is out of range, dates go from -9999-01-01 to 9999-12-31")
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Source of the date of `today()`, the system clock (in UTC) unless an evaluation runs in
+// the scope of a calendar with a fixed date
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Calendar {
+    today: Date,
+}
+
+impl Calendar {
+    pub fn new(today: Date) -> Calendar {
+        Calendar { today }
+    }
+
+    pub fn today() -> Result<Date, String> {
+        match TODAY.with(Cell::get) {
+            Some(today) => Ok(today),
+            None => system_today(),
+        }
+    }
+
+    // Runs `f` with this date as `today()`
+    pub fn scope<R>(&self, f: impl FnOnce() -> R) -> R {
+        let previous = TODAY.with(|today| today.replace(Some(self.today)));
+        let result = f();
+        TODAY.with(|today| today.set(previous));
+        result
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn system_today() -> Result<Date, String> {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|_| "The system clock is before 1970".to_string())?;
+    Date::from_days((now.as_secs() / 86400) as i64)
+}
+
+// There is no system clock in the browser, the application gives the date
+#[cfg(target_arch = "wasm32")]
+fn system_today() -> Result<Date, String> {
+    Err("The date of today() is unknown, run the evaluation in Calendar::scope".to_string())
+}
+
+// Number, date or duration. Dates and durations are counted in seconds, dates since
+// 1970-01-01 00:00, so that `2026-12-25 - 2026-10-18` is a duration of 68 days and a
+// date plus some hours is a date and time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Time {
+    Number(f64),
+    Date(f64),
+    Duration(f64),
+}
+
+impl Time {
+    pub fn date(date: Date) -> Time {
+        Time::Date(date.days() as f64 * DAY)
+    }
+
+    // Day of a date, without its time
+    fn day(seconds: f64) -> Result<Date, String> {
+        Date::from_days((seconds / DAY).floor() as i64)
+    }
+
+    fn is_zero(&self) -> bool {
+        *self == Time::Number(0.0)
+    }
+
+    fn check(self) -> Result<Time, String> {
+        let (Time::Number(value) | Time::Date(value) | Time::Duration(value)) = self;
+        if !value.is_finite() {
+            return Err(format!("{value} is not a finite number"));
+        }
+        if let Time::Date(seconds) = self {
+            Time::day(seconds)?;
+        }
+
+        Ok(self)
+    }
+}
+
+impl Display for Time {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Time::Number(value) => write!(f, "{value}"),
+            Time::Date(seconds) => {
+                let seconds = seconds.round();
+                match Time::day(seconds) {
+                    Ok(day) => write!(f, "{day}")?,
+                    Err(_) => return write!(f, "{seconds}s after 1970-01-01"),
+                }
+                let time = seconds.rem_euclid(DAY).round() as u32;
+                match (time / 3600, time / 60 % 60, time % 60) {
+                    (0, 0, 0) => Ok(()),
+                    (hours, minutes, 0) => write!(f, " {hours:02}:{minutes:02}"),
+                    (hours, minutes, seconds) => {
+                        write!(f, " {hours:02}:{minutes:02}:{seconds:02}")
+                    }
+                }
+            }
+            Time::Duration(seconds) => {
+                if seconds < 0.0 {
+                    write!(f, "-")?;
+                }
+                let seconds = seconds.abs();
+                let (days, hours) = ((seconds / DAY).floor(), (seconds % DAY / 3600.0).floor());
+                let (minutes, seconds) = ((seconds % 3600.0 / 60.0).floor(), seconds % 60.0);
+                let mut parts = vec![];
+                match days {
+                    0.0 => {}
+                    1.0 => parts.push("1 day".to_string()),
+                    days => parts.push(format!("{days} days")),
+                }
+                if hours != 0.0 {
+                    parts.push(format!("{hours}h"));
+                }
+                if minutes != 0.0 {
+                    parts.push(format!("{minutes}min"));
+                }
+                if seconds != 0.0 || parts.is_empty() {
+                    parts.push(format!("{seconds}s"));
+                }
+                write!(f, "{}", parts.join(" "))
+            }
+        }
+    }
+}
+
+impl Number for Time {
+    fn from_litteral(value: T) -> Self {
+        Time::Number(value as f64)
+    }
+
+    fn from_f64(value: f64) -> Result<Self, String> {
+        Time::Number(value).check()
+    }
+
+    // Seconds for the dates and the durations
+    fn to_f64(self) -> f64 {
+        match self {
+            Time::Number(value) | Time::Date(value) | Time::Duration(value) => value,
+        }
+    }
+
+    // A bare zero is the neutral element of the sums, so that sum() adds durations
+    fn add(self, other: Self) -> Result<Self, String> {
+        match (self, other) {
+            (Time::Number(a), Time::Number(b)) => Ok(Time::Number(a + b)),
+            (Time::Date(a), Time::Duration(b)) | (Time::Duration(b), Time::Date(a)) => {
+                Ok(Time::Date(a + b))
+            }
+            (Time::Duration(a), Time::Duration(b)) => Ok(Time::Duration(a + b)),
+            (zero, time) | (time, zero) if zero.is_zero() => Ok(time),
+            (Time::Date(_), Time::Date(_)) => Err(format!("Cannot add {self} and {other}")),
+            (Time::Date(_), number) | (number, Time::Date(_)) => Err(format!(
+                "Cannot add {number} to a date, add a duration such as {number} days"
+            )),
+            _ => Err(format!("Cannot add {self} and {other}")),
+        }?
+        .check()
+    }
+
+    fn sub(self, other: Self) -> Result<Self, String> {
+        match (self, other) {
+            (Time::Number(a), Time::Number(b)) => Ok(Time::Number(a - b)),
+            (Time::Date(a), Time::Date(b)) => Ok(Time::Duration(a - b)),
+            (Time::Date(a), Time::Duration(b)) => Ok(Time::Date(a - b)),
+            (Time::Duration(a), Time::Duration(b)) => Ok(Time::Duration(a - b)),
+            (time, zero) if zero.is_zero() => Ok(time),
+            (zero, Time::Duration(b)) if zero.is_zero() => Ok(Time::Duration(-b)),
+            (Time::Date(_), number @ Time::Number(_)) => Err(format!(
+                "Cannot subtract {number} from a date, subtract a duration such as {number} days"
+            )),
+            _ => Err(format!("Cannot subtract {other} from {self}")),
+        }?
+        .check()
+    }
+
+    fn mul(self, other: Self) -> Result<Self, String> {
+        match (self, other) {
+            (Time::Number(a), Time::Number(b)) => Ok(Time::Number(a * b)),
+            (Time::Number(a), Time::Duration(b)) | (Time::Duration(b), Time::Number(a)) => {
+                Ok(Time::Duration(a * b))
+            }
+            _ => Err(format!("Cannot multiply {self} by {other}")),
+        }?
+        .check()
+    }
+
+    fn div(self, other: Self) -> Result<Self, String> {
+        if other.to_f64() == 0.0 {
+            return Err(format!("Cannot divide {} by zero", self));
+        }
+
+        match (self, other) {
+            (Time::Number(a), Time::Number(b)) => Ok(Time::Number(a / b)),
+            (Time::Duration(a), Time::Number(b)) => Ok(Time::Duration(a / b)),
+            (Time::Duration(a), Time::Duration(b)) => Ok(Time::Number(a / b)),
+            _ => Err(format!("Cannot divide {self} by {other}")),
+        }?
+        .check()
+    }
+
+    fn rem(self, other: Self) -> Result<Self, String> {
+        if other.to_f64() == 0.0 {
+            return Err(format!("Cannot divide {} by zero", self));
+        }
+
+        match (self, other) {
+            (Time::Number(a), Time::Number(b)) => Ok(Time::Number(a % b)),
+            (Time::Duration(a), Time::Duration(b)) => Ok(Time::Duration(a % b)),
+            _ => Err(format!(
+                "{self} % {other} is only defined for numbers and durations"
+            )),
+        }
+    }
+
+    fn pow(self, other: Self) -> Result<Self, String> {
+        match (self, other) {
+            (Time::Number(a), Time::Number(b)) => Number::pow(a, b).map(Time::Number),
+            _ => Err(format!("Cannot raise {self} to the power {other}")),
+        }
+    }
+
+    fn constant(name: &str) -> Option<Self> {
+        let (_, seconds) = DURATION_UNITS.iter().find(|(unit, _)| *unit == name)?;
+        Some(Time::Duration(*seconds))
+    }
+
+    fn round(self, digits: i32) -> Result<Self, String> {
+        match self {
+            Time::Number(value) => Number::round(value, digits).map(Time::Number),
+            _ => Err(format!("round expects a number, got {self}")),
+        }
+    }
+
+    // Number of units in a duration, `2 weeks to days` being 14
+    fn convert(self, unit: Self) -> Result<Self, String> {
+        match (self, unit) {
+            (Time::Duration(_), Time::Duration(_)) => self.div(unit),
+            _ => Err(format!("Cannot convert {self} to {unit}")),
+        }
+    }
+
+    // The parts have at most 4 digits
+    fn from_iso_date(year: T, month: T, day: T) -> Result<Self, String> {
+        Ok(Time::date(Date::new(
+            year as i32,
+            month as u32,
+            day as u32,
+        )?))
+    }
+
+    fn date(date: Date) -> Result<Self, String> {
+        Ok(Time::date(date))
+    }
+
+    fn weekday(self) -> Result<Self, String> {
+        match self {
+            Time::Date(seconds) => Ok(Time::Number(Time::day(seconds)?.weekday() as f64)),
+            _ => Err(format!("weekday expects a date, got {self}")),
+        }
+    }
+
+    // The time of the day is kept
+    fn add_months(self, months: i32) -> Result<Self, String> {
+        match self {
+            Time::Date(seconds) => {
+                let day = Time::day(seconds)?;
+                let time = seconds - day.days() as f64 * DAY;
+                Time::Date(Time::date(day.add_months(months)?).to_f64() + time).check()
+            }
+            _ => Err(format!("add_months expects a date, got {self}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Rational, Session};
+
+    fn date(year: i32, month: u32, day: u32) -> Date {
+        Date::new(year, month, day).unwrap()
+    }
+
+    fn eval(input: &str) -> Result<String, String> {
+        let mut session = Session::<Time>::new();
+        Calendar::new(date(2026, 10, 19))
+            .scope(|| session.run(input).map(|result| result.unwrap().to_string()))
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(date(1970, 1, 1).days(), 0);
+        assert_eq!(date(2000, 3, 1).days(), 11017);
+        assert_eq!(date(1969, 12, 31).days(), -1);
+        for days in [-800000, -1, 0, 59, 11016, 20744, 800000] {
+            assert_eq!(Date::from_days(days).unwrap().days(), days);
+        }
+        assert_eq!(Date::from_days(20744), Ok(date(2026, 10, 18)));
+        assert_eq!(date(2026, 10, 18).weekday(), 7);
+        assert_eq!(date(2026, 1, 31).add_months(1), Ok(date(2026, 2, 28)));
+        assert_eq!(date(2024, 1, 31).add_months(1), Ok(date(2024, 2, 29)));
+        assert_eq!(date(2026, 3, 15).add_months(-15), Ok(date(2024, 12, 15)));
+        assert_eq!(
+            Date::new(2026, 2, 29).unwrap_err(),
+            "2026-02-29 is not a valid date"
+        );
+        assert!(Date::new(2026, 13, 1).is_err());
+        assert_eq!(
+            Date::new(10000, 1, 1).unwrap_err(),
+            "10000-01-01 is out of range, dates go from -9999-01-01 to 9999-12-31"
+        );
+        assert!(Date::from_days(i64::MAX).is_err());
+        assert!(Date::from_days(i64::MIN).is_err());
+        assert!(date(9999, 12, 1).add_months(1).is_err());
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(eval("2026-12-25 - today()"), Ok("67 days".to_string()));
+        assert_eq!(eval("2026-10-18 + 2 weeks"), Ok("2026-11-01".to_string()));
+        assert_eq!(eval("2026-10-18 - 1 day"), Ok("2026-10-17".to_string()));
+        assert_eq!(
+            eval("2026-10-18 + 3h 20min"),
+            Ok("2026-10-18 03:20".to_string())
+        );
+        assert_eq!(eval("3h 20min"), Ok("3h 20min".to_string()));
+        assert_eq!(eval("2 * 3h 20min"), Ok("6h 40min".to_string()));
+        assert_eq!(eval("3h 20min * 2"), Ok("6h 40min".to_string()));
+        // The units of a duration in several parts can't be variables
+        assert_eq!(eval("h = 2; 1 day 3h"), Ok("1 day 3h".to_string()));
+        assert_eq!(eval("h = 2; 3h"), Ok("6".to_string()));
+        assert_eq!(eval("f(h) = h * 1h 30min; f(2)"), Ok("3h".to_string()));
+        assert_eq!(eval("2 * 90min"), Ok("3h".to_string()));
+        assert_eq!(eval("-(1 day + 30s)"), Ok("-1 day 30s".to_string()));
+        assert_eq!(eval("(2 weeks) / (1 day)"), Ok("14".to_string()));
+        assert_eq!(eval("10 weeks to days"), Ok("70".to_string()));
+        assert_eq!(eval("sum(k, 1, 3, k * 1h)"), Ok("6h".to_string()));
+        assert_eq!(eval("0 s"), Ok("0s".to_string()));
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(eval("weekday(2026-10-18)"), Ok("7".to_string()));
+        assert_eq!(eval("weekday(today())"), Ok("1".to_string()));
+        assert_eq!(
+            eval("add_months(2026-01-31, 1)"),
+            Ok("2026-02-28".to_string())
+        );
+        assert_eq!(
+            eval("add_months(2026-10-18 + 9h, 14)"),
+            Ok("2027-12-18 09:00".to_string())
+        );
+        assert_eq!(
+            eval("date(2026, 2, 1) - date(2026, 1, 1)"),
+            Ok("31 days".to_string())
+        );
+        assert_eq!(
+            eval("deadline = add_months(today(), 3); deadline - today() to weeks"),
+            Ok("13.142857142857142".to_string())
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            eval("2026-10-18 + 3"),
+            Err("Cannot add 3 to a date, add a duration such as 3 days".to_string())
+        );
+        assert_eq!(
+            eval("2026-10-18 + 2026-10-19"),
+            Err("Cannot add 2026-10-18 and 2026-10-19".to_string())
+        );
+        assert_eq!(
+            eval("2026-02-30"),
+            Err("2026-02-30 is not a valid date".to_string())
+        );
+        assert_eq!(
+            eval("weekday(3h)"),
+            Err("weekday expects a date, got 3h".to_string())
+        );
+        assert_eq!(
+            eval("add_months(2026-10-18, 1.5)"),
+            Err("add_months expects an integer number of months, got 1.5".to_string())
+        );
+        assert_eq!(
+            eval("2026-10-18 + 10^30 * 1 week"),
+            Err("The date is out of range, dates go from -9999-01-01 to 9999-12-31".to_string())
+        );
+        assert!(eval("9999-12-31 + 1 day").is_err());
+        assert!(eval("add_months(2026-10-18, 100000)").is_err());
+        assert_eq!(
+            eval("(2h) * (3h)"),
+            Err("Cannot multiply 2h by 3h".to_string())
+        );
+
+        // The other backends have no dates, the digits are a subtraction
+        let mut session = Session::<T>::new();
+        assert_eq!(
+            session.run("date(2026, 10, 18) + 1").unwrap_err(),
+            "2026-10-18 is a date, evaluate it with dates"
+        );
+        assert_eq!(
+            session.run("s = 2; 3s 4s").unwrap_err(),
+            "s is a unit, evaluate it with durations or quantities"
+        );
+        let result = session.run("2026-10-18").unwrap().unwrap();
+        assert_eq!(result.to_string(), "1998");
+        let result = Session::<f64>::new().run("2026-10-18").unwrap().unwrap();
+        assert_eq!(result.to_string(), "1998");
+        let result = Session::<Rational>::new()
+            .run("2026-10-18")
+            .unwrap()
+            .unwrap();
+        assert_eq!(result.to_string(), "1998");
+        // Spaces make it a subtraction with dates too
+        assert_eq!(eval("2026 - 10 - 18"), Ok("1998".to_string()));
+    }
+}
@@ -1,30 +1,97 @@
-use super::expr::OPERATORS_PRECEDENCE;
+use super::config::ParserConfig;
+use super::money::CURRENCY_SYMBOLS;
 
-pub fn tokenize(input: &str) -> Result<Vec<String>, String> {
+pub fn tokenize(input: &str, config: &ParserConfig) -> Result<Vec<String>, String> {
     let mut tokens = vec![];
-    let mut it = input.chars().peekable();
+    let mut it = input.char_indices().peekable();
 
-    while let Some(c) = it.next() {
+    while let Some((i, c)) = it.next() {
         match c {
             c if c.is_numeric() => {
                 let mut token = vec![c];
-                while let Some(cc) = it.next_if(|x| x.is_numeric()) {
+                while let Some((_, cc)) = it.next_if(|(_, x)| x.is_numeric()) {
+                    token.push(cc);
+                }
+                // Dates such as 2026-10-18, which only the backend with dates doesn't read as
+                // subtractions
+                if token.len() == 4 && is_date(&input[i..]) {
+                    token.extend(input[i + 4..i + 10].chars());
+                    for _ in 0..6 {
+                        it.next();
+                    }
+                }
+                // Decimal part, the point must be followed by a digit
+                if let Some(&(point, '.')) = it.peek() {
+                    if input[point + 1..].starts_with(char::is_numeric) {
+                        it.next();
+                        token.push('.');
+                        while let Some((_, cc)) = it.next_if(|(_, x)| x.is_numeric()) {
+                            token.push(cc);
+                        }
+                    }
+                }
+                tokens.push(token.into_iter().collect());
+            }
+            c if c.is_alphabetic() => {
+                let mut token = vec![c];
+                while let Some((_, cc)) = it.next_if(|(_, x)| x.is_alphanumeric() || *x == '_') {
                     token.push(cc);
                 }
                 tokens.push(token.into_iter().collect());
             }
-            '(' | ')' => tokens.push(c.to_string()),
-            c if OPERATORS_PRECEDENCE.contains_key(c.to_string().as_str()) => {
+            '(' | ')' | '[' | ']' | ',' | ';' => tokens.push(c.to_string()),
+            c if CURRENCY_SYMBOLS.iter().any(|&(symbol, _)| symbol == c) => {
                 tokens.push(c.to_string())
             }
             ' ' => {}
-            _ => return Err(format!("Unrecognized token {c}")),
+            _ => {
+                // Longest operator starting here, so that `**` wins over `*`
+                let Some(operator) = config
+                    .symbols()
+                    // The prefix plus, the negation being one of the operators
+                    .chain(["+", "=", "->"])
+                    .filter(|operator| input[i..].starts_with(operator))
+                    .max_by_key(|operator| operator.len())
+                else {
+                    return Err(format!("Unrecognized token {c}"));
+                };
+                for _ in 1..operator.chars().count() {
+                    it.next();
+                }
+                tokens.push(operator.to_string());
+            }
         };
+        config.limits.check_tokens(tokens.len())?;
     }
 
     Ok(tokens)
 }
 
+pub fn is_number(token: &str) -> bool {
+    token.starts_with(char::is_numeric)
+}
+
+// "2026-10-18" at the start of `text`, not followed by another digit
+fn is_date(text: &str) -> bool {
+    let digits =
+        |range: std::ops::Range<usize>| text.as_bytes()[range].iter().all(u8::is_ascii_digit);
+    text.len() >= 10
+        && text.as_bytes()[4] == b'-'
+        && text.as_bytes()[7] == b'-'
+        && digits(0..4)
+        && digits(5..7)
+        && digits(8..10)
+        && !text[10..].starts_with(char::is_numeric)
+}
+
+pub fn is_identifier(token: &str) -> bool {
+    token
+        .chars()
+        .next()
+        .map(|c| c.is_alphabetic())
+        .unwrap_or(false)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -32,7 +99,46 @@ mod tests {
     #[test]
     fn basic() {
         let input = "12 + (1 * 7)";
-        let tokens = tokenize(input).unwrap();
+        let tokens = tokenize(input, &ParserConfig::default()).unwrap();
         assert_eq!(tokens, vec!["12", "+", "(", "1", "*", "7", ")"]);
     }
+
+    #[test]
+    fn multi_character_operators() {
+        let config = ParserConfig::default().rename("^", "**");
+        let tokens = tokenize("2**3*-4", &config).unwrap();
+        assert_eq!(tokens, vec!["2", "**", "3", "*", "-", "4"]);
+        assert!(tokenize("2^3", &config).is_err());
+    }
+
+    #[test]
+    fn decimals() {
+        let tokens = tokenize("9.81 m + 2.5", &ParserConfig::default()).unwrap();
+        assert_eq!(tokens, vec!["9.81", "m", "+", "2.5"]);
+        assert!(tokenize("2.", &ParserConfig::default()).is_err());
+    }
+
+    #[test]
+    fn dates() {
+        let tokens = tokenize("2026-10-18 - 2026-1-2", &ParserConfig::default()).unwrap();
+        assert_eq!(tokens, vec!["2026-10-18", "-", "2026", "-", "1", "-", "2"]);
+        let tokens = tokenize("2026-10-180", &ParserConfig::default()).unwrap();
+        assert_eq!(tokens, vec!["2026", "-", "10", "-", "180"]);
+    }
+
+    #[test]
+    fn statements() {
+        let tokens = tokenize("x = 3; x", &ParserConfig::default()).unwrap();
+        assert_eq!(tokens, vec!["x", "=", "3", ";", "x"]);
+    }
+
+    #[test]
+    fn function_call() {
+        let input = "diff(x_1^2, x_1, 3)";
+        let tokens = tokenize(input, &ParserConfig::default()).unwrap();
+        assert_eq!(
+            tokens,
+            vec!["diff", "(", "x_1", "^", "2", ",", "x_1", ",", "3", ")"]
+        );
+    }
 }
@@ -0,0 +1,1153 @@
+use super::context::Context;
+use super::expr::{lambda_params, BinaryOp, Expr};
+use super::functions::{check_arity, Functions};
+use super::limits::Limits;
+use super::matrix::Matrix;
+use super::number::Number;
+use super::time::{Calendar, Date};
+use super::visit::{Visitor, Walk};
+use super::T;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Display;
+use std::rc::Rc;
+
+// Built-in functions, evaluated with values as some of them take or return lists and
+// lambdas
+pub(super) const BUILTIN_FUNCTIONS: [&str; 32] = [
+    "map",
+    "filter",
+    "reduce",
+    "sum",
+    "prod",
+    "range",
+    "len",
+    "mean",
+    "dot",
+    "cross",
+    "norm",
+    "transpose",
+    "det",
+    "inverse",
+    "rank",
+    "identity",
+    "sqrt",
+    "ln",
+    "abs",
+    "arg",
+    "re",
+    "im",
+    "conj",
+    "to",
+    "round",
+    "date",
+    "today",
+    "weekday",
+    "add_months",
+    "decimal",
+    "unit",
+    "iso_date",
+];
+
+// Result of `Expr::eval_value`, lists and lambdas are values like numbers
+#[derive(Debug, Clone)]
+pub enum Value<N> {
+    Number(N),
+    List(Vec<Value<N>>),
+    Lambda(Lambda<N>),
+}
+
+// Lambda with the values of the variables it refers to when it was created
+#[derive(Debug, Clone)]
+pub struct Lambda<N> {
+    params: Vec<String>,
+    body: Rc<Expr>,
+    captured: Vec<(String, Value<N>)>,
+}
+
+// Variables seen by an expression. Local scopes are created by the calls, their
+// variables shadow the ones of the scopes around them.
+pub(super) enum Scope<'a, N> {
+    Global(&'a HashMap<String, Value<N>>),
+    Local(Vec<(String, Value<N>)>, Option<&'a Scope<'a, N>>),
+}
+
+impl<N: Number> Value<N> {
+    pub fn number(self) -> Result<N, String> {
+        match self {
+            Value::Number(value) => Ok(value),
+            other => Err(format!("Expected a number, got {}", other.kind())),
+        }
+    }
+
+    fn list(self, function: &str) -> Result<Vec<Value<N>>, String> {
+        match self {
+            Value::List(items) => Ok(items),
+            other => Err(format!("{function} expects a list, got {}", other.kind())),
+        }
+    }
+
+    fn lambda(self, function: &str) -> Result<Lambda<N>, String> {
+        match self {
+            Value::Lambda(lambda) => Ok(lambda),
+            other => Err(format!(
+                "{function} expects a function, got {}",
+                other.kind()
+            )),
+        }
+    }
+
+    fn vector(self, function: &str) -> Result<Vec<N>, String> {
+        self.list(function)?
+            .into_iter()
+            .map(|item| match item {
+                Value::Number(value) => Ok(value),
+                other => Err(format!(
+                    "{function} expects a list of numbers, got {} in the list",
+                    other.kind()
+                )),
+            })
+            .collect()
+    }
+
+    // Lists of numbers of the same length
+    fn matrix(self, function: &str) -> Result<Matrix<N>, String> {
+        let Value::List(rows) = self else {
+            return Err(format!("{function} expects a matrix, got {}", self.kind()));
+        };
+        let rows = rows
+            .into_iter()
+            .map(|row| match row {
+                Value::List(_) => row.vector(function),
+                other => Err(format!(
+                    "{function} expects a matrix, got {} in the list of rows",
+                    other.kind()
+                )),
+            })
+            .collect::<Result<_, _>>()?;
+        Matrix::new(rows)
+    }
+
+    fn is_vector(&self) -> bool {
+        matches!(self, Value::List(items)
+            if !items.is_empty() && items.iter().all(|item| matches!(item, Value::Number(_))))
+    }
+
+    fn is_matrix(&self) -> bool {
+        matches!(self, Value::List(rows) if !rows.is_empty() && rows.iter().all(Value::is_vector))
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "a number",
+            Value::List(_) => "a list",
+            Value::Lambda(_) => "a function",
+        }
+    }
+
+    // Operators apply to lists item by item, a number being combined with every item
+    fn apply(left: Value<N>, right: Value<N>, operation: BinaryOp<N>) -> Result<Value<N>, String> {
+        let items: Result<Vec<Value<N>>, String> = match (left, right) {
+            (Value::List(left), Value::List(right)) => {
+                if left.len() != right.len() {
+                    return Err(format!(
+                        "Cannot combine lists of different lengths ({} and {})",
+                        left.len(),
+                        right.len()
+                    ));
+                }
+                left.into_iter()
+                    .zip(right)
+                    .map(|(left, right)| Value::apply(left, right, operation))
+                    .collect()
+            }
+            (Value::List(left), right @ Value::Number(_)) => left
+                .into_iter()
+                .map(|left| Value::apply(left, right.clone(), operation))
+                .collect(),
+            (left @ Value::Number(_), Value::List(right)) => right
+                .into_iter()
+                .map(|right| Value::apply(left.clone(), right, operation))
+                .collect(),
+            (left, right) => return Ok(Value::Number(operation(left.number()?, right.number()?)?)),
+        };
+        items.map(Value::List)
+    }
+
+    // `*` is the product of matrices, a vector being a column on the right and a row on
+    // the left of a matrix. It applies item by item to other values.
+    fn multiply(left: Value<N>, right: Value<N>) -> Result<Value<N>, String> {
+        match (left.is_matrix(), right.is_matrix()) {
+            (true, true) => Ok(left.matrix("*")?.mul(&right.matrix("*")?)?.into()),
+            (true, false) if right.is_vector() => {
+                let matrix = left.matrix("*")?;
+                let vector = right.vector("*")?;
+                if matrix.cols() != vector.len() {
+                    return Err(format!(
+                        "Cannot multiply a {matrix} by a vector of length {}",
+                        vector.len()
+                    ));
+                }
+                let column = Matrix::new(vector.into_iter().map(|item| vec![item]).collect())?;
+                Ok(Value::from_vector(matrix.mul(&column)?))
+            }
+            (false, true) if left.is_vector() => {
+                let vector = left.vector("*")?;
+                let matrix = right.matrix("*")?;
+                if vector.len() != matrix.rows() {
+                    return Err(format!(
+                        "Cannot multiply a vector of length {} by a {matrix}",
+                        vector.len()
+                    ));
+                }
+                let row = Matrix::new(vec![vector])?;
+                Ok(Value::from_vector(row.mul(&matrix)?))
+            }
+            _ => Value::apply(left, right, N::mul),
+        }
+    }
+
+    // `^` raises a matrix to an integer power with matrix products. It applies item by
+    // item to other values.
+    fn power(left: Value<N>, right: Value<N>) -> Result<Value<N>, String> {
+        if !left.is_matrix() {
+            return Value::apply(left, right, N::pow);
+        }
+        let matrix = left.matrix("^")?;
+        let exponent = right.number()?;
+        let Some(exponent) = integer(exponent) else {
+            return Err(format!(
+                "Cannot raise a {matrix} to the non integer power {exponent}"
+            ));
+        };
+        Ok(matrix.pow(exponent)?.into())
+    }
+
+    // Entries of a matrix with a single row or column
+    fn from_vector(matrix: Matrix<N>) -> Value<N> {
+        let items = matrix.to_rows().into_iter().flatten();
+        Value::List(items.map(Value::Number).collect())
+    }
+
+    fn index(self, index: Value<N>) -> Result<Value<N>, String> {
+        let index = index.number()?;
+        let Value::List(mut items) = self else {
+            return Err(format!("Cannot index {}", self.kind()));
+        };
+
+        let position = index.to_real()?;
+        if position.fract() != 0.0 {
+            return Err(format!("Index {index} is not an integer"));
+        }
+        if position < 0.0 || position >= items.len() as f64 {
+            return Err(format!(
+                "Index {index} is out of range for a list of {} items",
+                items.len()
+            ));
+        }
+        Ok(items.swap_remove(position as usize))
+    }
+}
+
+impl<N> From<N> for Value<N> {
+    fn from(value: N) -> Self {
+        Value::Number(value)
+    }
+}
+
+impl<N: Number> From<Matrix<N>> for Value<N> {
+    fn from(matrix: Matrix<N>) -> Self {
+        let rows = matrix.to_rows().into_iter();
+        Value::List(
+            rows.map(|row| Value::List(row.into_iter().map(Value::Number).collect()))
+                .collect(),
+        )
+    }
+}
+
+impl<N: PartialEq> PartialEq for Value<N> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Number(left), Value::Number(right)) => left == right,
+            (Value::List(left), Value::List(right)) => left == right,
+            // A lambda is only equal to its copies
+            (Value::Lambda(left), Value::Lambda(right)) => {
+                Rc::ptr_eq(&left.body, &right.body) && left.captured == right.captured
+            }
+            _ => false,
+        }
+    }
+}
+
+impl<N: Display> Display for Value<N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Number(value) => write!(f, "{value}"),
+            Value::List(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                write!(f, "]")
+            }
+            Value::Lambda(lambda) => write!(f, "{lambda}"),
+        }
+    }
+}
+
+impl<N: Number> Lambda<N> {
+    fn new(params: &[String], body: &Expr, scope: &Scope<N>) -> Lambda<N> {
+        let mut names = Names::default();
+        names.visit_expr(body);
+        let captured = names
+            .0
+            .into_iter()
+            .filter(|name| !params.contains(name))
+            .filter_map(|name| {
+                let value = scope.get(&name)?.clone();
+                Some((name, value))
+            })
+            .collect();
+
+        Lambda {
+            params: params.to_vec(),
+            body: Rc::new(body.clone()),
+            captured,
+        }
+    }
+
+    pub fn params(&self) -> &[String] {
+        &self.params
+    }
+
+    // `name` is the one of the lambda in the errors
+    fn call(
+        &self,
+        name: &str,
+        args: Vec<Value<N>>,
+        context: &mut Context<N>,
+    ) -> Result<Value<N>, String> {
+        check_arity(name, &self.params, args.len())?;
+
+        let mut variables = self.captured.clone();
+        variables.extend(self.params.iter().cloned().zip(args));
+
+        context.enter_call(name)?;
+        let result = self
+            .body
+            .eval_value_in(&Scope::Local(variables, None), context);
+        context.leave_call();
+        result
+    }
+}
+
+impl<N> Display for Lambda<N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{:?}", lambda_params(&self.params), self.body)
+    }
+}
+
+// Names of the variables and functions used by an expression, the ones bound in it
+// included
+#[derive(Default)]
+struct Names(BTreeSet<String>);
+
+impl Visitor for Names {
+    fn visit_variable(&mut self, name: &str) {
+        self.0.insert(name.to_string());
+    }
+
+    fn visit_call(&mut self, name: &str, _args: &[Expr]) -> Walk {
+        self.0.insert(name.to_string());
+        Walk::Children
+    }
+}
+
+impl<N: Number> Scope<'_, N> {
+    fn get(&self, name: &str) -> Option<&Value<N>> {
+        let mut scope = self;
+        loop {
+            match scope {
+                Scope::Global(variables) => return variables.get(name),
+                Scope::Local(variables, parent) => {
+                    let found = variables
+                        .iter()
+                        .rev()
+                        .find(|(variable, _)| variable == name);
+                    if let Some((_, value)) = found {
+                        return Some(value);
+                    }
+                    scope = (*parent)?;
+                }
+            }
+        }
+    }
+
+    // The visible variables which are numbers
+    fn numbers(&self) -> HashMap<String, N> {
+        let mut visible: HashMap<&str, &Value<N>> = HashMap::new();
+        let mut scope = Some(self);
+        while let Some(current) = scope {
+            scope = match current {
+                Scope::Global(variables) => {
+                    for (name, value) in variables.iter() {
+                        visible.entry(name).or_insert(value);
+                    }
+                    None
+                }
+                Scope::Local(variables, parent) => {
+                    for (name, value) in variables.iter().rev() {
+                        visible.entry(name).or_insert(value);
+                    }
+                    *parent
+                }
+            };
+        }
+
+        visible
+            .into_iter()
+            .filter_map(|(name, value)| match value {
+                Value::Number(value) => Some((name.to_string(), *value)),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+fn arguments<'a, const K: usize>(
+    function: &str,
+    params: &str,
+    args: &'a [Expr],
+) -> Result<&'a [Expr; K], String> {
+    args.try_into().map_err(|_| {
+        format!(
+            "{function} expects {K} arguments ({params}), got {}",
+            args.len()
+        )
+    })
+}
+
+// Value of an argument which must be an integer, such as a number of months
+fn integer<N: Number>(value: N) -> Option<i32> {
+    let integer = value.to_real().ok()?;
+    if integer.fract() != 0.0 || integer.abs() > i32::MAX as f64 {
+        return None;
+    }
+    Some(integer as i32)
+}
+
+pub(super) fn from_numbers<N: Number>(variables: &HashMap<String, N>) -> HashMap<String, Value<N>> {
+    variables
+        .iter()
+        .map(|(name, value)| (name.clone(), Value::Number(*value)))
+        .collect()
+}
+
+enum Step<'a, N> {
+    Eval(&'a Expr),
+    Apply(BinaryOp<N>),
+    Multiply,
+    Power,
+    // Gathers the last values into a list
+    List(usize),
+    Index,
+}
+
+// Integer bounds of `sum`, `prod` and `range`, both included
+fn bounds<N: Number>(from: Value<N>, to: Value<N>) -> Result<(T, T), String> {
+    Ok((
+        from.number()?.to_real()?.ceil() as T,
+        to.number()?.to_real()?.floor() as T,
+    ))
+}
+
+fn dot<N: Number>(left: &[N], right: &[N]) -> Result<N, String> {
+    if left.len() != right.len() {
+        return Err(format!(
+            "Cannot take the dot product of vectors of different lengths ({} and {})",
+            left.len(),
+            right.len()
+        ));
+    }
+    left.iter()
+        .zip(right)
+        .try_fold(N::from_litteral(0), |sum, (left, right)| {
+            sum.add(left.mul(*right)?)
+        })
+}
+
+fn cross<N: Number>(left: &[N], right: &[N]) -> Result<Value<N>, String> {
+    let ([a1, a2, a3], [b1, b2, b3]) = (left, right) else {
+        return Err(format!(
+            "cross expects vectors of length 3, got lengths {} and {}",
+            left.len(),
+            right.len()
+        ));
+    };
+    let minor = |a: N, b: N, c: N, d: N| -> Result<Value<N>, String> {
+        Ok(Value::Number(a.mul(b)?.sub(c.mul(d)?)?))
+    };
+    Ok(Value::List(vec![
+        minor(*a2, *b3, *a3, *b2)?,
+        minor(*a3, *b1, *a1, *b3)?,
+        minor(*a1, *b2, *a2, *b1)?,
+    ]))
+}
+
+impl Expr {
+    pub fn eval_value<N: Number>(
+        &self,
+        variables: &HashMap<String, Value<N>>,
+    ) -> Result<Value<N>, String> {
+        self.eval_value_with_functions(variables, &Functions::new(), &Limits::default())
+    }
+
+    pub fn eval_value_with_functions<N: Number>(
+        &self,
+        variables: &HashMap<String, Value<N>>,
+        functions: &Functions,
+        limits: &Limits,
+    ) -> Result<Value<N>, String> {
+        let mut context = Context::with_functions(limits, functions, variables.clone());
+        self.eval_value_in(&Scope::Global(variables), &mut context)
+    }
+
+    pub(super) fn eval_value_in<N: Number>(
+        &self,
+        scope: &Scope<N>,
+        context: &mut Context<N>,
+    ) -> Result<Value<N>, String> {
+        let mut steps = vec![Step::Eval(self)];
+        let mut values: Vec<Value<N>> = vec![];
+
+        while let Some(step) = steps.pop() {
+            let expr = match step {
+                Step::Apply(operation) => {
+                    let right = values.pop().unwrap();
+                    let left = values.pop().unwrap();
+                    values.push(Value::apply(left, right, operation)?);
+                    continue;
+                }
+                Step::Multiply => {
+                    let right = values.pop().unwrap();
+                    let left = values.pop().unwrap();
+                    values.push(Value::multiply(left, right)?);
+                    continue;
+                }
+                Step::Power => {
+                    let right = values.pop().unwrap();
+                    let left = values.pop().unwrap();
+                    values.push(Value::power(left, right)?);
+                    continue;
+                }
+                Step::List(len) => {
+                    let items = values.split_off(values.len() - len);
+                    values.push(Value::List(items));
+                    continue;
+                }
+                Step::Index => {
+                    let index = values.pop().unwrap();
+                    let list = values.pop().unwrap();
+                    values.push(list.index(index)?);
+                    continue;
+                }
+                Step::Eval(expr) => expr,
+            };
+
+            context.step()?;
+            let (left, right, operation) = match expr {
+                Expr::Litteral(val) => {
+                    values.push(Value::Number(N::from_litteral(*val)));
+                    continue;
+                }
+                Expr::Variable(name) => {
+                    let value = scope
+                        .get(name)
+                        .cloned()
+                        .or_else(|| N::constant(name).map(Value::Number))
+                        .ok_or_else(|| format!("Unknown variable {name}"))?;
+                    values.push(value);
+                    continue;
+                }
+                Expr::Call(name, args) => {
+                    values.push(Expr::call_value(name, args, scope, context)?);
+                    continue;
+                }
+                Expr::Lambda(params, body) => {
+                    values.push(Value::Lambda(Lambda::new(params, body, scope)));
+                    continue;
+                }
+                Expr::List(items) => {
+                    steps.push(Step::List(items.len()));
+                    steps.extend(items.iter().rev().map(Step::Eval));
+                    continue;
+                }
+                Expr::Index(list, index) => {
+                    steps.extend([Step::Index, Step::Eval(index), Step::Eval(list)]);
+                    continue;
+                }
+                Expr::Add(left, right) => (left, right, Step::Apply(N::add)),
+                Expr::Prod(left, right) => (left, right, Step::Multiply),
+                Expr::Sub(left, right) => (left, right, Step::Apply(N::sub)),
+                Expr::Div(left, right) => (left, right, Step::Apply(N::div)),
+                Expr::Pow(left, right) => (left, right, Step::Power),
+                Expr::Mod(left, right) => (left, right, Step::Apply(N::rem)),
+            };
+            // The left operand is evaluated first, so its error wins
+            steps.extend([operation, Step::Eval(right), Step::Eval(left)]);
+        }
+
+        Ok(values.pop().unwrap())
+    }
+
+    pub(super) fn call_value<N: Number>(
+        name: &str,
+        args: &[Expr],
+        scope: &Scope<N>,
+        context: &mut Context<N>,
+    ) -> Result<Value<N>, String> {
+        if BUILTIN_FUNCTIONS.contains(&name) || name == "integrate" || name == "diff" {
+            return Expr::call_builtin(name, args, scope, context);
+        }
+
+        // Lambdas stored in variables, then user defined functions
+        if let Some(Value::Lambda(lambda)) = scope.get(name) {
+            let args = args
+                .iter()
+                .map(|arg| arg.eval_value_in(scope, context))
+                .collect::<Result<Vec<_>, String>>()?;
+            return lambda.call(name, args, context);
+        }
+
+        let Some(function) = context.function(name) else {
+            return Err(match scope.get(name) {
+                Some(value) => format!("{name} is not a function, it is {}", value.kind()),
+                None => format!("Unknown function {name}"),
+            });
+        };
+        check_arity(name, function.params(), args.len())?;
+        let mut variables = vec![];
+        for (param, arg) in function.params().iter().zip(args) {
+            let value = arg.eval_value_in(scope, context)?;
+            variables.push((function.param_name(param), value));
+        }
+
+        // The body sees the globals, not the variables bound by its callers
+        let globals = context.globals();
+        context.enter_call(name)?;
+        let result = function.body().eval_value_in(
+            &Scope::Local(variables, Some(&Scope::Global(&globals))),
+            context,
+        );
+        context.leave_call();
+        result
+    }
+
+    // Kept apart from `call_value`, so that the nested calls of user defined functions
+    // take less of the stack
+    fn call_builtin<N: Number>(
+        name: &str,
+        args: &[Expr],
+        scope: &Scope<N>,
+        context: &mut Context<N>,
+    ) -> Result<Value<N>, String> {
+        let given = format!("The function given to {name}");
+        match name {
+            "integrate" | "diff" => {
+                Expr::eval_call(name, args, &scope.numbers(), context).map(Value::Number)
+            }
+            "decimal" => Expr::eval_decimal(args).map(Value::Number),
+            "unit" => Expr::eval_unit(args).map(Value::Number),
+            "iso_date" => Expr::eval_iso_date(args).map(Value::Number),
+            "sum" | "prod" => {
+                let (mut result, operation): (N, BinaryOp<N>) = match name {
+                    "sum" => (N::from_litteral(0), N::add),
+                    _ => (N::from_litteral(1), N::mul),
+                };
+                // The expression is evaluated with the variable bound to each integer
+                // between the bounds
+                let (var, from, to, body) = match args {
+                    [list] => {
+                        let items = list.eval_value_in(scope, context)?.list(name)?;
+                        return items
+                            .into_iter()
+                            .try_fold(Value::Number(result), |result, item| {
+                                Value::apply(result, item, operation)
+                            });
+                    }
+                    [var, from, to, body] => (var, from, to, body),
+                    _ => {
+                        return Err(format!(
+                            "{name} expects 1 argument (list) or 4 arguments (var, from, to, expr), got {}",
+                            args.len()
+                        ))
+                    }
+                };
+                let var = Expr::bound_variable(name, var)?;
+                let from = from.eval_value_in(scope, context)?;
+                let to = to.eval_value_in(scope, context)?;
+                let (from, to) = bounds(from, to)?;
+
+                for i in from..=to {
+                    let value = Value::Number(N::from_litteral(i));
+                    let scope = Scope::Local(vec![(var.to_string(), value)], Some(scope));
+                    result = operation(result, body.eval_value_in(&scope, context)?.number()?)?;
+                }
+                Ok(Value::Number(result))
+            }
+            "range" => {
+                let [from, to] = arguments(name, "from, to", args)?;
+                let from = from.eval_value_in(scope, context)?;
+                let to = to.eval_value_in(scope, context)?;
+                let (from, to) = bounds(from, to)?;
+
+                let mut items = vec![];
+                for i in from..=to {
+                    context.step()?;
+                    items.push(Value::Number(N::from_litteral(i)));
+                }
+                Ok(Value::List(items))
+            }
+            "len" => {
+                let [list] = arguments(name, "list", args)?;
+                let items = list.eval_value_in(scope, context)?.list(name)?;
+                Ok(Value::Number(N::from_litteral(items.len() as T)))
+            }
+            "mean" => {
+                let [list] = arguments(name, "list", args)?;
+                let items = list.eval_value_in(scope, context)?.list(name)?;
+                let Some((first, rest)) = items.split_first() else {
+                    return Err("Cannot take the mean of an empty list".to_string());
+                };
+                let len = Value::Number(N::from_litteral(items.len() as T));
+                let sum = rest.iter().try_fold(first.clone(), |sum, item| {
+                    Value::apply(sum, item.clone(), N::add)
+                })?;
+                Value::apply(sum, len, N::div)
+            }
+            "dot" | "cross" => {
+                let [left, right] = arguments(name, "u, v", args)?;
+                let left = left.eval_value_in(scope, context)?.vector(name)?;
+                let right = right.eval_value_in(scope, context)?.vector(name)?;
+                if name == "dot" {
+                    dot(&left, &right).map(Value::Number)
+                } else {
+                    cross(&left, &right)
+                }
+            }
+            "norm" => {
+                let [vector] = arguments(name, "v", args)?;
+                let vector = vector.eval_value_in(scope, context)?.vector(name)?;
+                let squares = dot(&vector, &vector)?;
+                N::from_f64(squares.to_f64().sqrt()).map(Value::Number)
+            }
+            "sqrt" | "ln" | "abs" | "arg" | "re" | "im" | "conj" => {
+                let [z] = arguments(name, "z", args)?;
+                let z = z.eval_value_in(scope, context)?.number()?;
+                let value = match name {
+                    "sqrt" => z.sqrt()?,
+                    "ln" => z.ln()?,
+                    "abs" => z.abs()?,
+                    "arg" => z.arg()?,
+                    "re" => z.re(),
+                    "im" => z.im(),
+                    _ => z.conj(),
+                };
+                Ok(Value::Number(value))
+            }
+            "round" => {
+                let [x, digits] = arguments(name, "x, digits", args)?;
+                let x = x.eval_value_in(scope, context)?.number()?;
+                let digits = digits.eval_value_in(scope, context)?.number()?;
+                let count = digits.to_real()?;
+                if count.fract() != 0.0 || count.abs() > 1000.0 {
+                    return Err(format!(
+                        "round expects an integer number of digits, got {digits}"
+                    ));
+                }
+                x.round(count as i32).map(Value::Number)
+            }
+            "date" => {
+                let [year, month, day] = arguments(name, "year, month, day", args)?;
+                let mut parts = [0; 3];
+                for (part, arg) in parts.iter_mut().zip([year, month, day]) {
+                    let value = arg.eval_value_in(scope, context)?.number()?;
+                    *part = integer(value).ok_or(format!("date expects integers, got {value}"))?;
+                }
+                let [year, month, day] = parts;
+                N::date(Date::new(year, month as u32, day as u32)?).map(Value::Number)
+            }
+            "today" => {
+                let [] = arguments(name, "", args)?;
+                N::date(Calendar::today()?).map(Value::Number)
+            }
+            "weekday" => {
+                let [date] = arguments(name, "date", args)?;
+                let date = date.eval_value_in(scope, context)?.number()?;
+                date.weekday().map(Value::Number)
+            }
+            "add_months" => {
+                let [date, months] = arguments(name, "date, months", args)?;
+                let date = date.eval_value_in(scope, context)?.number()?;
+                let months = months.eval_value_in(scope, context)?.number()?;
+                let Some(count) = integer(months) else {
+                    return Err(format!(
+                        "add_months expects an integer number of months, got {months}"
+                    ));
+                };
+                date.add_months(count).map(Value::Number)
+            }
+            // `value to unit`, item by item for lists
+            "to" => {
+                let [value, unit] = arguments(name, "value, unit", args)?;
+                let value = value.eval_value_in(scope, context)?;
+                let unit = unit.eval_value_in(scope, context)?;
+                Value::apply(value, unit, N::convert)
+            }
+            "transpose" | "det" | "inverse" | "rank" => {
+                let [matrix] = arguments(name, "m", args)?;
+                let matrix = matrix.eval_value_in(scope, context)?.matrix(name)?;
+                match name {
+                    "transpose" => Ok(matrix.transpose().into()),
+                    "det" => matrix.det().map(Value::Number),
+                    "inverse" => matrix.inverse().map(Value::from),
+                    _ => Ok(Value::Number(N::from_litteral(matrix.rank()? as T))),
+                }
+            }
+            "identity" => {
+                let [size] = arguments(name, "n", args)?;
+                let size = size.eval_value_in(scope, context)?.number()?;
+                let from = size.to_real()?;
+                let to = from.floor();
+                if from != to || from < 1.0 {
+                    return Err(format!("{size} is not a valid size of matrix"));
+                }
+                for _ in 0..(to as usize).saturating_mul(to as usize) {
+                    context.step()?;
+                }
+                Ok(Matrix::identity(to as usize).into())
+            }
+            "map" | "filter" => {
+                let [function, list] = arguments(name, "function, list", args)?;
+                let function = function.eval_value_in(scope, context)?.lambda(name)?;
+                let list = list.eval_value_in(scope, context)?.list(name)?;
+
+                let mut items = vec![];
+                for item in list {
+                    if name == "map" {
+                        items.push(function.call(&given, vec![item], context)?);
+                        continue;
+                    }
+                    let keep = function.call(&given, vec![item.clone()], context)?;
+                    if keep.number()? != N::from_litteral(0) {
+                        items.push(item);
+                    }
+                }
+                Ok(Value::List(items))
+            }
+            "reduce" => {
+                let (function, list, initial) = match args {
+                    [function, list] => (function, list, None),
+                    [function, list, initial] => (function, list, Some(initial)),
+                    _ => {
+                        return Err(format!(
+                            "reduce expects 2 or 3 arguments (function, list, initial), got {}",
+                            args.len()
+                        ))
+                    }
+                };
+                let function = function.eval_value_in(scope, context)?.lambda(name)?;
+                let mut items = list.eval_value_in(scope, context)?.list(name)?.into_iter();
+
+                let mut result = match initial {
+                    Some(initial) => initial.eval_value_in(scope, context)?,
+                    None => items
+                        .next()
+                        .ok_or("Cannot reduce an empty list without an initial value")?,
+                };
+                for item in items {
+                    result = function.call(&given, vec![result, item], context)?;
+                }
+                Ok(result)
+            }
+            _ => Err(format!("Unknown function {name}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Rational, Session};
+
+    fn eval(input: &str) -> Result<String, String> {
+        let variables: HashMap<String, Value<T>> = HashMap::new();
+        Expr::parse(input)?
+            .eval_value(&variables)
+            .map(|value| value.to_string())
+    }
+
+    #[test]
+    fn lambdas() {
+        assert_eq!(eval("map(x -> x^2, range(1, 4))").unwrap(), "[1, 4, 9, 16]");
+        assert_eq!(eval("(x, y) -> x * y").unwrap(), "(x, y) -> Prod(x, y)");
+        assert_eq!(eval("reduce((a, b) -> a * b, range(1, 5))").unwrap(), "120");
+        assert_eq!(
+            eval("filter(n -> n % 3, range(1, 7))").unwrap(),
+            "[1, 2, 4, 5, 7]"
+        );
+        assert_eq!(
+            eval("reduce((a, b) -> a + b, range(1, 0), 7)").unwrap(),
+            "7"
+        );
+        // Lambdas returning lambdas
+        let input = "map(f -> f(10), map(k -> x -> k * x, range(1, 3)))";
+        assert_eq!(eval(input).unwrap(), "[10, 20, 30]");
+    }
+
+    #[test]
+    fn lists() {
+        assert_eq!(eval("[1, 2, 3] * 2").unwrap(), "[2, 4, 6]");
+        assert_eq!(eval("[1, 2, 3] + [10, 20, 30]").unwrap(), "[11, 22, 33]");
+        assert_eq!(eval("1 - [[1, 2], [3, 4]]").unwrap(), "[[0, -1], [-2, -3]]");
+        assert_eq!(eval("[1, 2 + 3, [4]][1]").unwrap(), "5");
+        assert_eq!(eval("[[1, 2], [3, 4]][1][0]").unwrap(), "3");
+        assert_eq!(eval("[]").unwrap(), "[]");
+
+        assert_eq!(eval("len([1, 2, 3])").unwrap(), "3");
+        assert_eq!(eval("sum([1, 2, 3])").unwrap(), "6");
+        assert_eq!(eval("sum([[1, 2], [3, 4]])").unwrap(), "[4, 6]");
+        assert_eq!(eval("prod([2, 3, 4])").unwrap(), "24");
+        assert_eq!(eval("mean([1, 2, 3, 6])").unwrap(), "3");
+        assert_eq!(eval("dot([1, 2, 3], [4, 5, 6])").unwrap(), "32");
+        assert_eq!(eval("cross([1, 0, 0], [0, 1, 0])").unwrap(), "[0, 0, 1]");
+        assert_eq!(eval("norm([3, 4])").unwrap(), "5");
+
+        let expr = Expr::parse("v[1] * 2").unwrap();
+        let variables = HashMap::from([(
+            "v".to_string(),
+            Value::List(vec![Value::Number(1.5), Value::Number(2.5)]),
+        )]);
+        assert_eq!(expr.eval_value(&variables), Ok(Value::Number(5.0)));
+        assert_eq!(Expr::parse("[1, 2][0] + 1").unwrap().eval(), Ok(2));
+    }
+
+    #[test]
+    fn list_errors() {
+        assert_eq!(
+            eval("[1, 2, 3] + [1, 2]").unwrap_err(),
+            "Cannot combine lists of different lengths (3 and 2)"
+        );
+        assert_eq!(
+            eval("[1, 2][2]").unwrap_err(),
+            "Index 2 is out of range for a list of 2 items"
+        );
+        assert_eq!(
+            eval("[1, 2][-1]").unwrap_err(),
+            "Index -1 is out of range for a list of 2 items"
+        );
+        assert_eq!(eval("3[0]").unwrap_err(), "Cannot index a number");
+        assert_eq!(
+            eval("dot([1, 2], [1, 2, 3])").unwrap_err(),
+            "Cannot take the dot product of vectors of different lengths (2 and 3)"
+        );
+        assert_eq!(
+            eval("cross([1, 2], [3, 4])").unwrap_err(),
+            "cross expects vectors of length 3, got lengths 2 and 2"
+        );
+        assert_eq!(
+            eval("norm([[1]])").unwrap_err(),
+            "norm expects a list of numbers, got a list in the list"
+        );
+        assert_eq!(
+            eval("mean([])").unwrap_err(),
+            "Cannot take the mean of an empty list"
+        );
+        assert_eq!(
+            eval("len(1)").unwrap_err(),
+            "len expects a list, got a number"
+        );
+        assert_eq!(
+            Expr::parse("[1, 2]").unwrap().eval().unwrap_err(),
+            "Expected a number, got a list"
+        );
+    }
+
+    #[test]
+    fn sums_and_products() {
+        assert_eq!(eval("sum(i, 1, 10, i^2)").unwrap(), "385");
+        assert_eq!(eval("prod(i, 1, 5, i)").unwrap(), "120");
+        assert_eq!(eval("sum(i, 1, 3, sum(j, 1, i, i * j))").unwrap(), "25");
+        assert_eq!(eval("sum(i, 5, 1, i)").unwrap(), "0");
+
+        // Also from the evaluation of numbers
+        let expr = Expr::parse("sum(i, 1, n, i) + x").unwrap();
+        let variables = HashMap::from([("n".to_string(), 4), ("x".to_string(), 1)]);
+        assert_eq!(expr.eval_with(&variables), Ok(11));
+        assert_eq!(
+            expr.free_variables().into_iter().collect::<Vec<_>>(),
+            vec!["n", "x"]
+        );
+    }
+
+    #[test]
+    fn matrices() {
+        let a = "[[1, 2], [3, 4]]";
+        assert_eq!(
+            eval(&format!("{a} * [[5, 6], [7, 8]]")).unwrap(),
+            "[[19, 22], [43, 50]]"
+        );
+        assert_eq!(eval(&format!("{a} * [1, 1]")).unwrap(), "[3, 7]");
+        assert_eq!(eval(&format!("[1, 1] * {a}")).unwrap(), "[4, 6]");
+        // Other operators and vectors still apply item by item
+        assert_eq!(eval(&format!("{a} + {a}")).unwrap(), "[[2, 4], [6, 8]]");
+        assert_eq!(eval("[1, 2] * [3, 4]").unwrap(), "[3, 8]");
+
+        assert_eq!(eval("transpose([[1, 2, 3]])").unwrap(), "[[1], [2], [3]]");
+        assert_eq!(eval(&format!("det({a})")).unwrap(), "-2");
+        assert_eq!(eval("rank([[1, 2], [2, 4], [3, 6]])").unwrap(), "1");
+        assert_eq!(
+            eval("inverse([[2, 1], [1, 1]])").unwrap(),
+            "[[1, -1], [-1, 2]]"
+        );
+        assert_eq!(eval("identity(2) * [5, 6]").unwrap(), "[5, 6]");
+        // Powers are matrix products, vectors are still raised item by item
+        assert_eq!(eval(&format!("{a}^2")).unwrap(), "[[7, 10], [15, 22]]");
+        assert_eq!(
+            eval(&format!("{a}^3")).unwrap(),
+            eval(&format!("{a} * {a} * {a}")).unwrap()
+        );
+        assert_eq!(eval(&format!("{a}^0")).unwrap(), "[[1, 0], [0, 1]]");
+        assert_eq!(eval("[[2, 1], [1, 1]]^-2").unwrap(), "[[2, -3], [-3, 5]]");
+        assert_eq!(eval("[1, 2]^2").unwrap(), "[1, 4]");
+
+        let mut session = Session::<Rational>::new();
+        session.run(&format!("m = {a}")).unwrap();
+        assert_eq!(
+            session.run("inverse(m)").unwrap().unwrap().to_string(),
+            "[[-2, 1], [3/2, -1/2]]"
+        );
+        assert_eq!(
+            session.run("m * inverse(m)").unwrap().unwrap().to_string(),
+            "[[1, 0], [0, 1]]"
+        );
+    }
+
+    #[test]
+    fn matrix_errors() {
+        assert_eq!(
+            eval("[[1, 2, 3], [4, 5, 6]] * [[1, 2, 3], [4, 5, 6]]").unwrap_err(),
+            "Cannot multiply a 2x3 matrix by a 2x3 matrix"
+        );
+        assert_eq!(
+            eval("[[1, 2, 3], [4, 5, 6]] * [1, 2]").unwrap_err(),
+            "Cannot multiply a 2x3 matrix by a vector of length 2"
+        );
+        assert_eq!(
+            eval("[1, 2, 3] * [[1, 2], [3, 4]]").unwrap_err(),
+            "Cannot multiply a vector of length 3 by a 2x2 matrix"
+        );
+        assert_eq!(
+            eval("det([[1, 2, 3], [4, 5, 6]])").unwrap_err(),
+            "det expects a square matrix, got a 2x3 matrix"
+        );
+        assert_eq!(
+            eval("det([[1], [2, 3]])").unwrap_err(),
+            "The rows of a matrix must have the same length (1 and 2)"
+        );
+        assert_eq!(
+            eval("inverse([[1, 2], [2, 4]])").unwrap_err(),
+            "Cannot invert a singular 2x2 matrix"
+        );
+        assert_eq!(
+            eval("transpose([1, 2])").unwrap_err(),
+            "transpose expects a matrix, got a number in the list of rows"
+        );
+        assert_eq!(
+            eval("rank(3)").unwrap_err(),
+            "rank expects a matrix, got a number"
+        );
+        assert_eq!(
+            eval("[[1, 2, 3], [4, 5, 6]]^2").unwrap_err(),
+            "^ expects a square matrix, got a 2x3 matrix"
+        );
+        assert_eq!(
+            Session::<Rational>::new()
+                .run("[[1, 2], [3, 4]]^0.5")
+                .unwrap_err(),
+            "Cannot raise a 2x2 matrix to the non integer power 1/2"
+        );
+        assert_eq!(
+            eval("identity(0)").unwrap_err(),
+            "0 is not a valid size of matrix"
+        );
+    }
+
+    #[test]
+    fn scopes() {
+        let mut session = Session::<T>::new();
+        session.run("k = 10; add = x -> x + k").unwrap();
+        // The lambda keeps the value of k it was created with
+        session.run("k = 1").unwrap();
+        assert_eq!(session.run("add(5)"), Ok(Some(Value::Number(15))));
+        assert_eq!(
+            session
+                .run("map(x -> add(x) * k, range(1, 3))")
+                .unwrap()
+                .unwrap()
+                .to_string(),
+            "[11, 12, 13]"
+        );
+
+        session.run("twice(f, x) = f(f(x))").unwrap();
+        assert_eq!(session.run("twice(add, 1)"), Ok(Some(Value::Number(21))));
+        assert_eq!(
+            session.run("twice(x -> x * x, 3)"),
+            Ok(Some(Value::Number(81)))
+        );
+        session.run("adder(x) = y -> x + y").unwrap();
+        assert_eq!(
+            session.run("f = adder(2); f(3)"),
+            Ok(Some(Value::Number(5)))
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            eval("map(x -> x, 3)").unwrap_err(),
+            "map expects a list, got a number"
+        );
+        assert_eq!(
+            eval("map(3, range(1, 2))").unwrap_err(),
+            "map expects a function, got a number"
+        );
+        assert_eq!(
+            eval("map((x, y) -> x, range(1, 2))").unwrap_err(),
+            "The function given to map expects 2 arguments (x, y), got 1"
+        );
+        assert_eq!(
+            eval("(x -> x) + 1").unwrap_err(),
+            "Expected a number, got a function"
+        );
+        assert_eq!(
+            eval("sum(i, 1, 2)").unwrap_err(),
+            "sum expects 1 argument (list) or 4 arguments (var, from, to, expr), got 3"
+        );
+        assert_eq!(
+            eval("reduce((a, b) -> a, range(1, 0))").unwrap_err(),
+            "Cannot reduce an empty list without an initial value"
+        );
+        assert_eq!(
+            Expr::parse("x -> x").unwrap().eval().unwrap_err(),
+            "Expected a number, got a function"
+        );
+        assert_eq!(
+            Session::<T>::new().run("g = f -> f(f); g(g)").unwrap_err(),
+            "Too many nested calls in f (the limit is 100)"
+        );
+        assert!(Expr::parse("(x, x) -> x").is_err());
+    }
+}
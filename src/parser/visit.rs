@@ -0,0 +1,331 @@
+use super::expr::Expr;
+use super::T;
+
+// Whether the children of a visited node are visited too
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Walk {
+    Children,
+    Skip,
+}
+
+// Read-only traversal of an expression. Every node is visited before its children, which
+// are visited by default, so an analysis only overrides the nodes it cares about:
+//
+//     struct Divisions(usize);
+//
+//     impl Visitor for Divisions {
+//         fn visit_div(&mut self, _left: &Expr, _right: &Expr) -> Walk {
+//             self.0 += 1;
+//             Walk::Children
+//         }
+//     }
+//
+// The tree is walked with an explicit stack, so deep trees don't overflow the call stack.
+pub trait Visitor {
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr);
+    }
+
+    // Called on every node, dispatches to the method of its kind
+    fn enter(&mut self, expr: &Expr) -> Walk {
+        visit_node(self, expr)
+    }
+
+    fn visit_litteral(&mut self, _value: T) {}
+
+    fn visit_variable(&mut self, _name: &str) {}
+
+    fn visit_add(&mut self, _left: &Expr, _right: &Expr) -> Walk {
+        Walk::Children
+    }
+
+    fn visit_sub(&mut self, _left: &Expr, _right: &Expr) -> Walk {
+        Walk::Children
+    }
+
+    fn visit_prod(&mut self, _left: &Expr, _right: &Expr) -> Walk {
+        Walk::Children
+    }
+
+    fn visit_div(&mut self, _left: &Expr, _right: &Expr) -> Walk {
+        Walk::Children
+    }
+
+    fn visit_mod(&mut self, _left: &Expr, _right: &Expr) -> Walk {
+        Walk::Children
+    }
+
+    fn visit_pow(&mut self, _left: &Expr, _right: &Expr) -> Walk {
+        Walk::Children
+    }
+
+    fn visit_call(&mut self, _name: &str, _args: &[Expr]) -> Walk {
+        Walk::Children
+    }
+
+    fn visit_lambda(&mut self, _params: &[String], _body: &Expr) -> Walk {
+        Walk::Children
+    }
+
+    fn visit_list(&mut self, _items: &[Expr]) -> Walk {
+        Walk::Children
+    }
+
+    fn visit_index(&mut self, _list: &Expr, _index: &Expr) -> Walk {
+        Walk::Children
+    }
+}
+
+pub fn walk_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expr) {
+    let mut pending = vec![expr];
+    while let Some(expr) = pending.pop() {
+        if visitor.enter(expr) == Walk::Children {
+            pending.extend(expr.children().into_iter().rev());
+        }
+    }
+}
+
+pub fn visit_node<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expr) -> Walk {
+    match expr {
+        Expr::Litteral(value) => {
+            visitor.visit_litteral(*value);
+            Walk::Skip
+        }
+        Expr::Variable(name) => {
+            visitor.visit_variable(name);
+            Walk::Skip
+        }
+        Expr::Add(left, right) => visitor.visit_add(left, right),
+        Expr::Sub(left, right) => visitor.visit_sub(left, right),
+        Expr::Prod(left, right) => visitor.visit_prod(left, right),
+        Expr::Div(left, right) => visitor.visit_div(left, right),
+        Expr::Mod(left, right) => visitor.visit_mod(left, right),
+        Expr::Pow(left, right) => visitor.visit_pow(left, right),
+        Expr::Call(name, args) => visitor.visit_call(name, args),
+        Expr::Lambda(params, body) => visitor.visit_lambda(params, body),
+        Expr::List(items) => visitor.visit_list(items),
+        Expr::Index(list, index) => visitor.visit_index(list, index),
+    }
+}
+
+// Rebuilding traversal of an expression, children are folded before their parent is
+// rebuilt from them. The default methods rebuild the same node:
+//
+//     struct Substitute(String, T);
+//
+//     impl Folder for Substitute {
+//         fn fold_variable(&mut self, name: String) -> Expr {
+//             if name == self.0 { Expr::Litteral(self.1) } else { Expr::Variable(name) }
+//         }
+//     }
+//
+// Like the visitors, folders walk the tree with an explicit stack.
+pub trait Folder {
+    fn fold_expr(&mut self, expr: Expr) -> Expr {
+        walk_fold(self, expr)
+    }
+
+    // Called on every node before its children are folded, such as to track the scopes
+    fn enter(&mut self, _expr: &Expr) {}
+
+    fn fold_litteral(&mut self, value: T) -> Expr {
+        Expr::Litteral(value)
+    }
+
+    fn fold_variable(&mut self, name: String) -> Expr {
+        Expr::Variable(name)
+    }
+
+    fn fold_add(&mut self, left: Expr, right: Expr) -> Expr {
+        Expr::Add(Box::new(left), Box::new(right))
+    }
+
+    fn fold_sub(&mut self, left: Expr, right: Expr) -> Expr {
+        Expr::Sub(Box::new(left), Box::new(right))
+    }
+
+    fn fold_prod(&mut self, left: Expr, right: Expr) -> Expr {
+        Expr::Prod(Box::new(left), Box::new(right))
+    }
+
+    fn fold_div(&mut self, left: Expr, right: Expr) -> Expr {
+        Expr::Div(Box::new(left), Box::new(right))
+    }
+
+    fn fold_mod(&mut self, left: Expr, right: Expr) -> Expr {
+        Expr::Mod(Box::new(left), Box::new(right))
+    }
+
+    fn fold_pow(&mut self, left: Expr, right: Expr) -> Expr {
+        Expr::Pow(Box::new(left), Box::new(right))
+    }
+
+    fn fold_call(&mut self, name: String, args: Vec<Expr>) -> Expr {
+        Expr::Call(name, args)
+    }
+
+    fn fold_lambda(&mut self, params: Vec<String>, body: Expr) -> Expr {
+        Expr::Lambda(params, Box::new(body))
+    }
+
+    fn fold_list(&mut self, items: Vec<Expr>) -> Expr {
+        Expr::List(items)
+    }
+
+    fn fold_index(&mut self, list: Expr, index: Expr) -> Expr {
+        Expr::Index(Box::new(list), Box::new(index))
+    }
+}
+
+enum Fold {
+    Enter(Expr),
+    // Node whose children were detached, with their number
+    Rebuild(Expr, usize),
+}
+
+pub fn walk_fold<F: Folder + ?Sized>(folder: &mut F, expr: Expr) -> Expr {
+    let mut pending = vec![Fold::Enter(expr)];
+    let mut folded: Vec<Expr> = vec![];
+    while let Some(fold) = pending.pop() {
+        match fold {
+            Fold::Enter(mut expr) => {
+                folder.enter(&expr);
+                let mut children = vec![];
+                expr.detach_children(&mut children);
+                pending.push(Fold::Rebuild(expr, children.len()));
+                pending.extend(children.into_iter().rev().map(Fold::Enter));
+            }
+            Fold::Rebuild(expr, count) => {
+                let children = folded.split_off(folded.len() - count);
+                folded.push(fold_node(folder, expr, children));
+            }
+        }
+    }
+    folded.pop().unwrap()
+}
+
+// Rebuilds `expr` from its folded children
+fn fold_node<F: Folder + ?Sized>(folder: &mut F, mut expr: Expr, children: Vec<Expr>) -> Expr {
+    let mut children = children.into_iter();
+    let mut child = || children.next().unwrap();
+    // `Expr` implements `Drop`, the names are taken out rather than moved out
+    match &mut expr {
+        Expr::Litteral(value) => folder.fold_litteral(*value),
+        Expr::Variable(name) => folder.fold_variable(std::mem::take(name)),
+        Expr::Add(..) => folder.fold_add(child(), child()),
+        Expr::Sub(..) => folder.fold_sub(child(), child()),
+        Expr::Prod(..) => folder.fold_prod(child(), child()),
+        Expr::Div(..) => folder.fold_div(child(), child()),
+        Expr::Mod(..) => folder.fold_mod(child(), child()),
+        Expr::Pow(..) => folder.fold_pow(child(), child()),
+        Expr::Call(name, _) => folder.fold_call(std::mem::take(name), children.collect()),
+        Expr::Lambda(params, _) => folder.fold_lambda(std::mem::take(params), child()),
+        Expr::List(_) => folder.fold_list(children.collect()),
+        Expr::Index(..) => folder.fold_index(child(), child()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    #[derive(Default)]
+    struct Counter {
+        operations: usize,
+        variables: BTreeSet<String>,
+        divisions: Vec<String>,
+    }
+
+    impl Visitor for Counter {
+        fn enter(&mut self, expr: &Expr) -> Walk {
+            if !matches!(expr, Expr::Litteral(_) | Expr::Variable(_)) {
+                self.operations += 1;
+            }
+            visit_node(self, expr)
+        }
+
+        fn visit_variable(&mut self, name: &str) {
+            self.variables.insert(name.to_string());
+        }
+
+        // Divisions whose divisor isn't a non zero constant
+        fn visit_div(&mut self, _left: &Expr, right: &Expr) -> Walk {
+            if !matches!(right, Expr::Litteral(value) if *value != 0) {
+                self.divisions.push(format!("{:?}", right));
+            }
+            Walk::Children
+        }
+    }
+
+    #[test]
+    fn visitor() {
+        let expr = Expr::parse("x / (y - 1) + f(z / 2, 3 / 0)").unwrap();
+        let mut counter = Counter::default();
+        counter.visit_expr(&expr);
+
+        assert_eq!(counter.operations, 6);
+        assert_eq!(
+            counter.variables.into_iter().collect::<Vec<_>>(),
+            vec!["x", "y", "z"]
+        );
+        assert_eq!(counter.divisions, vec!["Sub(y, 1)", "0"]);
+    }
+
+    struct Substitute(&'static str, T);
+
+    impl Folder for Substitute {
+        fn fold_variable(&mut self, name: String) -> Expr {
+            if name == self.0 {
+                Expr::Litteral(self.1)
+            } else {
+                Expr::Variable(name)
+            }
+        }
+    }
+
+    // Folds the operations on constants
+    struct Simplify;
+
+    impl Folder for Simplify {
+        fn fold_add(&mut self, left: Expr, right: Expr) -> Expr {
+            match (left, right) {
+                (Expr::Litteral(left), Expr::Litteral(right)) => Expr::Litteral(left + right),
+                (left, right) => Expr::Add(Box::new(left), Box::new(right)),
+            }
+        }
+
+        fn fold_prod(&mut self, left: Expr, right: Expr) -> Expr {
+            match (left, right) {
+                (Expr::Litteral(left), Expr::Litteral(right)) => Expr::Litteral(left * right),
+                (left, right) => Expr::Prod(Box::new(left), Box::new(right)),
+            }
+        }
+    }
+
+    #[test]
+    fn folder() {
+        let expr = Expr::parse("x * (y + 2) + f(x, z)").unwrap();
+        let expr = Substitute("x", 3).fold_expr(expr);
+        assert_eq!(format!("{:?}", expr), "Add(Prod(3, Add(y, 2)), f(3, z))");
+
+        let expr = Substitute("y", 1).fold_expr(expr);
+        let expr = Simplify.fold_expr(expr);
+        assert_eq!(format!("{:?}", expr), "Add(9, f(3, z))");
+    }
+
+    #[test]
+    fn deep() {
+        let mut expr = Expr::Variable("x".to_string());
+        for _ in 0..300_000 {
+            expr = Expr::Add(Box::new(expr), Box::new(Expr::Litteral(1)));
+        }
+
+        let mut counter = Counter::default();
+        counter.visit_expr(&expr);
+        assert_eq!(counter.operations, 300_000);
+
+        let expr = Simplify.fold_expr(Substitute("x", 2).fold_expr(expr));
+        assert_eq!(format!("{:?}", expr), "300002");
+    }
+}